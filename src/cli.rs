@@ -1,8 +1,10 @@
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use clap_verbosity_flag::Verbosity;
 use regex::Regex;
 
+use crate::hash_ioc::CsvColumns;
 use crate::output_format::OutputFormat;
+use crate::yara::{EvtxMatchMode, EvtxTimestamp, ExternalVariable, FileTypeDetection, MetaCondition, RulePattern};
 
 #[derive(Parser, Clone)]
 #[clap(author, version, about, long_about = None)]
@@ -23,12 +25,66 @@ pub struct Cli {
     output_file: Option<String>,
 
     /// use yara scanner with the specified ruleset. This can be a
-    /// single file, a zip file or a directory containing lots of
-    /// yara files. Yara files must end with 'yar' or 'yara', and zip
-    /// files must end with 'zip'
+    /// single file, a zip file, a file with compiled rules or a directory
+    /// containing lots of yara files. Yara files must end with 'yar' or 'yara',
+    /// and zip files must end with 'zip'
     #[clap(short('Y'), long("yara"), display_order(100))]
     pub(crate) yara: Option<String>,
 
+    /// store the compiled yara rules in the specified file, and load them from there
+    /// on the next run. The rules are compiled again if the rule sources have changed
+    #[clap(long("yara-cache"), display_order(105))]
+    pub(crate) yara_cache: Option<String>,
+
+    /// abort if any of the yara files cannot be compiled. By default, such files
+    /// are skipped and the scan continues with the remaining rules
+    #[clap(long("yara-strict"), display_order(106))]
+    pub(crate) yara_strict: bool,
+
+    /// use only yara rules whose identifier matches PATTERN. PATTERN is a glob,
+    /// or a regular expression if it is enclosed in slashes. It is matched against
+    /// the rule identifier and against '<namespace>:<identifier>'.
+    /// This parameter can be specified multiple times
+    #[clap(long("yara-include-rule"), value_name("PATTERN"), display_order(107))]
+    pub(crate) yara_include_rules: Vec<RulePattern>,
+
+    /// do not use yara rules whose identifier matches PATTERN (see --yara-include-rule).
+    /// This parameter can be specified multiple times
+    #[clap(long("yara-exclude-rule"), value_name("PATTERN"), display_order(107))]
+    pub(crate) yara_exclude_rules: Vec<RulePattern>,
+
+    /// use only yara rules which have the specified tag.
+    /// This parameter can be specified multiple times
+    #[clap(long("yara-include-tag"), value_name("TAG"), display_order(108))]
+    pub(crate) yara_include_tags: Vec<String>,
+
+    /// do not use yara rules which have the specified tag.
+    /// This parameter can be specified multiple times
+    #[clap(long("yara-exclude-tag"), value_name("TAG"), display_order(108))]
+    pub(crate) yara_exclude_tags: Vec<String>,
+
+    /// use only yara rules whose metadata match CONDITION, e.g. 'score>=70' or
+    /// 'author=Florian Roth'. Supported operators are =, !=, <, <=, > and >=.
+    /// This parameter can be specified multiple times
+    #[clap(long("yara-include-meta"), value_name("CONDITION"), display_order(109))]
+    pub(crate) yara_include_meta: Vec<MetaCondition>,
+
+    /// do not use yara rules whose metadata match CONDITION (see --yara-include-meta).
+    /// This parameter can be specified multiple times
+    #[clap(long("yara-exclude-meta"), value_name("CONDITION"), display_order(109))]
+    pub(crate) yara_exclude_meta: Vec<MetaCondition>,
+
+    /// define an external variable for the yara rules, e.g. 'env=prod'. Values are
+    /// booleans ('true' or 'false'), integers, floats or strings.
+    /// This parameter can be specified multiple times
+    #[clap(short('D'), long("define"), value_name("NAME=VALUE"), display_order(111))]
+    pub(crate) yara_externals: Vec<ExternalVariable>,
+
+    /// file with definitions of external variables for the yara rules (one
+    /// 'NAME=VALUE' per line, see --define). Lines starting with '#' are ignored
+    #[clap(long("define-file"), value_name("FILE"), display_order(112))]
+    pub(crate) yara_externals_file: Option<String>,
+
     /// timeout for the yara scanner, in seconds
     #[clap(long("yara-timeout"), default_value_t = 240, display_order(110))]
     pub(crate) yara_timeout: u16,
@@ -37,25 +93,83 @@ pub struct Cli {
     #[clap(short('s'), long("print-strings"), display_order(120))]
     pub(crate) print_strings: bool,
 
-    /// also do YARA scan in Windows EVTX records (exported as JSON)
+    /// also scan Windows EVTX records: yara rules are applied to the records (exported
+    /// as JSON), hash and filename IOCs to process creation events (Sysmon 1, Security 4688)
     #[clap(long("evtx"), display_order(130))]
     #[cfg(feature = "scan_evtx")]
     pub(crate) yara_scan_evtx: bool,
 
-    /// also do YARA scan in Windows registry hive files
+    /// search for evtx chunks in files which are no evtx files, e.g. disk images
+    /// or unallocated space, and scan their records (requires --evtx)
+    #[clap(long("evtx-carve"), display_order(130), requires("yara_scan_evtx"))]
+    #[cfg(feature = "scan_evtx")]
+    pub(crate) evtx_carve: bool,
+
+    /// how evtx records are scanned: 'field' scans every value of a record separately,
+    /// 'json' and 'xml' scan every record as a whole, so that rules can combine
+    /// strings from different fields
+    #[clap(long("evtx-match"), value_name("MODE"), value_enum, default_value_t = EvtxMatchMode::Field, display_order(130))]
+    #[cfg(feature = "scan_evtx")]
+    pub(crate) evtx_match_mode: EvtxMatchMode,
+
+    /// scan only evtx records of the specified channel, e.g. 'Security'.
+    /// This parameter can be specified multiple times
+    #[clap(long("evtx-channel"), value_name("CHANNEL"), display_order(131))]
+    #[cfg(feature = "scan_evtx")]
+    pub(crate) evtx_channels: Vec<String>,
+
+    /// scan only evtx records with the specified EventID.
+    /// This parameter can be specified multiple times
+    #[clap(long("evtx-event-id"), value_name("EVENT_ID"), display_order(131))]
+    #[cfg(feature = "scan_evtx")]
+    pub(crate) evtx_event_ids: Vec<u64>,
+
+    /// scan only evtx records of the specified provider, e.g. 'Microsoft-Windows-Sysmon'.
+    /// This parameter can be specified multiple times
+    #[clap(long("evtx-provider"), value_name("PROVIDER"), display_order(131))]
+    #[cfg(feature = "scan_evtx")]
+    pub(crate) evtx_providers: Vec<String>,
+
+    /// scan only evtx records which have been created at or after TIMESTAMP, e.g.
+    /// '2022-06-17', '2022-06-17 22:51:20' or '2022-06-17T22:51:20+02:00' (default time zone is UTC)
+    #[clap(long("evtx-from"), value_name("TIMESTAMP"), display_order(131))]
+    #[cfg(feature = "scan_evtx")]
+    pub(crate) evtx_from: Option<EvtxTimestamp>,
+
+    /// scan only evtx records which have been created at or before TIMESTAMP (see --evtx-from)
+    #[clap(long("evtx-to"), value_name("TIMESTAMP"), display_order(131))]
+    #[cfg(feature = "scan_evtx")]
+    pub(crate) evtx_to: Option<EvtxTimestamp>,
+
+    /// also scan Windows registry hive files: yara rules are applied to keys and values,
+    /// filename IOCs to the files which are started by autostart locations (Run keys, services, etc.)
     #[clap(long("reg"), display_order(130))]
     #[cfg(feature = "scan_reg")]
     pub(crate) yara_scan_reg: bool,
 
-    /// allow yara to scan compressed files. Currently, xz, bz2 and gz are supported
+    /// recover deleted keys and values from unallocated cells of registry hives
+    /// and scan them as well (requires --reg)
+    #[clap(long("reg-carve"), display_order(130), requires("yara_scan_reg"))]
+    #[cfg(feature = "scan_reg")]
+    pub(crate) reg_carve: bool,
+
+    /// allow yara to scan compressed files and archives. Currently, zip, 7z, tar, xz, bz2, gz, zstd, lz4, lzma and Z (compress) are supported
     #[clap(short('C'), long("scan-compressed"), display_order(140))]
     pub(crate) scan_compressed: bool,
 
+    /// how the types of files are detected: 'magic' uses libmagic and falls back to the
+    /// built-in signatures if libmagic is not available, 'signatures' uses only the built-in signatures
+    #[clap(long("file-type-detection"), value_name("MODE"), value_enum, default_value_t = FileTypeDetection::Magic, display_order(140))]
+    pub(crate) file_type_detection: FileTypeDetection,
+
     /// do not scan files whose names match the specified regular expression (case sensitive match)
     #[clap(long("exclude-pattern"), display_order(150))]
     pub(crate) exclude_pattern: Option<Regex>,
 
-    /// maximum size (in MiB) of decompression buffer (per thread), which is used to scan compressed files
+    /// maximum size (in MiB) of decompression buffer (per thread), which is used to scan compressed files.
+    /// Larger data is scanned in overlapping windows of this size. Then, yara's `filesize` is the size
+    /// of the window, `md5`, `sha1` and `sha256` are unknown ('-'), and `total_size` is the size of the
+    /// complete data, or -1 if it is unknown
     #[clap(
         long("decompression-buffer"),
         default_value_t = 128,
@@ -63,11 +177,55 @@ pub struct Cli {
     )]
     pub(crate) decompression_buffer_size: usize,
 
+    /// overlap (in KiB) of the windows which are used to scan decompressed data
+    /// that does not fit into the decompression buffer
+    #[clap(long("window-overlap"), default_value_t = 1024, display_order(150))]
+    pub(crate) window_overlap: usize,
+
+    /// maximum number of nested archives and compressed streams which are unpacked
+    /// (e.g. a gzip file inside of a zip archive counts as two levels)
+    #[clap(long("nesting-depth"), default_value_t = 5, display_order(150))]
+    pub(crate) nesting_depth: usize,
+
+    /// password which is tried to open encrypted zip and 7z archives.
+    /// This option can be specified multiple times
+    #[clap(long("password"), value_name("PASSWORD"), display_order(150))]
+    pub(crate) passwords: Vec<String>,
+
+    /// file with passwords (one per line), which are tried to open encrypted zip and 7z archives
+    #[clap(long("password-file"), value_name("FILE"), display_order(150))]
+    pub(crate) password_file: Option<String>,
+
+    /// scan the memory of all running processes with yara (Linux only).
+    /// If no path is specified, no files are scanned
+    #[clap(long("processes"), display_order(160))]
+    #[cfg(target_os = "linux")]
+    pub(crate) scan_processes: bool,
+
+    /// scan the memory of the process with the specified PID with yara (Linux only).
+    /// If no path is specified, no files are scanned.
+    /// This parameter can be specified multiple times
+    #[clap(long("pid"), display_order(161))]
+    #[cfg(target_os = "linux")]
+    pub(crate) pids: Vec<u32>,
+
     /// Hash of file to match against. Use any of MD5, SHA1 or SHA256.
     /// This parameter can be specified multiple times
     #[clap(short('H'), long("file-hash"), display_order(200))]
     pub(crate) file_hash: Vec<String>,
 
+    /// file with hash IOCs. '.csv' files need a header line (see --hash-csv-columns),
+    /// '.json' files contain a list of hashes or of objects with 'hash' (or 'md5', 'sha1', 'sha256'),
+    /// 'name' and 'description'. All other files contain one hash per line, optionally followed by
+    /// the name of the IOC. Lines starting with '#' are ignored.
+    /// This parameter can be specified multiple times
+    #[clap(long("hash-file"), value_name("FILE"), display_order(200))]
+    pub(crate) hash_files: Vec<String>,
+
+    /// columns of CSV hash files which contain the hash, the name and the description of the IOCs
+    #[clap(long("hash-csv-columns"), value_name("HASH[,NAME[,DESCRIPTION]]"), default_value("hash,name,description"), display_order(201))]
+    pub(crate) hash_csv_columns: CsvColumns,
+
     /// regular expression to match against the basename of files.
     /// This parameter can be specified multiple times
     #[clap(short('F'), long("filename"), display_order(210))]
@@ -98,14 +256,51 @@ impl Default for Cli {
             path: Default::default(),
             output_format: OutputFormat::Csv,
             yara: Default::default(),
+            yara_cache: Default::default(),
+            yara_strict: Default::default(),
+            yara_include_rules: Default::default(),
+            yara_exclude_rules: Default::default(),
+            yara_include_tags: Default::default(),
+            yara_exclude_tags: Default::default(),
+            yara_include_meta: Default::default(),
+            yara_exclude_meta: Default::default(),
+            yara_externals: Default::default(),
+            yara_externals_file: Default::default(),
             yara_timeout: Default::default(),
             print_strings: Default::default(),
             yara_scan_evtx: Default::default(),
+            #[cfg(feature = "scan_evtx")]
+            evtx_carve: Default::default(),
+            #[cfg(feature = "scan_evtx")]
+            evtx_match_mode: Default::default(),
+            #[cfg(feature = "scan_evtx")]
+            evtx_channels: Default::default(),
+            #[cfg(feature = "scan_evtx")]
+            evtx_event_ids: Default::default(),
+            #[cfg(feature = "scan_evtx")]
+            evtx_providers: Default::default(),
+            #[cfg(feature = "scan_evtx")]
+            evtx_from: Default::default(),
+            #[cfg(feature = "scan_evtx")]
+            evtx_to: Default::default(),
             yara_scan_reg: Default::default(),
+            #[cfg(feature = "scan_reg")]
+            reg_carve: Default::default(),
             scan_compressed: Default::default(),
+            file_type_detection: Default::default(),
             exclude_pattern: Default::default(),
             decompression_buffer_size: 128,
+            window_overlap: 1024,
+            nesting_depth: 5,
+            passwords: Default::default(),
+            password_file: Default::default(),
+            #[cfg(target_os = "linux")]
+            scan_processes: Default::default(),
+            #[cfg(target_os = "linux")]
+            pids: Default::default(),
             file_hash: Default::default(),
+            hash_files: Default::default(),
+            hash_csv_columns: Default::default(),
             filenames: Default::default(),
             levenshtein: Default::default(),
             threads: num_cpus::get(),
@@ -127,16 +322,88 @@ impl Cli {
         self
     }
 
+    pub fn with_yara_cache(mut self, yara_cache: String) -> Self {
+        self.yara_cache = Some(yara_cache);
+        self
+    }
+
+    pub fn with_yara_strict(mut self, yara_strict: bool) -> Self {
+        self.yara_strict = yara_strict;
+        self
+    }
+
+    pub fn with_yara_include_tag(mut self, tag: &str) -> Self {
+        self.yara_include_tags.push(tag.to_owned());
+        self
+    }
+
+    pub fn with_yara_exclude_rule(mut self, pattern: &str) -> Self {
+        self.yara_exclude_rules.push(pattern.parse().unwrap());
+        self
+    }
+
+    pub fn with_yara_include_meta(mut self, condition: &str) -> Self {
+        self.yara_include_meta.push(condition.parse().unwrap());
+        self
+    }
+
+    pub fn with_yara_external(mut self, definition: &str) -> Self {
+        self.yara_externals.push(definition.parse().unwrap());
+        self
+    }
+
+    pub fn with_yara_externals_file(mut self, file: String) -> Self {
+        self.yara_externals_file = Some(file);
+        self
+    }
+
     pub fn with_yara_evtx(mut self, use_evtx: bool) -> Self {
         self.yara_scan_evtx = use_evtx;
         self
     }
 
+    #[cfg(feature = "scan_evtx")]
+    pub fn with_evtx_carve(mut self, evtx_carve: bool) -> Self {
+        self.evtx_carve = evtx_carve;
+        self
+    }
+
+    #[cfg(feature = "scan_evtx")]
+    pub fn with_evtx_match_mode(mut self, mode: &str) -> Self {
+        self.evtx_match_mode = EvtxMatchMode::from_str(mode, true).unwrap();
+        self
+    }
+
+    #[cfg(feature = "scan_evtx")]
+    pub fn with_evtx_channel(mut self, channel: &str) -> Self {
+        self.evtx_channels.push(channel.to_owned());
+        self
+    }
+
+    #[cfg(feature = "scan_evtx")]
+    pub fn with_evtx_event_id(mut self, event_id: u64) -> Self {
+        self.evtx_event_ids.push(event_id);
+        self
+    }
+
+    #[cfg(feature = "scan_evtx")]
+    pub fn with_evtx_time_range(mut self, from: Option<&str>, to: Option<&str>) -> Self {
+        self.evtx_from = from.map(|t| t.parse().unwrap());
+        self.evtx_to = to.map(|t| t.parse().unwrap());
+        self
+    }
+
     pub fn with_yara_reg(mut self, use_reg: bool) -> Self {
         self.yara_scan_reg = use_reg;
         self
     }
 
+    #[cfg(feature = "scan_reg")]
+    pub fn with_reg_carve(mut self, reg_carve: bool) -> Self {
+        self.reg_carve = reg_carve;
+        self
+    }
+
     pub fn with_format(mut self, format: OutputFormat) -> Self {
         self.output_format = format;
         self
@@ -156,11 +423,52 @@ impl Cli {
         self
     }
 
+    pub fn with_file_type_detection(mut self, detection: &str) -> Self {
+        self.file_type_detection = FileTypeDetection::from_str(detection, true).unwrap();
+        self
+    }
+
+    pub fn with_decompression_buffer_size(mut self, decompression_buffer_size: usize) -> Self {
+        self.decompression_buffer_size = decompression_buffer_size;
+        self
+    }
+
+    pub fn with_window_overlap(mut self, window_overlap: usize) -> Self {
+        self.window_overlap = window_overlap;
+        self
+    }
+
+    pub fn with_nesting_depth(mut self, nesting_depth: usize) -> Self {
+        self.nesting_depth = nesting_depth;
+        self
+    }
+
+    pub fn with_password(mut self, password: &str) -> Self {
+        self.passwords.push(password.to_owned());
+        self
+    }
+
+    #[cfg(target_os = "linux")]
+    pub fn with_pid(mut self, pid: u32) -> Self {
+        self.pids.push(pid);
+        self
+    }
+
     pub fn with_hash(mut self, hash: &str) -> Self {
         self.file_hash.push(hash.to_owned());
         self
     }
 
+    pub fn with_hash_file(mut self, file: String) -> Self {
+        self.hash_files.push(file);
+        self
+    }
+
+    pub fn with_hash_csv_columns(mut self, columns: &str) -> Self {
+        self.hash_csv_columns = columns.parse().unwrap();
+        self
+    }
+
     pub fn with_filename(mut self, filename: &str) -> Self {
         self.filenames.push(filename.to_owned());
         self
@@ -169,4 +477,13 @@ impl Cli {
     pub fn output_file(&self) -> Option<&String> {
         self.output_file.as_ref()
     }
+
+    /// returns `true` if the memory of processes must be scanned
+    pub(crate) fn scan_processes(&self) -> bool {
+        #[cfg(target_os = "linux")]
+        return self.scan_processes || !self.pids.is_empty();
+
+        #[cfg(not(target_os = "linux"))]
+        return false;
+    }
 }
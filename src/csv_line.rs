@@ -1,4 +1,5 @@
 use serde::{Serialize, Deserialize};
+use serde_json::Value;
 
 #[derive(PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct CsvLine {
@@ -6,6 +7,10 @@ pub struct CsvLine {
     rule_name: String,
     found_in_file: String,
     details: String,
+
+    /// scanner specific information about the finding, e.g. the archive member
+    /// or the evtx record which matched. This is a JSON object, or empty
+    context: String,
 }
 
 impl CsvLine {
@@ -14,10 +19,16 @@ impl CsvLine {
             scanner_name: scanner_name.to_owned(),
             rule_name: rule_name.to_owned(),
             found_in_file: found_in_file.to_owned(),
-            details
+            details,
+            context: String::new(),
         }
     }
 
+    pub fn with_context(mut self, context: Value) -> Self {
+        self.context = context.to_string();
+        self
+    }
+
     pub fn found_in_file(&self) -> &str {
         &self.found_in_file[..]
     }
@@ -5,7 +5,7 @@ use simplelog::{
 };
 use std::fs::{File, OpenOptions};
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::Instant;
 use walkdir::WalkDir;
@@ -13,10 +13,22 @@ use walkdir::WalkDir;
 use crate::cli::Cli;
 use crate::filename_scanner::FilenameScanner;
 use crate::filescanner::*;
+use crate::hash_ioc::{load_hash_iocs, HashIoc};
 use crate::hash_scanner::HashScanner;
 use crate::levenshtein_scanner::LevenshteinScanner;
+use crate::process_info::ProcessInfo;
+use crate::processscanner::ProcessScanner;
 use crate::scanner_result::ScannerResult;
-use crate::yara::YaraScanner;
+use crate::yara::{ExternalVariable, YaraRuleFilter, YaraRulesLoader, YaraScanner};
+
+#[cfg(feature = "scan_evtx")]
+use crate::evtx_ioc_scanner::EvtxIocScanner;
+
+#[cfg(feature = "scan_evtx")]
+use crate::yara::EvtxFilter;
+
+#[cfg(feature = "scan_reg")]
+use crate::registry_ioc_scanner::RegistryIocScanner;
 
 use rayon::{prelude::*, current_thread_index};
 
@@ -47,8 +59,11 @@ fn set_display_strings(val: bool) {
     unsafe { DISPLAY_STRINGS = val }
 }
 
+type FileScanners = Arc<Vec<Box<dyn FileScanner>>>;
+type ProcessScanners = Arc<Vec<Arc<dyn ProcessScanner>>>;
+
 pub struct Dionysos {
-    path: PathBuf,
+    path: Option<PathBuf>,
     loglevel: LevelFilter,
     yara_rules: Option<PathBuf>,
     filenames: Vec<regex::Regex>,
@@ -95,6 +110,45 @@ fn handle_file(
     result
 }
 
+fn handle_process(scanners: &ProcessScanners, pid: u32) -> ScannerResult {
+    let mut result = ScannerResult::new();
+
+    // the process might have been terminated in the meantime
+    let process = match ProcessInfo::from_pid(pid) {
+        Ok(process) => process,
+        Err(why) => {
+            log::warn!("{}", why);
+            return result;
+        }
+    };
+
+    for scanner in scanners.iter() {
+        log::trace!("starting {} on {}", scanner, process);
+        let begin = Instant::now();
+
+        for res in scanner.scan_process(&process).into_iter() {
+            match res {
+                Err(why) => {
+                    log::error!("{}", why);
+                }
+
+                Ok(res) => {
+                    log::trace!("new finding from {} for {}", scanner, process);
+                    result.add_finding(res);
+                }
+            }
+        }
+
+        log::trace!(
+            "finished {} on {} in {}s",
+            scanner,
+            process,
+            Instant::now().duration_since(begin).as_secs_f64()
+        );
+    }
+    result
+}
+
 impl Dionysos {
     pub fn new(cli: Cli) -> Result<Self> {
         Self::parse_options(cli)
@@ -106,13 +160,14 @@ impl Dionysos {
 
         log::info!("running dionysos version {}", env!("CARGO_PKG_VERSION"));
 
-        let scanners = self.init_scanners()?;
+        let (scanners, process_scanners) = self.init_scanners()?;
         
         /* this closure does all the work, and will be running inside of a thread pool */
         let pooled_operation = || match self.cli.output_file() {
-            None => self.scan_to_output(scanners, std::io::stdout()),
+            None => self.scan_to_output(scanners, process_scanners, std::io::stdout()),
             Some(filename) => self.scan_to_output(
                 scanners,
+                process_scanners,
                 File::create(filename).expect("unable to write to destination file"),
             ),
         };
@@ -140,7 +195,12 @@ impl Dionysos {
         Ok(())
     }
 
-    fn scan_to_output<W: Write + Send>(&self, scanners: Arc<Vec<Box<dyn FileScanner>>>, output: W) {
+    fn scan_to_output<W: Write + Send>(
+        &self,
+        scanners: FileScanners,
+        process_scanners: ProcessScanners,
+        output: W,
+    ) {
         let output = self.cli.output_format.to_options(output);
         let filename_filter = |e: &walkdir::DirEntry| {
             match self.cli.exclude_pattern.as_ref() {
@@ -154,7 +214,39 @@ impl Dionysos {
             }
         };  
 
-        WalkDir::new(&self.path)
+        if !process_scanners.is_empty() {
+            let pids = match self.process_ids() {
+                Ok(pids) => pids,
+                Err(why) => {
+                    log::error!("unable to enumerate processes: {}", why);
+                    Vec::new()
+                }
+            };
+
+            pids.into_par_iter().for_each(|pid| {
+                log::info!("scanning process {}", pid);
+
+                if PROGRESS.is_set() {
+                    PROGRESS.with(|pbs| {
+                        let idx = current_thread_index().unwrap();
+                        pbs.spinner_bars[idx].set_message(format!("process {pid}"));
+                    });
+                }
+
+                let result = handle_process(&process_scanners, pid);
+
+                if result.has_findings() {
+                    output.print_result(&result);
+                }
+            });
+        }
+
+        let path = match &self.path {
+            Some(path) => path,
+            None => return,
+        };
+
+        WalkDir::new(path)
             .into_iter()
             .filter_map(|e| e.ok())
             .filter(|e| e.file_type().is_file())
@@ -196,7 +288,10 @@ impl Dionysos {
                     "[{elapsed_precise}] {bar:32.cyan/blue} {pos:>9}/{len:9}({percent}%) {msg}",
                 )?
                 .progress_chars("##-");
-            let count = WalkDir::new(&self.path).into_iter().count();
+            let count = match &self.path {
+                Some(path) => WalkDir::new(path).into_iter().count(),
+                None => 0,
+            };
             let progress = m_progress.add(ProgressBar::new(count as u64));
             progress.set_style(progress_style);
 
@@ -221,40 +316,180 @@ impl Dionysos {
         }
     }
 
-    fn init_scanners(&self) -> Result<Arc<Vec<Box<dyn FileScanner>>>> {
+    fn process_ids(&self) -> Result<Vec<u32>> {
+        #[cfg(target_os = "linux")]
+        if !self.cli.pids.is_empty() {
+            return Ok(self.cli.pids.clone());
+        }
+        ProcessInfo::all_pids()
+    }
+
+    /// returns the external variables specified with `-D`, followed
+    /// by the definitions in the file specified with `--define-file`
+    fn yara_externals(&self) -> Result<Vec<ExternalVariable>> {
+        let mut externals = self.cli.yara_externals.clone();
+        if let Some(externals_file) = &self.cli.yara_externals_file {
+            let content = std::fs::read_to_string(externals_file)
+                .map_err(|why| anyhow!("unable to read file '{externals_file}': {why}"))?;
+            for (idx, line) in content.lines().enumerate() {
+                let line = line.trim();
+                if line.is_empty() || line.starts_with('#') {
+                    continue;
+                }
+                externals.push(
+                    line.parse()
+                        .map_err(|why| anyhow!("{externals_file}, line {}: {why}", idx + 1))?,
+                );
+            }
+        }
+        Ok(externals)
+    }
+
+    #[cfg(feature = "scan_evtx")]
+    fn evtx_filter(&self) -> EvtxFilter {
+        EvtxFilter::default()
+            .with_channels(self.cli.evtx_channels.clone())
+            .with_event_ids(self.cli.evtx_event_ids.clone())
+            .with_providers(self.cli.evtx_providers.clone())
+            .with_time_range(self.cli.evtx_from, self.cli.evtx_to)
+    }
+
+    fn hash_iocs(&self) -> Result<Vec<HashIoc>> {
+        let mut iocs = Vec::new();
+        for hash_file in self.cli.hash_files.iter() {
+            iocs.extend(load_hash_iocs(Path::new(hash_file), &self.cli.hash_csv_columns)?);
+        }
+        Ok(iocs)
+    }
+
+    /// returns the passwords specified with `--password`, followed
+    /// by the contents of the password file
+    fn passwords(&self) -> Result<Vec<String>> {
+        let mut passwords = self.cli.passwords.clone();
+        if let Some(password_file) = &self.cli.password_file {
+            let content = std::fs::read_to_string(password_file)
+                .map_err(|why| anyhow!("unable to read password file '{password_file}': {why}"))?;
+            passwords.extend(
+                content
+                    .lines()
+                    .filter(|line| !line.is_empty())
+                    .map(|line| line.to_owned()),
+            );
+        }
+        Ok(passwords)
+    }
+
+    fn init_scanners(&self) -> Result<(FileScanners, ProcessScanners)> {
         let mut scanners: Vec<Box<dyn FileScanner>> = Vec::new();
+        #[cfg_attr(not(target_os = "linux"), allow(unused_mut))]
+        let mut process_scanners: Vec<Arc<dyn ProcessScanner>> = Vec::new();
 
         if let Some(ref yara_rules) = self.yara_rules {
-            let yara_scanner = YaraScanner::new(yara_rules)?
+            let externals = self.yara_externals()?;
+            let (rules, references) = YaraRulesLoader::new(yara_rules)
+                .with_cache_file(self.cli.yara_cache.as_ref().map(PathBuf::from))
+                .with_strict(self.cli.yara_strict)
+                .with_externals(externals.clone())
+                .load()?;
+
+            let rule_filter = YaraRuleFilter::default()
+                .with_rules(self.cli.yara_include_rules.clone(), self.cli.yara_exclude_rules.clone())
+                .with_tags(self.cli.yara_include_tags.clone(), self.cli.yara_exclude_tags.clone())
+                .with_meta(self.cli.yara_include_meta.clone(), self.cli.yara_exclude_meta.clone());
+
+            let yara_scanner = YaraScanner::new(rules)
+                .with_rule_filter(&rule_filter)
+                .with_rule_references(references)
                 .with_scan_compressed(self.cli.scan_compressed)
+                .with_file_type_detection(self.cli.file_type_detection)
                 .with_buffer_size(self.cli.decompression_buffer_size)
+                .with_window_overlap(self.cli.window_overlap)
+                .with_max_depth(self.cli.nesting_depth)
+                .with_passwords(self.passwords()?)
+                .with_externals(externals)
                 .with_timeout(self.cli.yara_timeout);
 
             #[cfg(feature = "scan_evtx")]
-            let yara_scanner = yara_scanner.with_scan_evtx(self.cli.yara_scan_evtx);
+            let yara_scanner = yara_scanner
+                .with_scan_evtx(self.cli.yara_scan_evtx)
+                .with_evtx_carve(self.cli.evtx_carve)
+                .with_evtx_match_mode(self.cli.evtx_match_mode)
+                .with_evtx_filter(self.evtx_filter());
 
             #[cfg(feature = "scan_reg")]
-            let yara_scanner = yara_scanner.with_scan_reg(self.cli.yara_scan_reg);
-
+            let yara_scanner = yara_scanner
+                .with_scan_reg(self.cli.yara_scan_reg)
+                .with_reg_carve(self.cli.reg_carve);
+
+            let yara_scanner = Arc::new(yara_scanner);
+            #[cfg(target_os = "linux")]
+            if self.cli.scan_processes() {
+                process_scanners.push(Arc::clone(&yara_scanner) as Arc<dyn ProcessScanner>);
+            }
             scanners.push(Box::new(yara_scanner));
+        } else if self.cli.scan_processes() {
+            return Err(anyhow!("scanning processes requires yara rules, use '-Y'"));
         };
 
+        // hash and filename IOCs are also applied to process creation events in evtx files
+        #[cfg(feature = "scan_evtx")]
+        let mut evtx_ioc_scanner = EvtxIocScanner::default().with_evtx_filter(self.evtx_filter());
+
+        // filename IOCs are also applied to files which are started by the registry
+        #[cfg(feature = "scan_reg")]
+        let mut registry_ioc_scanner = RegistryIocScanner::default();
+
         if !self.filenames.is_empty() {
-            let filename_scanner = FilenameScanner::new(self.filenames.clone());
+            let filename_scanner = Arc::new(FilenameScanner::new(self.filenames.clone()));
+            #[cfg(feature = "scan_evtx")]
+            {
+                evtx_ioc_scanner = evtx_ioc_scanner.with_filename_scanner(Arc::clone(&filename_scanner));
+            }
+            #[cfg(feature = "scan_reg")]
+            {
+                registry_ioc_scanner = registry_ioc_scanner.with_filename_scanner(Arc::clone(&filename_scanner));
+            }
             scanners.push(Box::new(filename_scanner));
         }
 
         if self.cli.levenshtein {
-            let levenshtein_scanner = LevenshteinScanner::default();
+            let levenshtein_scanner = Arc::new(LevenshteinScanner::default());
+            #[cfg(feature = "scan_evtx")]
+            {
+                evtx_ioc_scanner = evtx_ioc_scanner.with_levenshtein_scanner(Arc::clone(&levenshtein_scanner));
+            }
+            #[cfg(feature = "scan_reg")]
+            {
+                registry_ioc_scanner =
+                    registry_ioc_scanner.with_levenshtein_scanner(Arc::clone(&levenshtein_scanner));
+            }
             scanners.push(Box::new(levenshtein_scanner));
         }
 
-        if !self.cli.file_hash.is_empty() {
-            let hash_scanner = HashScanner::default().with_hashes(&self.cli.file_hash)?;
+        if !self.cli.file_hash.is_empty() || !self.cli.hash_files.is_empty() {
+            let hash_scanner = Arc::new(
+                HashScanner::default()
+                    .with_hashes(&self.cli.file_hash)?
+                    .with_hash_iocs(self.hash_iocs()?),
+            );
+            #[cfg(feature = "scan_evtx")]
+            {
+                evtx_ioc_scanner = evtx_ioc_scanner.with_hash_scanner(Arc::clone(&hash_scanner));
+            }
             scanners.push(Box::new(hash_scanner));
         }
 
-        Ok(Arc::new(scanners))
+        #[cfg(feature = "scan_evtx")]
+        if self.cli.yara_scan_evtx && evtx_ioc_scanner.has_iocs() {
+            scanners.push(Box::new(evtx_ioc_scanner));
+        }
+
+        #[cfg(feature = "scan_reg")]
+        if self.cli.yara_scan_reg && registry_ioc_scanner.has_iocs() {
+            scanners.push(Box::new(registry_ioc_scanner));
+        }
+
+        Ok((Arc::new(scanners), Arc::new(process_scanners)))
     }
 
     fn init_logging(&self) -> Result<()> {
@@ -293,13 +528,16 @@ impl Dionysos {
 
     fn parse_options(cli: Cli) -> Result<Self> {
         let path = match &cli.path {
-            Some(path) => PathBuf::from(&path),
+            Some(path) => Some(PathBuf::from(&path)),
+
+            // when scanning processes, files are only scanned if a path was specified
+            None if cli.scan_processes() => None,
 
             #[cfg(target_os = "windows")]
-            None => PathBuf::from("\\"),
+            None => Some(PathBuf::from("\\")),
 
             #[cfg(not(target_os = "windows"))]
-            None => PathBuf::from("/"),
+            None => Some(PathBuf::from("/")),
         };
 
         let yara_rules = match &cli.yara {
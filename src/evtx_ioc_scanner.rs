@@ -0,0 +1,365 @@
+use std::collections::HashSet;
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+use std::sync::Arc;
+
+use chrono::SecondsFormat;
+use evtx::ParserSettings;
+use maplit::hashset;
+use serde_json::{json, Value};
+use walkdir::DirEntry;
+
+use crate::csv_line::CsvLine;
+use crate::filename_scanner::FilenameScanner;
+use crate::filescanner::FileScanner;
+use crate::hash_scanner::{CryptoHash, HashScanner};
+use crate::levenshtein_scanner::LevenshteinScanner;
+use crate::scanner_result::ScannerFinding;
+use crate::yara::{EvtxChunkReader, EvtxFilter, EvtxRecordInfo};
+
+const EVTX_FILE_SIGNATURE: &[u8] = b"ElfFile\0";
+
+/// applies hash and filename IOCs to process creation events in evtx files.
+/// Such events are evidence of execution, even if the executed file has been
+/// deleted in the meantime.
+#[derive(Default)]
+pub struct EvtxIocScanner {
+    hash_scanner: Option<Arc<HashScanner>>,
+    filename_scanner: Option<Arc<FilenameScanner>>,
+    levenshtein_scanner: Option<Arc<LevenshteinScanner>>,
+    filter: EvtxFilter,
+}
+
+/// the executed file of a process creation event
+struct ProcessCreation {
+    image: String,
+    image_field: &'static str,
+
+    /// hashes of the executed file, which are only logged by sysmon
+    hashes: Vec<CryptoHash>,
+}
+
+impl ProcessCreation {
+    fn from_record(record: &EvtxRecordInfo, data: &Value) -> Option<Self> {
+        let event_data = &data["Event"]["EventData"];
+        let provider = record.provider.as_deref().unwrap_or_default();
+        match (provider, record.event_id) {
+            ("Microsoft-Windows-Sysmon", Some(1)) => Some(Self {
+                image: event_data["Image"].as_str()?.to_owned(),
+                image_field: "Event.EventData.Image",
+                hashes: event_data["Hashes"]
+                    .as_str()
+                    .map(Self::parse_sysmon_hashes)
+                    .unwrap_or_default(),
+            }),
+            ("Microsoft-Windows-Security-Auditing", Some(4688)) => Some(Self {
+                image: event_data["NewProcessName"].as_str()?.to_owned(),
+                image_field: "Event.EventData.NewProcessName",
+                hashes: Vec::new(),
+            }),
+            _ => None,
+        }
+    }
+
+    /// parses hashes like `SHA1=...,MD5=...,SHA256=...,IMPHASH=...`. Import hashes
+    /// are ignored, because they cannot be distinguished from MD5 hashes by their length
+    fn parse_sysmon_hashes(hashes: &str) -> Vec<CryptoHash> {
+        hashes
+            .split(',')
+            .filter_map(|h| h.split_once('='))
+            .filter(|(algorithm, _)| {
+                ["MD5", "SHA1", "SHA256"].iter().any(|a| a.eq_ignore_ascii_case(algorithm.trim()))
+            })
+            .filter_map(|(_, value)| HashScanner::parse_hash(value.trim()).ok())
+            .collect()
+    }
+
+    /// the image path uses Windows path separators, regardless of the platform we are running on
+    fn file_name(&self) -> &str {
+        self.image.rsplit(['\\', '/']).next().unwrap_or(&self.image)
+    }
+}
+
+impl EvtxIocScanner {
+    pub fn with_hash_scanner(mut self, hash_scanner: Arc<HashScanner>) -> Self {
+        self.hash_scanner = Some(hash_scanner);
+        self
+    }
+
+    pub fn with_filename_scanner(mut self, filename_scanner: Arc<FilenameScanner>) -> Self {
+        self.filename_scanner = Some(filename_scanner);
+        self
+    }
+
+    pub fn with_levenshtein_scanner(mut self, levenshtein_scanner: Arc<LevenshteinScanner>) -> Self {
+        self.levenshtein_scanner = Some(levenshtein_scanner);
+        self
+    }
+
+    pub fn with_evtx_filter(mut self, filter: EvtxFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    pub fn has_iocs(&self) -> bool {
+        self.hash_scanner.is_some() || self.filename_scanner.is_some() || self.levenshtein_scanner.is_some()
+    }
+
+    fn is_evtx_file(file: &Path) -> std::io::Result<bool> {
+        let mut signature = [0u8; EVTX_FILE_SIGNATURE.len()];
+        match File::open(file)?.read_exact(&mut signature) {
+            Ok(()) => Ok(signature == EVTX_FILE_SIGNATURE),
+            Err(why) if why.kind() == std::io::ErrorKind::UnexpectedEof => Ok(false),
+            Err(why) => Err(why),
+        }
+    }
+
+    fn scan_evtx(&self, file: &Path) -> anyhow::Result<Vec<EvtxIocFinding>> {
+        let filename = file.display().to_string();
+        let settings = Arc::new(ParserSettings::default());
+        let mut results = Vec::new();
+        let mut damaged_records = 0;
+
+        for candidate in EvtxChunkReader::from_evtx_file(BufReader::new(File::open(file)?)) {
+            let mut chunk_data = match candidate?.chunk {
+                Ok(chunk_data) => chunk_data,
+                Err(_) => {
+                    damaged_records += 1;
+                    continue;
+                }
+            };
+            let mut chunk = match chunk_data.parse(Arc::clone(&settings)) {
+                Ok(chunk) => chunk,
+                Err(_) => {
+                    damaged_records += 1;
+                    continue;
+                }
+            };
+
+            for record in chunk.iter() {
+                let record = match record {
+                    Ok(record) => record.into_json_value(),
+                    Err(why) => Err(why),
+                };
+                let record = match record {
+                    Ok(record) => record,
+                    Err(_) => {
+                        damaged_records += 1;
+                        continue;
+                    }
+                };
+                if !self.filter.matches_timestamp(&record.timestamp) {
+                    continue;
+                }
+                let info = EvtxRecordInfo::new(
+                    record.event_record_id,
+                    record.timestamp.to_rfc3339_opts(SecondsFormat::Micros, true),
+                    &record.data,
+                );
+                if !self.filter.matches(&info) {
+                    continue;
+                }
+                if let Some(process) = ProcessCreation::from_record(&info, &record.data) {
+                    results.extend(self.scan_process_creation(&process, &info, &filename));
+                }
+            }
+        }
+
+        if damaged_records > 0 {
+            log::warn!("skipped {damaged_records} damaged chunks or records of '{filename}'");
+        }
+        Ok(results)
+    }
+
+    fn scan_process_creation(
+        &self,
+        process: &ProcessCreation,
+        record: &EvtxRecordInfo,
+        filename: &str,
+    ) -> Vec<EvtxIocFinding> {
+        let finding = |ioc: EvtxIoc, field: &str| EvtxIocFinding {
+            ioc,
+            image: process.image.clone(),
+            found_in_file: filename.to_owned(),
+            record: record.clone().with_field(field.to_owned()),
+        };
+        let mut results = Vec::new();
+
+        if let Some(hash_scanner) = &self.hash_scanner {
+            results.extend(
+                process
+                    .hashes
+                    .iter()
+                    .filter(|h| hash_scanner.contains(h))
+                    .map(|h| finding(EvtxIoc::Hash(h.clone()), "Event.EventData.Hashes")),
+            );
+        }
+
+        if let Some(filename_scanner) = &self.filename_scanner {
+            results.extend(
+                filename_scanner
+                    .matching_patterns(process.file_name())
+                    .map(|p| finding(EvtxIoc::Filename(p.clone()), process.image_field)),
+            );
+        }
+
+        if let Some(levenshtein_scanner) = &self.levenshtein_scanner {
+            results.extend(
+                levenshtein_scanner
+                    .similar_names(process.file_name())
+                    .into_iter()
+                    .map(|n| finding(EvtxIoc::Levenshtein(n), process.image_field)),
+            );
+        }
+        results
+    }
+}
+
+impl Display for EvtxIocScanner {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "EvtxIocScanner")
+    }
+}
+
+impl FileScanner for EvtxIocScanner {
+    fn scan_file(&self, file: &DirEntry) -> Vec<anyhow::Result<Box<dyn ScannerFinding>>> {
+        let file = file.path();
+        match Self::is_evtx_file(file) {
+            Ok(false) => vec![],
+            Err(why) => vec![Err(anyhow::anyhow!("unable to read '{}': {why}", file.display()))],
+            Ok(true) => match self.scan_evtx(file) {
+                Err(why) => vec![Err(anyhow::anyhow!("evtx scan error with '{}': {why}", file.display()))],
+                Ok(findings) => findings
+                    .into_iter()
+                    .map(|f| Ok(Box::new(f) as Box<dyn ScannerFinding>))
+                    .collect(),
+            },
+        }
+    }
+}
+
+enum EvtxIoc {
+    Hash(CryptoHash),
+    Filename(regex::Regex),
+    Levenshtein(String),
+}
+
+struct EvtxIocFinding {
+    ioc: EvtxIoc,
+    image: String,
+    found_in_file: String,
+    record: EvtxRecordInfo,
+}
+
+impl Display for EvtxIocFinding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let image = &self.image;
+        match &self.ioc {
+            EvtxIoc::Hash(hash) => writeln!(f, "{image} with the hash value {hash} has been executed")?,
+            EvtxIoc::Filename(pattern) => {
+                writeln!(f, "the name of the executed file {image} matches the pattern /{pattern}/")?
+            }
+            EvtxIoc::Levenshtein(name) => {
+                writeln!(f, "the name of the executed file {image} is very similar to {name}")?
+            }
+        }
+        writeln!(f, "  found in: {}", self.found_in_file())?;
+        writeln!(f, "  evtx record: {}", self.record)
+    }
+}
+
+impl ScannerFinding for EvtxIocFinding {
+    fn format_csv(&self) -> HashSet<CsvLine> {
+        let (scanner, rule) = match &self.ioc {
+            EvtxIoc::Hash(hash) => ("Hash", hash.to_string()),
+            EvtxIoc::Filename(pattern) => ("Filename", pattern.to_string()),
+            EvtxIoc::Levenshtein(name) => ("Levenshtein", name.clone()),
+        };
+        let r = &self.record;
+        hashset![CsvLine::new(scanner, &rule, self.found_in_file(), format!("executed file {}", self.image))
+            .with_context(json!({"evtx_record": r.to_json()}))]
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        let mut value = match &self.ioc {
+            EvtxIoc::Hash(hash) => json!({"01_scanner": "hash", "03_hash": hash.to_string()}),
+            EvtxIoc::Filename(pattern) => json!({"01_scanner": "filename", "03_pattern": pattern.to_string()}),
+            EvtxIoc::Levenshtein(name) => json!({"01_scanner": "levenshtein", "03_original_name": name}),
+        };
+        value["02_suspicious_file"] = json!(self.found_in_file());
+        value["04_executed_file"] = json!(self.image);
+        value["11_evtx_record"] = self.record.to_json();
+        value
+    }
+
+    fn found_in_file(&self) -> &str {
+        &self.found_in_file[..]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use serde_json::json;
+
+    use super::{EvtxIocScanner, ProcessCreation};
+    use crate::filename_scanner::FilenameScanner;
+    use crate::hash_scanner::HashScanner;
+    use crate::levenshtein_scanner::LevenshteinScanner;
+    use crate::yara::EvtxRecordInfo;
+
+    const MD5: &str = "2b7e4f3b4bfa4fcbd7ff5b2e4a4f6d7c";
+
+    fn sysmon_event() -> serde_json::Value {
+        json!({"Event": {
+            "System": {
+                "Provider": {"#attributes": {"Name": "Microsoft-Windows-Sysmon"}},
+                "EventID": 1,
+                "Channel": "Microsoft-Windows-Sysmon/Operational",
+            },
+            "EventData": {
+                "Image": "C:\\Users\\Public\\expl0rer.exe",
+                "Hashes": format!("SHA1=da39a3ee5e6b4b0d3255bfef95601890afd80709,MD5={},IMPHASH={}", MD5.to_uppercase(), MD5),
+            }
+        }})
+    }
+
+    #[test]
+    fn test_process_creation() {
+        let data = sysmon_event();
+        let info = EvtxRecordInfo::new(42, "2022-06-17T22:51:20Z".to_owned(), &data);
+        let process = ProcessCreation::from_record(&info, &data).unwrap();
+        assert_eq!(process.file_name(), "expl0rer.exe");
+        assert_eq!(process.hashes.len(), 2);
+
+        let data = json!({"Event": {
+            "System": {"Provider": {"#attributes": {"Name": "Microsoft-Windows-Security-Auditing"}}, "EventID": 4624},
+            "EventData": {"NewProcessName": "C:\\Windows\\System32\\cmd.exe"}
+        }});
+        let info = EvtxRecordInfo::new(43, "2022-06-17T22:51:20Z".to_owned(), &data);
+        assert!(ProcessCreation::from_record(&info, &data).is_none());
+    }
+
+    #[test]
+    fn test_scan_process_creation() {
+        let data = sysmon_event();
+        let info = EvtxRecordInfo::new(42, "2022-06-17T22:51:20Z".to_owned(), &data);
+        let process = ProcessCreation::from_record(&info, &data).unwrap();
+
+        let scanner = EvtxIocScanner::default()
+            .with_hash_scanner(Arc::new(HashScanner::default().with_hashes(&[MD5.to_owned()]).unwrap()))
+            .with_filename_scanner(Arc::new(FilenameScanner::new(vec![regex::Regex::new(r"^expl").unwrap()])))
+            .with_levenshtein_scanner(Arc::new(LevenshteinScanner::default()));
+        let findings = scanner.scan_process_creation(&process, &info, "Sysmon.evtx");
+        assert_eq!(findings.len(), 3);
+        for finding in findings.iter() {
+            assert_eq!(finding.record.record_id, 42);
+            assert_eq!(finding.record.timestamp, "2022-06-17T22:51:20Z");
+        }
+        assert_eq!(findings[0].record.field.as_deref(), Some("Event.EventData.Hashes"));
+        assert_eq!(findings[1].record.field.as_deref(), Some("Event.EventData.Image"));
+    }
+}
@@ -20,23 +20,20 @@ impl FilenameScanner {
         }
     }
 
+    /// returns all patterns which match `filename`
+    pub fn matching_patterns<'s>(&'s self, filename: &'s str) -> impl Iterator<Item = &'s regex::Regex> {
+        self.patterns.iter().filter(move |pattern| pattern.is_match(filename))
+    }
+
     fn scan_file_str(&self, filename: &str, filepath: &str) -> Vec<anyhow::Result<Box<dyn ScannerFinding>>> {
-        let mut results = Vec::new();
-        for pattern in self.patterns.iter() {
-            if pattern.is_match(filename) {
-                results.push(
-                    Ok(
-                        Box::new(
-                            FilenameFinding{
-                                pattern: pattern.clone(),
-                                found_in_file: filepath.to_owned()
-                            }
-                        ) as Box<dyn ScannerFinding>
-                    )
-                )
-            }
-        }
-        results
+        self.matching_patterns(filename)
+            .map(|pattern| {
+                Ok(Box::new(FilenameFinding {
+                    pattern: pattern.clone(),
+                    found_in_file: filepath.to_owned(),
+                }) as Box<dyn ScannerFinding>)
+            })
+            .collect()
     }
 }
 
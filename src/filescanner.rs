@@ -1,4 +1,5 @@
 use std::fmt::Display;
+use std::sync::Arc;
 use walkdir::DirEntry;
 use crate::scanner_result::*;
 
@@ -6,3 +7,11 @@ pub trait FileScanner: Display + Sync + Send
 {
     fn scan_file(&self, file: &DirEntry) -> Vec<anyhow::Result<Box<dyn ScannerFinding>>>;
 }
+
+/// allows a scanner to be used as file scanner and as process scanner at the same time
+impl<S> FileScanner for Arc<S> where S: FileScanner
+{
+    fn scan_file(&self, file: &DirEntry) -> Vec<anyhow::Result<Box<dyn ScannerFinding>>> {
+        self.as_ref().scan_file(file)
+    }
+}
@@ -0,0 +1,282 @@
+use std::fmt::Display;
+use std::path::Path;
+use std::str::FromStr;
+
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value};
+
+use crate::hash_scanner::{CryptoHash, HashScanner};
+
+/// a hash IOC, together with the information where it comes from
+#[derive(Clone)]
+pub struct HashIoc {
+    pub hash: CryptoHash,
+    pub name: Option<String>,
+    pub description: Option<String>,
+
+    /// the file (and line) which contains the IOC, or `command line`
+    pub source: String,
+}
+
+impl HashIoc {
+    pub fn new(hash: CryptoHash, source: String) -> Self {
+        Self {
+            hash,
+            name: None,
+            description: None,
+            source,
+        }
+    }
+
+    pub fn with_name(mut self, name: Option<String>) -> Self {
+        self.name = name.filter(|n| !n.is_empty());
+        self
+    }
+
+    pub fn with_description(mut self, description: Option<String>) -> Self {
+        self.description = description.filter(|d| !d.is_empty());
+        self
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "name": self.name,
+            "description": self.description,
+            "source": self.source,
+        })
+    }
+}
+
+impl Display for HashIoc {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(name) = &self.name {
+            write!(f, "IOC '{name}', ")?;
+        }
+        if let Some(description) = &self.description {
+            write!(f, "{description}, ")?;
+        }
+        write!(f, "source: {}", self.source)
+    }
+}
+
+/// the columns of a CSV file which contain the hash, and optionally the name
+/// and the description of an IOC, e.g. `sha256,signature,comment`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CsvColumns {
+    hash: String,
+    name: Option<String>,
+    description: Option<String>,
+}
+
+impl Default for CsvColumns {
+    fn default() -> Self {
+        Self {
+            hash: "hash".to_owned(),
+            name: Some("name".to_owned()),
+            description: Some("description".to_owned()),
+        }
+    }
+}
+
+impl FromStr for CsvColumns {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut columns = s.split(',').map(|c| c.trim().to_owned());
+        let hash = columns.next().filter(|c| !c.is_empty());
+        let hash = hash.ok_or_else(|| anyhow!("missing hash column in '{s}'"))?;
+        let name = columns.next().filter(|c| !c.is_empty());
+        let description = columns.next().filter(|c| !c.is_empty());
+        if columns.next().is_some() {
+            return Err(anyhow!("expected at most three columns (hash, name and description) in '{s}'"));
+        }
+        Ok(Self {
+            hash,
+            name,
+            description,
+        })
+    }
+}
+
+/// loads hash IOCs from a file. The format is determined by the extension:
+///
+///  - `.csv` files must have a header line, the columns are selected by `columns`
+///  - `.json` files contain a list of hashes, or of objects with a `hash`
+///    (or `md5`, `sha1` and `sha256`), `name` and `description`
+///  - all other files contain one hash per line, optionally followed by the
+///    name of the IOC
+///
+/// Lines starting with `#` are treated as comments in all formats.
+pub fn load_hash_iocs(path: &Path, columns: &CsvColumns) -> Result<Vec<HashIoc>> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|why| anyhow!("unable to read hash file '{}': {why}", path.display()))?;
+    let extension = path.extension().map(|e| e.to_string_lossy().to_lowercase());
+    let iocs = match extension.as_deref() {
+        Some("csv") => parse_csv(&content, path, columns),
+        Some("json") => parse_json(&content, path),
+        _ => parse_text(&content, path),
+    }?;
+    log::info!("loaded {} hash IOCs from '{}'", iocs.len(), path.display());
+    Ok(iocs)
+}
+
+fn is_comment(line: &str) -> bool {
+    let line = line.trim_start();
+    line.is_empty() || line.starts_with('#')
+}
+
+fn parse_hash(hash: &str, source: &str) -> Result<CryptoHash> {
+    HashScanner::parse_hash(hash.trim()).map_err(|why| anyhow!("{source}: {why}"))
+}
+
+fn parse_text(content: &str, path: &Path) -> Result<Vec<HashIoc>> {
+    let mut iocs = Vec::new();
+    for (idx, line) in content.lines().enumerate() {
+        if is_comment(line) {
+            continue;
+        }
+        let source = format!("{}:{}", path.display(), idx + 1);
+        let line = line.trim();
+        let (hash, name) = match line.split_once(char::is_whitespace) {
+            Some((hash, name)) => (hash, Some(name.trim().to_owned())),
+            None => (line, None),
+        };
+        iocs.push(HashIoc::new(parse_hash(hash, &source)?, source).with_name(name));
+    }
+    Ok(iocs)
+}
+
+fn parse_csv(content: &str, path: &Path, columns: &CsvColumns) -> Result<Vec<HashIoc>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .comment(Some(b'#'))
+        .flexible(true)
+        .from_reader(content.as_bytes());
+
+    let headers = reader
+        .headers()
+        .map_err(|why| anyhow!("unable to read header of '{}': {why}", path.display()))?
+        .clone();
+    let column_index = |column: &str| headers.iter().position(|h| h.trim().eq_ignore_ascii_case(column));
+    let hash_column = column_index(&columns.hash)
+        .ok_or_else(|| anyhow!("'{}' has no column named '{}'", path.display(), columns.hash))?;
+    let name_column = columns.name.as_deref().and_then(column_index);
+    let description_column = columns.description.as_deref().and_then(column_index);
+
+    let mut iocs = Vec::new();
+    for record in reader.records() {
+        let record = record.map_err(|why| anyhow!("unable to read '{}': {why}", path.display()))?;
+        let line = record.position().map(|p| p.line()).unwrap_or_default();
+        let source = format!("{}:{line}", path.display());
+        let field = |column: Option<usize>| column.and_then(|c| record.get(c)).map(|v| v.trim().to_owned());
+        let hash = field(Some(hash_column)).unwrap_or_default();
+        iocs.push(
+            HashIoc::new(parse_hash(&hash, &source)?, source)
+                .with_name(field(name_column))
+                .with_description(field(description_column)),
+        );
+    }
+    Ok(iocs)
+}
+
+fn parse_json(content: &str, path: &Path) -> Result<Vec<HashIoc>> {
+    // JSON has no comments, so they are removed before parsing
+    let content: Vec<_> = content.lines().map(|line| if is_comment(line) { "" } else { line }).collect();
+    let entries: Vec<Value> = serde_json::from_str(&content.join("\n"))
+        .map_err(|why| anyhow!("unable to parse '{}': {why}", path.display()))?;
+
+    let mut iocs = Vec::new();
+    for (idx, entry) in entries.iter().enumerate() {
+        let source = format!("{}, entry {}", path.display(), idx + 1);
+        let text = |key: &str| entry[key].as_str().map(str::to_owned);
+        let hashes: Vec<&str> = match entry {
+            Value::String(hash) => vec![hash],
+            Value::Object(_) => ["hash", "md5", "sha1", "sha256"]
+                .iter()
+                .filter_map(|key| entry[*key].as_str())
+                .collect(),
+            _ => Vec::new(),
+        };
+        if hashes.is_empty() {
+            return Err(anyhow!("{source}: missing hash value"));
+        }
+        for hash in hashes {
+            iocs.push(
+                HashIoc::new(parse_hash(hash, &source)?, source.clone())
+                    .with_name(text("name"))
+                    .with_description(text("description")),
+            );
+        }
+    }
+    Ok(iocs)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::{load_hash_iocs, CsvColumns};
+
+    const MD5: &str = "e6a65c3b01c87ea2f31134e3345a2c67";
+    const SHA1: &str = "47b76fed75208dffbba1a44296ae2ecf5f670c59";
+
+    fn load(extension: &str, content: &str, columns: &str) -> Vec<super::HashIoc> {
+        let mut file = tempfile::Builder::new()
+            .suffix(extension)
+            .tempfile()
+            .unwrap();
+        write!(file, "{content}").unwrap();
+        load_hash_iocs(file.path(), &columns.parse().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_text() {
+        let iocs = load(
+            ".txt",
+            &format!("# sample hashes\n\n{MD5}  Sample Two\n{SHA1}\n"),
+            "hash",
+        );
+        assert_eq!(iocs.len(), 2);
+        assert_eq!(iocs[0].hash.to_string(), format!("MD5:{MD5}"));
+        assert_eq!(iocs[0].name.as_deref(), Some("Sample Two"));
+        assert!(iocs[0].source.ends_with(":3"));
+        assert!(iocs[1].name.is_none());
+    }
+
+    #[test]
+    fn test_csv() {
+        let content = format!(
+            "# exported IOCs\nsha1,family,comment\n{SHA1},Sample,\"second sample, plain text\"\n"
+        );
+        let iocs = load(".csv", &content, "SHA1,family,comment");
+        assert_eq!(iocs.len(), 1);
+        assert_eq!(iocs[0].name.as_deref(), Some("Sample"));
+        assert_eq!(
+            iocs[0].description.as_deref(),
+            Some("second sample, plain text")
+        );
+        assert!(iocs[0].source.ends_with(":3"));
+    }
+
+    #[test]
+    fn test_json() {
+        let content = format!(
+            "# exported IOCs\n[\n  \"{MD5}\",\n  {{\"md5\": \"{MD5}\", \"sha1\": \"{SHA1}\", \"name\": \"Sample\"}}\n]"
+        );
+        let iocs = load(".json", &content, "hash");
+        assert_eq!(iocs.len(), 3);
+        assert!(iocs[0].name.is_none());
+        assert_eq!(iocs[2].hash.to_string(), format!("SHA1:{SHA1}"));
+        assert_eq!(iocs[2].name.as_deref(), Some("Sample"));
+        assert!(iocs[2].source.ends_with(", entry 2"));
+    }
+
+    #[test]
+    fn test_csv_columns() {
+        assert_eq!(
+            "hash,name,description".parse::<CsvColumns>().unwrap(),
+            CsvColumns::default()
+        );
+        assert!("".parse::<CsvColumns>().is_err());
+        assert!("a,b,c,d".parse::<CsvColumns>().is_err());
+    }
+}
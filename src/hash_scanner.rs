@@ -8,11 +8,13 @@ use sha2::Sha256;
 use std::convert::TryInto;
 use std::fmt::Display;
 use std::hash::Hash;
-use std::{collections::HashSet, fs::File};
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
 use walkdir::DirEntry;
 
 use crate::csv_line::CsvLine;
 use crate::filescanner::FileScanner;
+use crate::hash_ioc::HashIoc;
 use crate::scanner_result::ScannerFinding;
 
 const MD5_SIZE: usize = 128 / 8;
@@ -59,7 +61,7 @@ impl Display for CryptoHash {
 
 #[derive(Default)]
 pub struct HashScanner {
-    hashes: HashSet<CryptoHash>,
+    hashes: HashMap<CryptoHash, HashIoc>,
 
     has_md5_hashes: bool,
     has_sha1_hashes: bool,
@@ -67,20 +69,36 @@ pub struct HashScanner {
 }
 
 impl HashScanner {
-    pub fn with_hashes(mut self, hashes: &[String]) -> Result<Self> {
-        for hash in hashes.iter() {
-            let crypto_hash = Self::parse_hash(hash)?;
-            match &crypto_hash {
+    pub fn with_hashes(self, hashes: &[String]) -> Result<Self> {
+        let iocs = hashes
+            .iter()
+            .map(|hash| Ok(HashIoc::new(Self::parse_hash(hash)?, "command line".to_owned())))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(self.with_hash_iocs(iocs))
+    }
+
+    /// adds hash IOCs; if a hash is specified more than once, the first IOC is used
+    pub fn with_hash_iocs(mut self, iocs: Vec<HashIoc>) -> Self {
+        for ioc in iocs.into_iter() {
+            match &ioc.hash {
                 CryptoHash::MD5(_) => self.has_md5_hashes = true,
                 CryptoHash::SHA1(_) => self.has_sha1_hashes = true,
                 CryptoHash::SHA256(_) => self.has_sha256_hashes = true,
             }
-            self.hashes.insert(Self::parse_hash(hash)?);
+            if let Some(existing) = self.hashes.get(&ioc.hash) {
+                log::info!("ignoring duplicate hash {} from {}, it has already been loaded from {}", ioc.hash, ioc.source, existing.source);
+            } else {
+                self.hashes.insert(ioc.hash.clone(), ioc);
+            }
         }
-        Ok(self)
+        self
+    }
+
+    pub fn contains(&self, hash: &CryptoHash) -> bool {
+        self.hashes.contains_key(hash)
     }
 
-    fn parse_hash(hash: &str) -> Result<CryptoHash> {
+    pub fn parse_hash(hash: &str) -> Result<CryptoHash> {
         let bytes = hex::decode(hash)?;
         match bytes.len() {
             MD5_SIZE => Ok(CryptoHash::MD5(bytes.try_into().unwrap())),
@@ -123,9 +141,9 @@ impl HashScanner {
 
         let mut results = Vec::new();
         for h in &hashes {
-            if self.hashes.contains(h) {
+            if let Some(ioc) = self.hashes.get(h) {
                 results.push(Ok(Box::new(HashScannerFinding {
-                    hash: h.clone(),
+                    ioc: ioc.clone(),
                     found_in_file: entry.file_name().to_str().unwrap().to_owned(),
                 }) as Box<dyn ScannerFinding>));
             }
@@ -160,15 +178,15 @@ impl FileScanner for HashScanner {
 }
 
 struct HashScannerFinding {
-    hash: CryptoHash,
+    ioc: HashIoc,
     found_in_file: String,
 }
 
 impl Display for HashScannerFinding {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let found_in_file = self.found_in_file();
-        let hash = &self.hash;
-        writeln!(f, "file {found_in_file} has the hash value {hash}")
+        let hash = &self.ioc.hash;
+        writeln!(f, "file {found_in_file} has the hash value {hash} ({})", self.ioc)
     }
 }
 
@@ -177,17 +195,19 @@ impl ScannerFinding for HashScannerFinding {
         let file = self.found_in_file();
         hashset![CsvLine::new(
             "Hash",
-            &format!("{}", self.hash),
+            &format!("{}", self.ioc.hash),
             file,
-            String::new()
-        )]
+            self.ioc.description.clone().unwrap_or_default()
+        )
+        .with_context(json!({"ioc": self.ioc.to_json()}))]
     }
     fn to_json(&self) -> serde_json::Value {
         let file = self.found_in_file();
         json!({
             "01_scanner": "hash",
             "02_suspicious_file": file,
-            "03_hash": format!("{}", self.hash)
+            "03_hash": format!("{}", self.ioc.hash),
+            "04_ioc": self.ioc.to_json()
         })
     }
 
@@ -45,18 +45,27 @@ impl Display for LevenshteinScanner {
 }
 
 impl LevenshteinScanner {
+    /// returns the well known file names which are very similar, but not equal, to `file_name`
+    pub fn similar_names(&self, file_name: &str) -> Vec<String> {
+        let file_name: Vec<char> = file_name.chars().collect();
+        self.wellknown_files
+            .iter()
+            .filter(|l| has_levenshtein_distance_one(&file_name, l))
+            .map(|l| l.iter().collect())
+            .collect()
+    }
+
     fn intern_scan_file(&self, file: &Path) -> Vec<anyhow::Result<Box<dyn ScannerFinding>>> {
         match file.file_name() {
             None => vec![],
             Some(file_name) => match file_name.to_str() {
                 Some(os_fn) => {
                     let res: Vec<anyhow::Result<Box<dyn ScannerFinding>>> = self
-                        .wellknown_files
-                        .iter()
-                        .filter(|l| has_levenshtein_distance_one(&os_fn.chars().collect(), l))
-                        .map(|l| {
+                        .similar_names(os_fn)
+                        .into_iter()
+                        .map(|file_name| {
                             Ok(Box::new(LevenshteinScannerFinding {
-                                file_name: l.iter().collect(),
+                                file_name,
                                 found_in_file: file.display().to_string(),
                             }) as Box<dyn ScannerFinding>)
                         })
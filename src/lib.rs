@@ -1,10 +1,17 @@
 mod filescanner;
+mod processscanner;
+mod process_info;
 mod dionysos;
 mod yara;
 mod filename_scanner;
 mod scanner_result;
 mod levenshtein_scanner;
 mod hash_scanner;
+mod hash_ioc;
+#[cfg(feature = "scan_evtx")]
+mod evtx_ioc_scanner;
+#[cfg(feature = "scan_reg")]
+mod registry_ioc_scanner;
 mod csv_line;
 mod cli;
 mod output_format;
@@ -0,0 +1,178 @@
+use std::fmt::Display;
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Result};
+
+/// a single line of `/proc/<pid>/maps`
+#[derive(Clone)]
+pub struct MemoryRegion {
+    pub start: u64,
+    pub end: u64,
+    pub permissions: String,
+    pub offset: u64,
+    pub pathname: String,
+}
+
+impl MemoryRegion {
+    fn parse(line: &str) -> Result<Self> {
+        let mut parts = line.splitn(6, ' ');
+        let mut next = || {
+            parts
+                .next()
+                .ok_or_else(|| anyhow!("invalid memory map entry: '{line}'"))
+        };
+
+        let (start, end) = next()?
+            .split_once('-')
+            .ok_or_else(|| anyhow!("invalid address range in '{line}'"))?;
+        let permissions = next()?.to_owned();
+        let offset = u64::from_str_radix(next()?, 16)?;
+        let _device = next()?;
+        let _inode = next()?;
+        let pathname = next().unwrap_or_default().trim().to_owned();
+
+        Ok(Self {
+            start: u64::from_str_radix(start, 16)?,
+            end: u64::from_str_radix(end, 16)?,
+            permissions,
+            offset,
+            pathname,
+        })
+    }
+
+    pub fn contains(&self, address: u64) -> bool {
+        self.start <= address && address < self.end
+    }
+
+    #[cfg(target_os = "linux")]
+    pub fn is_readable(&self) -> bool {
+        self.permissions.starts_with('r')
+    }
+}
+
+impl Display for MemoryRegion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "0x{:x}-0x{:x} {} {}",
+            self.start, self.end, self.permissions, self.pathname
+        )
+    }
+}
+
+/// information about a running process, which has been read from `/proc/<pid>`
+#[derive(Clone)]
+pub struct ProcessInfo {
+    pub pid: u32,
+    pub name: String,
+    pub cmdline: String,
+    pub exe: String,
+    pub regions: Vec<MemoryRegion>,
+}
+
+impl ProcessInfo {
+    pub fn from_pid(pid: u32) -> Result<Self> {
+        let proc_dir = PathBuf::from("/proc").join(pid.to_string());
+
+        let name = fs::read_to_string(proc_dir.join("comm"))
+            .map(|s| s.trim_end().to_owned())
+            .unwrap_or_default();
+
+        let cmdline = fs::read(proc_dir.join("cmdline"))
+            .map(|cmdline| {
+                cmdline
+                    .split(|b| *b == 0)
+                    .filter(|arg| !arg.is_empty())
+                    .map(String::from_utf8_lossy)
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .unwrap_or_default();
+
+        // the link target has the suffix ' (deleted)' if the binary has been deleted
+        let exe = fs::read_link(proc_dir.join("exe"))
+            .map(|p| p.display().to_string())
+            .unwrap_or_default();
+
+        let regions = fs::read_to_string(proc_dir.join("maps"))
+            .map_err(|why| anyhow!("unable to read memory maps of process {pid}: {why}"))?
+            .lines()
+            .map(MemoryRegion::parse)
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            pid,
+            name,
+            cmdline,
+            exe,
+            regions,
+        })
+    }
+
+    /// returns the ids of all running processes, except of the current one
+    pub fn all_pids() -> Result<Vec<u32>> {
+        let own_pid = std::process::id();
+        let mut pids: Vec<u32> = fs::read_dir("/proc")?
+            .filter_map(|e| e.ok())
+            .filter_map(|e| e.file_name().to_str().and_then(|s| s.parse().ok()))
+            .filter(|pid| *pid != own_pid)
+            .collect();
+        pids.sort_unstable();
+        Ok(pids)
+    }
+
+    pub fn region_of(&self, address: u64) -> Option<&MemoryRegion> {
+        self.regions.iter().find(|r| r.contains(address))
+    }
+
+    #[cfg(target_os = "linux")]
+    pub fn has_readable_regions(&self) -> bool {
+        self.regions.iter().any(|r| r.is_readable())
+    }
+
+    /// removes all memory regions which do not contain any of the specified
+    /// addresses. This is used to keep findings small.
+    #[cfg(target_os = "linux")]
+    pub fn retain_regions(&mut self, addresses: &[u64]) {
+        self.regions
+            .retain(|r| addresses.iter().any(|a| r.contains(*a)));
+    }
+}
+
+impl Display for ProcessInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "process {} ({})", self.pid, self.name)
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::{MemoryRegion, ProcessInfo};
+
+    #[test]
+    fn test_parse_region() {
+        let region = MemoryRegion::parse(
+            "7f2c4c021000-7f2c4c1b6000 r-xp 00021000 fd:01 1054 /usr/lib/x86_64-linux-gnu/libc.so.6",
+        )
+        .unwrap();
+        assert_eq!(region.start, 0x7f2c4c021000);
+        assert_eq!(region.end, 0x7f2c4c1b6000);
+        assert_eq!(region.permissions, "r-xp");
+        assert_eq!(region.offset, 0x21000);
+        assert_eq!(region.pathname, "/usr/lib/x86_64-linux-gnu/libc.so.6");
+        assert!(region.is_readable());
+
+        let region = MemoryRegion::parse("7ffd1d3e4000-7ffd1d3e6000 ---p 00000000 00:00 0 ").unwrap();
+        assert_eq!(region.pathname, "");
+        assert!(!region.is_readable());
+    }
+
+    #[test]
+    fn test_own_process() {
+        let process = ProcessInfo::from_pid(std::process::id()).unwrap();
+        assert!(process.has_readable_regions());
+        assert!(!process.exe.is_empty());
+        assert!(!ProcessInfo::all_pids().unwrap().contains(&process.pid));
+    }
+}
@@ -0,0 +1,8 @@
+use std::fmt::Display;
+use crate::process_info::ProcessInfo;
+use crate::scanner_result::*;
+
+pub trait ProcessScanner: Display + Sync + Send
+{
+    fn scan_process(&self, process: &ProcessInfo) -> Vec<anyhow::Result<Box<dyn ScannerFinding>>>;
+}
@@ -0,0 +1,405 @@
+use std::collections::HashSet;
+use std::fmt::Display;
+use std::fs::File;
+use std::path::Path;
+use std::sync::Arc;
+
+use chrono::SecondsFormat;
+use maplit::hashset;
+use nt_hive2::{CleanHive, Hive, KeyNode, RegistryValue};
+use serde_json::json;
+use walkdir::DirEntry;
+
+use crate::csv_line::CsvLine;
+use crate::filename_scanner::FilenameScanner;
+use crate::filescanner::FileScanner;
+use crate::levenshtein_scanner::LevenshteinScanner;
+use crate::scanner_result::ScannerFinding;
+use crate::yara::{is_primary_hive_file, open_hive, RegistryEntryInfo, TransactionLogStatus};
+
+/// a registry location which contains the path of a file that is executed
+/// automatically. Key paths are relative to the root key of the hive; the
+/// `Software` key of user hives is omitted, because it does not exist in the
+/// `SOFTWARE` hive. A segment ending with `*` matches all key names starting with
+/// the same prefix.
+struct PersistenceLocation {
+    key_path: &'static [&'static str],
+
+    /// `None` means that every value of the key contains a file path
+    value_name: Option<&'static str>,
+
+    /// the value contains a list of files, instead of a command line
+    is_list: bool,
+}
+
+const fn location(key_path: &'static [&'static str], value_name: Option<&'static str>, is_list: bool) -> PersistenceLocation {
+    PersistenceLocation {
+        key_path,
+        value_name,
+        is_list,
+    }
+}
+
+const PERSISTENCE_LOCATIONS: &[PersistenceLocation] = &[
+    location(&["Microsoft", "Windows", "CurrentVersion", "Run"], None, false),
+    location(&["Microsoft", "Windows", "CurrentVersion", "RunOnce"], None, false),
+    location(&["Wow6432Node", "Microsoft", "Windows", "CurrentVersion", "Run"], None, false),
+    location(&["Wow6432Node", "Microsoft", "Windows", "CurrentVersion", "RunOnce"], None, false),
+    location(&["Microsoft", "Windows NT", "CurrentVersion", "Windows"], Some("AppInit_DLLs"), true),
+    location(&["Wow6432Node", "Microsoft", "Windows NT", "CurrentVersion", "Windows"], Some("AppInit_DLLs"), true),
+    location(&["Microsoft", "Windows NT", "CurrentVersion", "Image File Execution Options", "*"], Some("Debugger"), false),
+    location(&["Wow6432Node", "Microsoft", "Windows NT", "CurrentVersion", "Image File Execution Options", "*"], Some("Debugger"), false),
+    location(&["Microsoft", "Windows NT", "CurrentVersion", "Winlogon"], Some("Shell"), true),
+    location(&["Microsoft", "Windows NT", "CurrentVersion", "Winlogon"], Some("Userinit"), true),
+    location(&["ControlSet*", "Services", "*"], Some("ImagePath"), false),
+    location(&["ControlSet*", "Services", "*", "Parameters"], Some("ServiceDll"), false),
+];
+
+const EXECUTABLE_EXTENSIONS: &[&str] = &[
+    ".exe", ".dll", ".sys", ".com", ".scr", ".cpl", ".bat", ".cmd", ".ps1", ".vbs", ".js", ".hta",
+];
+
+fn segment_matches(pattern: &str, name: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => name.len() >= prefix.len() && name[..prefix.len()].eq_ignore_ascii_case(prefix),
+        None => pattern.eq_ignore_ascii_case(name),
+    }
+}
+
+impl PersistenceLocation {
+    fn matches_key(&self, key_path: &[String]) -> bool {
+        self.key_path.len() == key_path.len()
+            && self.key_path.iter().zip(key_path).all(|(p, n)| segment_matches(p, n))
+    }
+
+    /// returns true if a subkey of `key_path` might match this location
+    fn is_below(&self, key_path: &[String]) -> bool {
+        self.key_path.len() > key_path.len()
+            && self.key_path.iter().zip(key_path).all(|(p, n)| segment_matches(p, n))
+    }
+
+    fn matches_value(&self, value_name: &str) -> bool {
+        match self.value_name {
+            None => true,
+            Some(name) => name.eq_ignore_ascii_case(value_name),
+        }
+    }
+}
+
+/// removes the `Software` key of user hives
+fn normalized_path(key_path: &[String]) -> &[String] {
+    match key_path.first() {
+        Some(first) if first.eq_ignore_ascii_case("Software") => &key_path[1..],
+        _ => key_path,
+    }
+}
+
+/// extracts the paths of the executed files from a command line, e.g.
+/// `"C:\Program Files\App\app.exe" --minimized` or `%SystemRoot%\system32\svchost.exe -k netsvcs`.
+/// Lists, like `AppInit_DLLs` or `Userinit`, are split at commas and spaces.
+fn executable_paths(data: &str, is_list: bool) -> Vec<String> {
+    let mut paths = Vec::new();
+    let mut rest = data.trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+    while !rest.is_empty() {
+        let (path, remainder) = if let Some(quoted) = rest.strip_prefix('"') {
+            match quoted.find('"') {
+                Some(end) => (&quoted[..end], &quoted[end + 1..]),
+                None => (quoted, ""),
+            }
+        } else if is_list {
+            let end = rest.find(|c: char| c.is_whitespace() || c == ',').unwrap_or(rest.len());
+            (&rest[..end], &rest[end..])
+        } else {
+            // paths with spaces are not always quoted, so we search for the file extension first
+            let end = executable_end(rest)
+                .or_else(|| rest.find(char::is_whitespace))
+                .unwrap_or(rest.len());
+            (&rest[..end], &rest[end..])
+        };
+
+        if !path.trim().is_empty() {
+            paths.push(path.trim().to_owned());
+        }
+        if !is_list {
+            break;
+        }
+        rest = remainder.trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+    }
+    paths
+}
+
+/// returns the end of the first file name with a well known extension
+fn executable_end(command: &str) -> Option<usize> {
+    let lowercase = command.to_ascii_lowercase();
+    EXECUTABLE_EXTENSIONS
+        .iter()
+        .filter_map(|extension| {
+            lowercase.match_indices(extension).map(|(idx, _)| idx + extension.len()).find(|end| {
+                lowercase[*end..].chars().next().map_or(true, |c| c.is_whitespace() || c == ',' || c == '"')
+            })
+        })
+        .min()
+}
+
+/// the file name uses Windows path separators, regardless of the platform we are running on
+fn file_name(path: &str) -> &str {
+    path.rsplit(['\\', '/']).next().unwrap_or(path)
+}
+
+/// applies filename IOCs to the files which are started by well known
+/// persistence locations of the registry, like Run keys or services. Such
+/// files might have been deleted from the system in the meantime.
+#[derive(Default)]
+pub struct RegistryIocScanner {
+    filename_scanner: Option<Arc<FilenameScanner>>,
+    levenshtein_scanner: Option<Arc<LevenshteinScanner>>,
+}
+
+impl RegistryIocScanner {
+    pub fn with_filename_scanner(mut self, filename_scanner: Arc<FilenameScanner>) -> Self {
+        self.filename_scanner = Some(filename_scanner);
+        self
+    }
+
+    pub fn with_levenshtein_scanner(mut self, levenshtein_scanner: Arc<LevenshteinScanner>) -> Self {
+        self.levenshtein_scanner = Some(levenshtein_scanner);
+        self
+    }
+
+    pub fn has_iocs(&self) -> bool {
+        self.filename_scanner.is_some() || self.levenshtein_scanner.is_some()
+    }
+
+    fn scan_hive(&self, file: &Path) -> anyhow::Result<Vec<RegistryIocFinding>> {
+        let (mut hive, transaction_logs) = open_hive(file)?;
+        let root_key = hive.root_key_node()?;
+        let mut results = Vec::new();
+        let mut key_path = Vec::new();
+        self.scan_key(
+            &mut hive,
+            &root_key,
+            &mut key_path,
+            &transaction_logs,
+            &file.display().to_string(),
+            &mut results,
+        )?;
+        Ok(results)
+    }
+
+    fn scan_key(
+        &self,
+        hive: &mut Hive<File, CleanHive>,
+        key: &KeyNode,
+        key_path: &mut Vec<String>,
+        transaction_logs: &TransactionLogStatus,
+        filename: &str,
+        results: &mut Vec<RegistryIocFinding>,
+    ) -> anyhow::Result<()> {
+        let path = normalized_path(key_path);
+        let locations: Vec<_> = PERSISTENCE_LOCATIONS.iter().filter(|l| l.matches_key(path)).collect();
+
+        for location in locations {
+            for value in key.values().iter().filter(|v| location.matches_value(v.name())) {
+                let data = match value.value() {
+                    RegistryValue::RegSZ(s) | RegistryValue::RegExpandSZ(s) => vec![s.clone()],
+                    RegistryValue::RegMultiSZ(sl) => sl.clone(),
+                    _ => continue,
+                };
+                let entry = RegistryEntryInfo::new(
+                    filename,
+                    &key_path.join("\\"),
+                    key.timestamp().to_rfc3339_opts(SecondsFormat::Micros, true),
+                )
+                .with_value(value.name(), value.data_type().map(|t| t.to_string()))
+                .with_transaction_logs(transaction_logs.clone());
+
+                for path in data.iter().flat_map(|d| executable_paths(d, location.is_list)) {
+                    results.extend(self.scan_executable(&path, &entry));
+                }
+            }
+        }
+
+        for subkey in key.subkeys(hive)?.iter() {
+            let subkey = subkey.borrow();
+            key_path.push(subkey.name().to_owned());
+            if PERSISTENCE_LOCATIONS.iter().any(|l| {
+                let path = normalized_path(key_path);
+                l.matches_key(path) || l.is_below(path)
+            }) {
+                self.scan_key(hive, &subkey, key_path, transaction_logs, filename, results)?;
+            }
+            key_path.pop();
+        }
+        Ok(())
+    }
+
+    fn scan_executable(&self, path: &str, entry: &RegistryEntryInfo) -> Vec<RegistryIocFinding> {
+        let finding = |ioc: RegistryIoc| RegistryIocFinding {
+            ioc,
+            executable: path.to_owned(),
+            found_in_file: entry.hive.clone(),
+            entry: entry.clone(),
+        };
+        let mut results = Vec::new();
+
+        if let Some(filename_scanner) = &self.filename_scanner {
+            results.extend(
+                filename_scanner
+                    .matching_patterns(file_name(path))
+                    .map(|p| finding(RegistryIoc::Filename(p.clone()))),
+            );
+        }
+
+        if let Some(levenshtein_scanner) = &self.levenshtein_scanner {
+            results.extend(
+                levenshtein_scanner
+                    .similar_names(file_name(path))
+                    .into_iter()
+                    .map(|n| finding(RegistryIoc::Levenshtein(n))),
+            );
+        }
+        results
+    }
+}
+
+impl Display for RegistryIocScanner {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "RegistryIocScanner")
+    }
+}
+
+impl FileScanner for RegistryIocScanner {
+    fn scan_file(&self, file: &DirEntry) -> Vec<anyhow::Result<Box<dyn ScannerFinding>>> {
+        let file = file.path();
+        match is_primary_hive_file(file) {
+            Ok(false) => vec![],
+            Err(why) => vec![Err(anyhow::anyhow!("unable to read '{}': {why}", file.display()))],
+            Ok(true) => match self.scan_hive(file) {
+                Err(why) => vec![Err(anyhow::anyhow!("registry scan error with '{}': {why}", file.display()))],
+                Ok(findings) => findings
+                    .into_iter()
+                    .map(|f| Ok(Box::new(f) as Box<dyn ScannerFinding>))
+                    .collect(),
+            },
+        }
+    }
+}
+
+enum RegistryIoc {
+    Filename(regex::Regex),
+    Levenshtein(String),
+}
+
+struct RegistryIocFinding {
+    ioc: RegistryIoc,
+    executable: String,
+    found_in_file: String,
+    entry: RegistryEntryInfo,
+}
+
+impl Display for RegistryIocFinding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let executable = &self.executable;
+        match &self.ioc {
+            RegistryIoc::Filename(pattern) => {
+                writeln!(f, "the name of the autostarted file {executable} matches the pattern /{pattern}/")?
+            }
+            RegistryIoc::Levenshtein(name) => {
+                writeln!(f, "the name of the autostarted file {executable} is very similar to {name}")?
+            }
+        }
+        writeln!(f, "  found in: {}", self.found_in_file())?;
+        writeln!(f, "  registry entry: {}", self.entry)
+    }
+}
+
+impl ScannerFinding for RegistryIocFinding {
+    fn format_csv(&self) -> HashSet<CsvLine> {
+        let (scanner, rule) = match &self.ioc {
+            RegistryIoc::Filename(pattern) => ("Filename", pattern.to_string()),
+            RegistryIoc::Levenshtein(name) => ("Levenshtein", name.clone()),
+        };
+        let e = &self.entry;
+        hashset![CsvLine::new(scanner, &rule, self.found_in_file(), format!("autostarted file {}", self.executable))
+            .with_context(json!({"registry_entry": e.to_json()}))]
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        let mut value = match &self.ioc {
+            RegistryIoc::Filename(pattern) => json!({"01_scanner": "filename", "03_pattern": pattern.to_string()}),
+            RegistryIoc::Levenshtein(name) => json!({"01_scanner": "levenshtein", "03_original_name": name}),
+        };
+        value["02_suspicious_file"] = json!(self.found_in_file());
+        value["04_executed_file"] = json!(self.executable);
+        value["12_registry_entry"] = self.entry.to_json();
+        value
+    }
+
+    fn found_in_file(&self) -> &str {
+        &self.found_in_file[..]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::{executable_paths, normalized_path, RegistryIocScanner, PERSISTENCE_LOCATIONS};
+    use crate::filename_scanner::FilenameScanner;
+    use crate::levenshtein_scanner::LevenshteinScanner;
+    use crate::yara::RegistryEntryInfo;
+
+    #[test]
+    fn test_executable_paths() {
+        assert_eq!(
+            executable_paths("\"C:\\Program Files\\App\\app.exe\" --minimized", false),
+            vec!["C:\\Program Files\\App\\app.exe"]
+        );
+        assert_eq!(
+            executable_paths("C:\\Program Files\\App\\app.exe --minimized", false),
+            vec!["C:\\Program Files\\App\\app.exe"]
+        );
+        assert_eq!(
+            executable_paths("%SystemRoot%\\system32\\svchost.exe -k netsvcs", false),
+            vec!["%SystemRoot%\\system32\\svchost.exe"]
+        );
+        assert_eq!(
+            executable_paths("C:\\Windows\\system32\\userinit.exe,C:\\Users\\Public\\evil.exe,", true),
+            vec!["C:\\Windows\\system32\\userinit.exe", "C:\\Users\\Public\\evil.exe"]
+        );
+    }
+
+    #[test]
+    fn test_locations() {
+        let path = |p: &str| -> Vec<String> { p.split('\\').map(str::to_owned).collect() };
+        let matching = |p: &str| {
+            let p = path(p);
+            PERSISTENCE_LOCATIONS.iter().filter(|l| l.matches_key(normalized_path(&p))).count()
+        };
+        assert_eq!(matching("Software\\Microsoft\\Windows\\CurrentVersion\\Run"), 1);
+        assert_eq!(matching("Microsoft\\Windows\\CurrentVersion\\RunOnce"), 1);
+        assert_eq!(matching("ControlSet001\\Services\\EventLog"), 1);
+        assert_eq!(matching("ControlSet001\\Services"), 0);
+        assert_eq!(matching("Microsoft\\Windows NT\\CurrentVersion\\Image File Execution Options\\sethc.exe"), 1);
+    }
+
+    #[test]
+    fn test_scan_executable() {
+        let scanner = RegistryIocScanner::default()
+            .with_filename_scanner(Arc::new(FilenameScanner::new(vec![regex::Regex::new(r"^expl").unwrap()])))
+            .with_levenshtein_scanner(Arc::new(LevenshteinScanner::default()));
+        let entry = RegistryEntryInfo::new(
+            "NTUSER.DAT",
+            "Software\\Microsoft\\Windows\\CurrentVersion\\Run",
+            "2022-06-17T22:51:20Z".to_owned(),
+        )
+        .with_value("Updater", Some("RegSZ".to_owned()));
+
+        let findings = scanner.scan_executable("C:\\Users\\Public\\expl0rer.exe", &entry);
+        assert_eq!(findings.len(), 2);
+        for finding in findings.iter() {
+            assert_eq!(finding.found_in_file, "NTUSER.DAT");
+            assert_eq!(finding.entry.value_name.as_deref(), Some("Updater"));
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use std::fmt::Display;
+
+use serde_json::{json, Value};
+
+/// information about a file inside of an archive (zip or tar), which is
+/// reported together with findings in this file
+#[derive(Clone, Default)]
+pub struct ArchiveMember {
+    pub path: String,
+    pub size: u64,
+    pub mode: Option<u32>,
+    pub uid: Option<u64>,
+    pub gid: Option<u64>,
+    pub owner: Option<String>,
+    pub group: Option<String>,
+
+    /// last modification time. tar archives store UTC timestamps, whereas
+    /// zip archives store the local time of the system which created them
+    pub modified: Option<String>,
+
+    /// last modification time as unix timestamp, which is provided to yara rules
+    pub mtime: Option<i64>,
+
+    /// the member is encrypted, and a password has been used for decryption
+    /// which could not be verified, because the archive contains no checksums
+    pub unverified_password: bool,
+}
+
+impl ArchiveMember {
+    pub fn new(path: String, size: u64) -> Self {
+        Self {
+            path,
+            size,
+            ..Default::default()
+        }
+    }
+
+    /// sets the modification time to a UTC timestamp
+    pub fn set_mtime(&mut self, mtime: i64) {
+        self.mtime = Some(mtime);
+        self.modified = chrono::DateTime::from_timestamp(mtime, 0)
+            .map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string());
+    }
+
+    pub fn mode_string(&self) -> Option<String> {
+        self.mode.map(|mode| format!("{:04o}", mode & 0o7777))
+    }
+
+    /// formats owner and group like `ls` does, using the numeric ids if no
+    /// names are available
+    pub fn owner_string(&self) -> Option<String> {
+        let name_or_id = |name: &Option<String>, id: Option<u64>| match (name, id) {
+            (Some(name), _) if !name.is_empty() => Some(name.clone()),
+            (_, Some(id)) => Some(id.to_string()),
+            _ => None,
+        };
+
+        match (
+            name_or_id(&self.owner, self.uid),
+            name_or_id(&self.group, self.gid),
+        ) {
+            (None, None) => None,
+            (Some(owner), None) => Some(owner),
+            (None, Some(group)) => Some(format!(":{group}")),
+            (Some(owner), Some(group)) => Some(format!("{owner}:{group}")),
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        let mut json = json!({
+            "path": self.path,
+            "size": self.size,
+            "mode": self.mode_string(),
+            "uid": self.uid,
+            "gid": self.gid,
+            "owner": self.owner,
+            "group": self.group,
+            "modified": self.modified,
+        });
+        if self.unverified_password {
+            json["unverified_password"] = Value::Bool(true);
+        }
+        json
+    }
+}
+
+impl Display for ArchiveMember {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({} bytes", self.path, self.size)?;
+        if let Some(mode) = self.mode_string() {
+            write!(f, ", mode {mode}")?;
+        }
+        if let Some(owner) = self.owner_string() {
+            write!(f, ", owner {owner}")?;
+        }
+        if let Some(modified) = &self.modified {
+            write!(f, ", modified {modified}")?;
+        }
+        if self.unverified_password {
+            write!(f, ", decrypted with an unverified password")?;
+        }
+        write!(f, ")")
+    }
+}
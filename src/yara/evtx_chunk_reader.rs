@@ -0,0 +1,206 @@
+use std::io::{self, ErrorKind, Read, Seek, SeekFrom};
+
+use evtx::EvtxChunkData;
+
+const CHUNK_SIGNATURE: &[u8] = b"ElfChnk\0";
+const CHUNK_SIZE: usize = 64 * 1024;
+const FILE_HEADER_SIZE: u64 = 4096;
+
+/// carved chunks are expected to start at a sector boundary
+const SECTOR_SIZE: usize = 512;
+
+/// reads the chunks of an evtx file one by one, instead of giving up at the first
+/// damaged chunk. Chunks can either be read from their regular position in an
+/// evtx file, or be carved out of arbitrary data, e.g. unallocated space.
+pub struct EvtxChunkReader<R: Read + Seek> {
+    reader: R,
+    offset: u64,
+    carve: bool,
+    finished: bool,
+}
+
+/// a chunk which has been found at `offset`. Chunks whose header could not be
+/// parsed are returned as `Err`, because they are most likely lost records.
+pub struct EvtxChunkCandidate {
+    pub offset: u64,
+    pub chunk: Result<EvtxChunkData, String>,
+}
+
+impl<R: Read + Seek> EvtxChunkReader<R> {
+    /// reads the chunks of an evtx file, ignoring the file header
+    pub fn from_evtx_file(reader: R) -> Self {
+        Self {
+            reader,
+            offset: FILE_HEADER_SIZE,
+            carve: false,
+            finished: false,
+        }
+    }
+
+    /// searches for evtx chunks in arbitrary data. Only chunks with a valid
+    /// header checksum are returned.
+    pub fn carve(reader: R) -> Self {
+        Self {
+            reader,
+            offset: 0,
+            carve: true,
+            finished: false,
+        }
+    }
+
+    /// reads up to `CHUNK_SIZE` bytes at `offset`. A truncated chunk is padded
+    /// with zeroes, so that at least the records before the end of data can be parsed.
+    /// Returns `None` if there is no data at `offset`
+    fn read_chunk_at(&mut self, offset: u64) -> io::Result<Option<Vec<u8>>> {
+        self.reader.seek(SeekFrom::Start(offset))?;
+        let mut data = Vec::with_capacity(CHUNK_SIZE);
+        (&mut self.reader).take(CHUNK_SIZE as u64).read_to_end(&mut data)?;
+        if data.is_empty() {
+            return Ok(None);
+        }
+        if data.len() < CHUNK_SIZE {
+            log::warn!("evtx chunk at offset 0x{offset:x} is truncated to {} bytes", data.len());
+            data.resize(CHUNK_SIZE, 0);
+        }
+        Ok(Some(data))
+    }
+
+    fn next_regular_chunk(&mut self) -> io::Result<Option<EvtxChunkCandidate>> {
+        loop {
+            let offset = self.offset;
+            let data = match self.read_chunk_at(offset)? {
+                None => return Ok(None),
+                Some(data) => data,
+            };
+            self.offset += CHUNK_SIZE as u64;
+
+            // there might be empty chunks in the middle of a dirty file
+            if data.iter().all(|b| *b == 0) {
+                continue;
+            }
+
+            let chunk = EvtxChunkData::new(data, false).map_err(|why| why.to_string());
+            return Ok(Some(EvtxChunkCandidate { offset, chunk }));
+        }
+    }
+
+    fn next_carved_chunk(&mut self) -> io::Result<Option<EvtxChunkCandidate>> {
+        // sectors are read sequentially; the reader is only moved if a signature has been found
+        let mut sector = [0u8; SECTOR_SIZE];
+        if self.offset == 0 {
+            self.reader.seek(SeekFrom::Start(0))?;
+        }
+        loop {
+            let offset = self.offset;
+            match self.reader.read_exact(&mut sector) {
+                Ok(()) => (),
+                Err(why) if why.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+                Err(why) => return Err(why),
+            }
+            self.offset += SECTOR_SIZE as u64;
+
+            if !sector.starts_with(CHUNK_SIGNATURE) {
+                continue;
+            }
+
+            let data = match self.read_chunk_at(offset)? {
+                None => return Ok(None),
+                Some(data) => data,
+            };
+            match EvtxChunkData::new(data, false) {
+                Ok(chunk) if chunk.validate_header_checksum() => {
+                    log::info!("found evtx chunk at offset 0x{offset:x}");
+                    self.offset = offset + CHUNK_SIZE as u64;
+                    self.reader.seek(SeekFrom::Start(self.offset))?;
+                    return Ok(Some(EvtxChunkCandidate {
+                        offset,
+                        chunk: Ok(chunk),
+                    }));
+                }
+                _ => {
+                    log::debug!("ignoring invalid evtx chunk signature at offset 0x{offset:x}");
+                    self.reader.seek(SeekFrom::Start(self.offset))?;
+                }
+            }
+        }
+    }
+}
+
+impl<R: Read + Seek> Iterator for EvtxChunkReader<R> {
+    type Item = io::Result<EvtxChunkCandidate>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        let result = if self.carve {
+            self.next_carved_chunk()
+        } else {
+            self.next_regular_chunk()
+        };
+
+        match result {
+            Ok(Some(candidate)) => Some(Ok(candidate)),
+            Ok(None) => {
+                self.finished = true;
+                None
+            }
+            Err(why) => {
+                self.finished = true;
+                Some(Err(why))
+            }
+        }
+    }
+}
+
+/// returns the number of records a chunk claims to contain
+pub fn expected_records(chunk: &EvtxChunkData) -> u64 {
+    let header = &chunk.header;
+    if header.last_event_record_id >= header.first_event_record_id {
+        header.last_event_record_id - header.first_event_record_id + 1
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::{EvtxChunkReader, CHUNK_SIZE, FILE_HEADER_SIZE};
+
+    fn security_evtx() -> Vec<u8> {
+        std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/Security.evtx")).unwrap()
+    }
+
+    #[test]
+    fn test_damaged_chunk() {
+        let mut data = security_evtx();
+        data[FILE_HEADER_SIZE as usize..FILE_HEADER_SIZE as usize + 8].copy_from_slice(b"XXXXXXXX");
+        data.truncate(FILE_HEADER_SIZE as usize + CHUNK_SIZE + CHUNK_SIZE / 2);
+
+        let chunks: Vec<_> = EvtxChunkReader::from_evtx_file(Cursor::new(data))
+            .map(|c| c.unwrap())
+            .collect();
+        assert_eq!(chunks.len(), 2);
+        assert!(chunks[0].chunk.is_err());
+        assert_eq!(chunks[1].offset, FILE_HEADER_SIZE + CHUNK_SIZE as u64);
+        assert!(chunks[1].chunk.is_ok());
+    }
+
+    #[test]
+    fn test_carve() {
+        let evtx = security_evtx();
+        let start = FILE_HEADER_SIZE as usize + CHUNK_SIZE;
+        let mut data = b"ElfChnk\0 is not a chunk".to_vec();
+        data.resize(3 * 512, 0xAA);
+        data.extend_from_slice(&evtx[start..start + CHUNK_SIZE]);
+        data.extend_from_slice(&[0xAA; 1000]);
+
+        let chunks: Vec<_> = EvtxChunkReader::carve(Cursor::new(data)).map(|c| c.unwrap()).collect();
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].offset, 3 * 512);
+        assert!(chunks[0].chunk.is_ok());
+    }
+}
@@ -0,0 +1,133 @@
+use std::str::FromStr;
+
+use anyhow::anyhow;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+
+use super::evtx_record_info::EvtxRecordInfo;
+
+/// point in time which limits the evtx records to be scanned. Accepted formats
+/// are RFC 3339 (`2022-06-17T22:51:20Z`), `2022-06-17 22:51:20` and `2022-06-17`;
+/// timestamps without time zone are interpreted as UTC.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EvtxTimestamp(DateTime<Utc>);
+
+impl FromStr for EvtxTimestamp {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Ok(t) = DateTime::parse_from_rfc3339(s) {
+            return Ok(Self(t.with_timezone(&Utc)));
+        }
+        for format in ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f"] {
+            if let Ok(t) = NaiveDateTime::parse_from_str(s, format) {
+                return Ok(Self(t.and_utc()));
+            }
+        }
+        if let Ok(d) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+            return Ok(Self(d.and_hms_opt(0, 0, 0).unwrap().and_utc()));
+        }
+        Err(anyhow!("invalid timestamp: '{s}'"))
+    }
+}
+
+/// Selects the evtx records which are scanned. All conditions must be met;
+/// conditions which have not been specified are always met. Channel and
+/// provider names are compared case insensitive.
+#[derive(Clone, Default)]
+pub struct EvtxFilter {
+    channels: Vec<String>,
+    event_ids: Vec<u64>,
+    providers: Vec<String>,
+    from: Option<EvtxTimestamp>,
+    to: Option<EvtxTimestamp>,
+}
+
+impl EvtxFilter {
+    pub fn with_channels(mut self, channels: Vec<String>) -> Self {
+        self.channels = channels;
+        self
+    }
+
+    pub fn with_event_ids(mut self, event_ids: Vec<u64>) -> Self {
+        self.event_ids = event_ids;
+        self
+    }
+
+    pub fn with_providers(mut self, providers: Vec<String>) -> Self {
+        self.providers = providers;
+        self
+    }
+
+    /// only records which have been created between `from` and `to`
+    /// (both inclusive) are scanned
+    pub fn with_time_range(mut self, from: Option<EvtxTimestamp>, to: Option<EvtxTimestamp>) -> Self {
+        self.from = from;
+        self.to = to;
+        self
+    }
+
+    /// checks the timestamp of a record, which is known before the record is parsed
+    pub fn matches_timestamp(&self, timestamp: &DateTime<Utc>) -> bool {
+        self.from.map(|from| *timestamp >= from.0).unwrap_or(true)
+            && self.to.map(|to| *timestamp <= to.0).unwrap_or(true)
+    }
+
+    pub fn matches(&self, record: &EvtxRecordInfo) -> bool {
+        let matches_name = |names: &[String], value: &Option<String>| {
+            names.is_empty()
+                || value
+                    .as_ref()
+                    .map(|v| names.iter().any(|n| n.eq_ignore_ascii_case(v)))
+                    .unwrap_or(false)
+        };
+
+        matches_name(&self.channels, &record.channel)
+            && matches_name(&self.providers, &record.provider)
+            && (self.event_ids.is_empty()
+                || record
+                    .event_id
+                    .map(|id| self.event_ids.contains(&id))
+                    .unwrap_or(false))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EvtxFilter, EvtxTimestamp};
+    use crate::yara::evtx_record_info::EvtxRecordInfo;
+
+    #[test]
+    fn test_parse_timestamp() {
+        let expected: EvtxTimestamp = "2022-06-17T22:51:20Z".parse().unwrap();
+        assert_eq!("2022-06-18T00:51:20+02:00".parse::<EvtxTimestamp>().unwrap(), expected);
+        assert_eq!("2022-06-17 22:51:20".parse::<EvtxTimestamp>().unwrap(), expected);
+        assert_eq!(
+            "2022-06-17".parse::<EvtxTimestamp>().unwrap(),
+            "2022-06-17T00:00:00Z".parse::<EvtxTimestamp>().unwrap()
+        );
+        assert!("yesterday".parse::<EvtxTimestamp>().is_err());
+    }
+
+    #[test]
+    fn test_filter() {
+        let record = EvtxRecordInfo {
+            channel: Some("Security".to_owned()),
+            provider: Some("Microsoft-Windows-Security-Auditing".to_owned()),
+            event_id: Some(4688),
+            ..Default::default()
+        };
+        assert!(EvtxFilter::default().matches(&record));
+
+        let filter = EvtxFilter::default()
+            .with_channels(vec!["security".to_owned()])
+            .with_event_ids(vec![4688, 4624]);
+        assert!(filter.matches(&record));
+        assert!(!filter.clone().with_event_ids(vec![4624]).matches(&record));
+        assert!(!filter.with_providers(vec!["PowerShell".to_owned()]).matches(&record));
+
+        let filter = EvtxFilter::default().with_time_range(Some("2022-06-17".parse().unwrap()), None);
+        assert!(filter.matches_timestamp(&"2022-06-17T22:51:20Z".parse().unwrap()));
+        assert!(!filter.matches_timestamp(&"2022-06-16T22:51:20Z".parse().unwrap()));
+    }
+}
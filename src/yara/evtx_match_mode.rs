@@ -0,0 +1,15 @@
+use clap::ValueEnum;
+
+/// how the records of evtx files are passed to yara
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EvtxMatchMode {
+    // every string value of a record is scanned separately
+    #[default]
+    Field,
+
+    // every record is serialized as JSON and scanned as a whole
+    Json,
+
+    // every record is serialized as XML and scanned as a whole
+    Xml,
+}
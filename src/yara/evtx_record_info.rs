@@ -0,0 +1,131 @@
+use std::fmt::Display;
+
+use serde_json::{json, Value};
+
+/// information about the EVTX record in which a yara rule matched, which
+/// allows to pivot on the findings without reading the whole record
+#[derive(Clone, Default)]
+pub struct EvtxRecordInfo {
+    pub record_id: u64,
+    pub timestamp: String,
+    pub channel: Option<String>,
+    pub provider: Option<String>,
+    pub event_id: Option<u64>,
+    pub computer: Option<String>,
+
+    /// path of the field which matched, e.g. `Event.EventData.CommandLine`
+    pub field: Option<String>,
+
+    /// id of the PowerShell script block which has been reassembled from
+    /// several records, and the EventRecordIDs of these records
+    pub script_block_id: Option<String>,
+    pub script_block_records: Vec<u64>,
+}
+
+impl EvtxRecordInfo {
+    #[cfg(feature = "scan_evtx")]
+    pub fn new(record_id: u64, timestamp: String, data: &Value) -> Self {
+        let system = &data["Event"]["System"];
+        Self {
+            record_id,
+            timestamp,
+            channel: text_of(&system["Channel"]),
+            provider: text_of(&system["Provider"]["#attributes"]["Name"]),
+            event_id: text_of(&system["EventID"]).and_then(|id| id.parse().ok()),
+            computer: text_of(&system["Computer"]),
+            field: None,
+            script_block_id: None,
+            script_block_records: Vec::new(),
+        }
+    }
+
+    pub fn with_field(mut self, field: String) -> Self {
+        self.field = Some(field);
+        self
+    }
+
+    pub fn with_script_block(mut self, id: String, records: Vec<u64>) -> Self {
+        self.script_block_id = Some(id);
+        self.script_block_records = records;
+        self
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "record_id": self.record_id,
+            "timestamp": self.timestamp,
+            "channel": self.channel,
+            "provider": self.provider,
+            "event_id": self.event_id,
+            "computer": self.computer,
+            "field": self.field,
+            "script_block_id": self.script_block_id,
+            "script_block_records": self.script_block_records,
+        })
+    }
+}
+
+impl Display for EvtxRecordInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "EventRecordID {} ({}", self.record_id, self.timestamp)?;
+        if let Some(channel) = &self.channel {
+            write!(f, ", channel {channel}")?;
+        }
+        if let Some(provider) = &self.provider {
+            write!(f, ", provider {provider}")?;
+        }
+        if let Some(event_id) = self.event_id {
+            write!(f, ", EventID {event_id}")?;
+        }
+        if let Some(computer) = &self.computer {
+            write!(f, ", computer {computer}")?;
+        }
+        write!(f, ")")?;
+        if let Some(field) = &self.field {
+            write!(f, " in {field}")?;
+        }
+        if let Some(id) = &self.script_block_id {
+            let records: Vec<_> = self.script_block_records.iter().map(|r| r.to_string()).collect();
+            write!(f, " of script block {id} (EventRecordIDs {})", records.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// returns the text of a JSON value. Elements with attributes
+/// (e.g. `<EventID Qualifiers="0">4104</EventID>`) store their text in `#text`
+#[cfg(feature = "scan_evtx")]
+pub(super) fn text_of(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Object(o) => o.get("#text").and_then(text_of),
+        _ => None,
+    }
+}
+
+#[cfg(all(test, feature = "scan_evtx"))]
+mod tests {
+    use serde_json::json;
+
+    use super::EvtxRecordInfo;
+
+    #[test]
+    fn test_new() {
+        let data = json!({"Event": {"System": {
+            "Provider": {"#attributes": {"Name": "PowerShell"}},
+            "EventID": {"#attributes": {"Qualifiers": 0}, "#text": 400},
+            "Channel": "Windows PowerShell",
+            "Computer": "WinDevEval",
+        }}});
+        let info = EvtxRecordInfo::new(17, "2022-06-17T22:51:20.505344Z".to_owned(), &data)
+            .with_field("Event.EventData.Data".to_owned());
+        assert_eq!(info.event_id, Some(400));
+        assert_eq!(info.provider.as_deref(), Some("PowerShell"));
+        assert_eq!(
+            info.to_string(),
+            "EventRecordID 17 (2022-06-17T22:51:20.505344Z, channel Windows PowerShell, \
+             provider PowerShell, EventID 400, computer WinDevEval) in Event.EventData.Data"
+        );
+    }
+}
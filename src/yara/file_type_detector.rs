@@ -0,0 +1,322 @@
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use clap::ValueEnum;
+use filemagic::{magic, Magic};
+
+/// number of bytes which are required to detect all supported formats; tar
+/// archives have their signature at offset 257
+const HEADER_SIZE: usize = 512;
+
+const TAR_SIGNATURE_OFFSET: usize = 257;
+
+/// how the type of a file is determined
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FileTypeDetection {
+    /// use libmagic, and the built-in signatures if libmagic is not available
+    #[default]
+    Magic,
+
+    /// use only the built-in signatures
+    Signatures,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum FileType {
+    GZip,
+    BZip2,
+    XZ,
+    Zstd,
+    Lz4,
+    Lzma,
+    Compress,
+    Zip,
+    Tar,
+    SevenZip,
+    Evtx,
+    Reg,
+    Uncompressed,
+}
+
+impl FileType {
+    /// compressed streams contain exactly one file, which can be decompressed while reading
+    pub fn is_compressed_stream(&self) -> bool {
+        matches!(
+            self,
+            FileType::GZip
+                | FileType::BZip2
+                | FileType::XZ
+                | FileType::Zstd
+                | FileType::Lz4
+                | FileType::Lzma
+                | FileType::Compress
+        )
+    }
+
+    pub fn is_archive(&self) -> bool {
+        matches!(self, FileType::Zip | FileType::Tar | FileType::SevenZip)
+    }
+
+    /// interprets the output of libmagic
+    pub fn from_magic(m: &str) -> Self {
+        if m.starts_with("XZ compressed data") {
+            FileType::XZ
+        } else if m.starts_with("gzip compressed data") {
+            FileType::GZip
+        } else if m.starts_with("bzip2 compressed data") {
+            FileType::BZip2
+        } else if m.starts_with("Zstandard compressed data") {
+            FileType::Zstd
+        } else if m.starts_with("LZ4 compressed data") {
+            FileType::Lz4
+        } else if m.starts_with("LZMA compressed data") {
+            FileType::Lzma
+        } else if m.starts_with("compress'd data") {
+            FileType::Compress
+        } else if   m.starts_with("MS Windows Vista Event Log") ||
+                    m.starts_with("MS Windows 10-11 Event Log") {
+            FileType::Evtx
+        } else if m.starts_with("MS Windows registry file") {
+            FileType::Reg
+        } else if m.starts_with("Zip archive data") {
+            FileType::Zip
+        } else if m.contains("tar archive") {
+            FileType::Tar
+        } else if m.starts_with("7-zip archive data") {
+            FileType::SevenZip
+        } else {
+            if m.contains("compressed data") {
+                log::warn!("unknown compression format: '{}', file will be handled without decompression", m);
+            }
+            FileType::Uncompressed
+        }
+    }
+
+    /// detects the file type using the signature at the beginning of `header`
+    pub fn from_signature(header: &[u8]) -> Self {
+        let has_signature = |offset: usize, signature: &[u8]| {
+            header.get(offset..offset + signature.len()) == Some(signature)
+        };
+
+        if has_signature(0, b"\xFD7zXZ\0") {
+            FileType::XZ
+        } else if has_signature(0, b"\x1F\x8B") {
+            FileType::GZip
+        } else if has_signature(0, b"BZh") && matches!(header.get(3), Some(b'1'..=b'9')) {
+            FileType::BZip2
+        } else if has_signature(0, b"\x28\xB5\x2F\xFD") {
+            FileType::Zstd
+        } else if has_signature(0, b"\x04\x22\x4D\x18") || has_signature(0, b"\x02\x21\x4C\x18") {
+            FileType::Lz4
+        } else if is_lzma_header(header) {
+            FileType::Lzma
+        } else if has_signature(0, b"\x1F\x9D") && matches!(header.get(2), Some(flags) if is_compress_flags(*flags)) {
+            FileType::Compress
+        } else if has_signature(0, b"ElfFile\0") {
+            FileType::Evtx
+        } else if has_signature(0, b"regf") {
+            FileType::Reg
+        } else if has_signature(0, b"PK\x03\x04") || has_signature(0, b"PK\x05\x06") {
+            FileType::Zip
+        } else if has_signature(TAR_SIGNATURE_OFFSET, b"ustar") {
+            FileType::Tar
+        } else if has_signature(0, b"7z\xBC\xAF\x27\x1C") {
+            FileType::SevenZip
+        } else {
+            FileType::Uncompressed
+        }
+    }
+
+    /// a description which starts like the output of libmagic, so that
+    /// [`FileType::from_magic`] returns the same file type
+    pub fn description(&self) -> Option<&'static str> {
+        Some(match self {
+            FileType::GZip => "gzip compressed data",
+            FileType::BZip2 => "bzip2 compressed data",
+            FileType::XZ => "XZ compressed data",
+            FileType::Zstd => "Zstandard compressed data",
+            FileType::Lz4 => "LZ4 compressed data",
+            FileType::Lzma => "LZMA compressed data",
+            FileType::Compress => "compress'd data",
+            FileType::Zip => "Zip archive data",
+            FileType::Tar => "POSIX tar archive",
+            FileType::SevenZip => "7-zip archive data",
+            FileType::Evtx => "MS Windows Vista Event Log",
+            FileType::Reg => "MS Windows registry file",
+            FileType::Uncompressed => return None,
+        })
+    }
+}
+
+/// LZMA streams have no magic number, so the header is checked like xz-utils
+/// does: the default properties (lc=3, lp=0, pb=2) must be followed by a
+/// dictionary size of 2^n or 2^n + 2^(n-1) and a plausible uncompressed size
+fn is_lzma_header(header: &[u8]) -> bool {
+    if header.len() < 13 || header[0] != 0x5D {
+        return false;
+    }
+    let dict_size = u32::from_le_bytes([header[1], header[2], header[3], header[4]]);
+    let uncompressed_size = u64::from_le_bytes([
+        header[5], header[6], header[7], header[8], header[9], header[10], header[11], header[12],
+    ]);
+
+    // xz-utils rounds the dictionary size up to the next value of this form
+    let is_valid_dict_size = dict_size >= 4096 && {
+        let n = 31 - dict_size.leading_zeros();
+        dict_size == 1 << n || (n > 0 && dict_size == (1 << n) | (1 << (n - 1)))
+    };
+
+    // the size is unknown (u64::MAX), or smaller than 256 GiB
+    is_valid_dict_size && (uncompressed_size == u64::MAX || uncompressed_size < 1 << 38)
+}
+
+/// the third byte of Unix compress data contains the maximum code width
+/// (9 to 16 bits) and the block mode flag; the other bits are not used
+fn is_compress_flags(flags: u8) -> bool {
+    (9..=16).contains(&(flags & 0x1f)) && flags & 0x60 == 0
+}
+
+thread_local! {
+    /// a libmagic cookie must not be used by several threads at once, so every
+    /// thread opens its own cookie once, instead of opening one for every file
+    static MAGIC: RefCell<Option<Magic>> = const { RefCell::new(None) };
+}
+
+/// determines the type of files and buffers, either with libmagic or with
+/// built-in signatures. The detector is shared by all threads.
+pub struct FileTypeDetector {
+    can_use_magic: AtomicBool,
+}
+
+impl Default for FileTypeDetector {
+    fn default() -> Self {
+        Self::new(FileTypeDetection::default())
+    }
+}
+
+impl FileTypeDetector {
+    pub fn new(detection: FileTypeDetection) -> Self {
+        Self {
+            can_use_magic: AtomicBool::new(detection == FileTypeDetection::Magic),
+        }
+    }
+
+    /// runs `f` with the libmagic cookie of the current thread. Returns `None`
+    /// if libmagic is not used or cannot be loaded
+    fn with_magic<T>(&self, f: impl FnOnce(&Magic) -> T) -> Option<T> {
+        if !self.can_use_magic.load(Ordering::Relaxed) {
+            return None;
+        }
+        MAGIC.with(|cookie| {
+            let mut cookie = cookie.borrow_mut();
+            if cookie.is_none() {
+                match magic!() {
+                    Ok(m) => *cookie = Some(m),
+                    Err(why) => {
+                        if self.can_use_magic.swap(false, Ordering::Relaxed) {
+                            log::error!("unable to use magic: {why}");
+                            log::warn!("using built-in signatures to detect compressed files, hive files and event log files");
+                        }
+                        return None;
+                    }
+                }
+            }
+            cookie.as_ref().map(f)
+        })
+    }
+
+    /// returns the type of `file` and a description of it, which is passed to yara as `filetype`
+    pub fn detect_file(&self, file: &Path) -> (FileType, Option<String>) {
+        if let Some(result) = self.with_magic(|m| m.file(file)) {
+            match result {
+                Ok(magic) => {
+                    log::info!("treating '{}' as '{}'", file.display(), &magic);
+                    return (FileType::from_magic(&magic), Some(magic));
+                }
+                Err(why) => log::warn!("unable to determine file type for '{}': {}", file.display(), why),
+            }
+        }
+
+        let mut header = Vec::with_capacity(HEADER_SIZE);
+        match File::open(file).and_then(|f| f.take(HEADER_SIZE as u64).read_to_end(&mut header)) {
+            Ok(_) => {
+                let file_type = FileType::from_signature(&header);
+                let description = file_type.description().map(str::to_owned);
+                (file_type, description)
+            }
+            Err(why) => {
+                log::warn!("unable to determine file type for '{}': {}", file.display(), why);
+                (FileType::Uncompressed, None)
+            }
+        }
+    }
+
+    /// returns the type of decompressed data
+    pub fn detect_buffer(&self, buffer: &[u8]) -> FileType {
+        match self.with_magic(|m| m.buffer(buffer)) {
+            Some(Ok(magic)) => FileType::from_magic(&magic),
+            _ => FileType::from_signature(buffer),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FileType, FileTypeDetection, FileTypeDetector};
+
+    fn data_path(file: &str) -> std::path::PathBuf {
+        std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data").join(file)
+    }
+
+    #[test]
+    fn test_signatures() {
+        let detector = FileTypeDetector::new(FileTypeDetection::Signatures);
+        let expected = [
+            ("sample1.txt.gz", FileType::GZip),
+            ("sample1.txt.bz2", FileType::BZip2),
+            ("sample1.txt.xz", FileType::XZ),
+            ("messages.zst", FileType::Zstd),
+            ("messages.lz4", FileType::Lz4),
+            ("messages.lzma", FileType::Lzma),
+            ("messages.Z", FileType::Compress),
+            ("sample.zip", FileType::Zip),
+            ("solid.7z", FileType::SevenZip),
+            ("Security.evtx", FileType::Evtx),
+            ("NTUSER.DAT", FileType::Reg),
+            ("sample1.txt", FileType::Uncompressed),
+        ];
+        for (file, file_type) in expected {
+            let (detected, description) = detector.detect_file(&data_path(file));
+            assert_eq!(detected, file_type, "{file}");
+
+            // the description must be interpreted like the output of libmagic
+            if let Some(description) = description {
+                assert_eq!(FileType::from_magic(&description), file_type);
+            }
+        }
+    }
+
+    #[test]
+    fn test_short_signatures() {
+        // plain data which starts like LZMA or compress data must not be unpacked
+        assert_eq!(FileType::from_signature(b"\x5D\0\0\x01\x02\x03\x04\x05\x06\x07\x08\x09\x0a"), FileType::Uncompressed);
+        assert_eq!(FileType::from_signature(b"\x1F\x9D\x05 plain data"), FileType::Uncompressed);
+        assert_eq!(FileType::from_signature(b"\x1F\x9D\xf0 plain data"), FileType::Uncompressed);
+
+        let mut lzma = b"\x5D\0\0\x18\0".to_vec();
+        lzma.extend_from_slice(&u64::MAX.to_le_bytes());
+        assert_eq!(FileType::from_signature(&lzma), FileType::Lzma);
+        assert_eq!(FileType::from_signature(b"\x1F\x9D\x8c"), FileType::Compress);
+    }
+
+    #[test]
+    fn test_tar_signature() {
+        let mut header = vec![0u8; 512];
+        header[257..263].copy_from_slice(b"ustar\0");
+        assert_eq!(FileType::from_signature(&header), FileType::Tar);
+        assert_eq!(FileType::from_signature(b"ustar"), FileType::Uncompressed);
+    }
+}
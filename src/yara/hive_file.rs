@@ -0,0 +1,114 @@
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::Read;
+use std::panic::AssertUnwindSafe;
+use std::path::{Path, PathBuf};
+
+use anyhow::anyhow;
+use nt_hive2::transactionlog::TransactionLog;
+use nt_hive2::{BaseBlock, CleanHive, ContainsHive, DirtyHive, Hive, HiveParseMode};
+
+use super::registry_entry_info::TransactionLogStatus;
+
+const HIVE_SIGNATURE: &[u8] = b"regf";
+
+/// offset of the file type in the base block of a registry hive; primary
+/// hive files have the file type 0, transaction logs have 1, 2 or 6
+const HIVE_FILE_TYPE_OFFSET: usize = 0x1C;
+
+/// checks if `file` is a primary hive file. Transaction logs start with a
+/// base block as well, but they have another file type.
+pub fn is_primary_hive_file(file: &Path) -> std::io::Result<bool> {
+    let mut base_block = [0u8; 0x20];
+    match File::open(file)?.read_exact(&mut base_block) {
+        Ok(()) => Ok(base_block.starts_with(HIVE_SIGNATURE)
+            && base_block[HIVE_FILE_TYPE_OFFSET..HIVE_FILE_TYPE_OFFSET + 4] == [0, 0, 0, 0]),
+        Err(why) if why.kind() == std::io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(why) => Err(why),
+    }
+}
+
+/// opens a registry hive. If the hive is dirty, its transaction logs are
+/// searched next to the hive file and applied, so that the most recent
+/// changes are scanned as well.
+pub fn open_hive(file: &Path) -> anyhow::Result<(Hive<File, CleanHive>, TransactionLogStatus)> {
+    let hive = Hive::new(File::open(file)?, HiveParseMode::NormalWithBaseBlock)?;
+    if !hive.base_block().map(|b| b.is_dirty()).unwrap_or(false) {
+        return Ok((hive.treat_hive_as_clean(), TransactionLogStatus::NotNeeded));
+    }
+
+    let logs = find_transaction_logs(file)?;
+    if logs.is_empty() {
+        log::warn!(
+            "'{}' is dirty, but no transaction logs have been found; recent changes will not be scanned",
+            file.display()
+        );
+        return Ok((hive.treat_hive_as_clean(), TransactionLogStatus::Missing));
+    }
+
+    let names: Vec<_> = logs
+        .iter()
+        .filter_map(|log| log.file_name())
+        .map(|name| name.to_string_lossy().to_string())
+        .collect();
+
+    match apply_transaction_logs(hive, &logs) {
+        Ok(hive) => {
+            log::info!("applied transaction logs {} to '{}'", names.join(", "), file.display());
+            Ok((hive, TransactionLogStatus::Applied(names)))
+        }
+        Err(why) => {
+            log::warn!(
+                "unable to apply transaction logs to '{}': {why}; recent changes will not be scanned",
+                file.display()
+            );
+            let hive = Hive::new(File::open(file)?, HiveParseMode::NormalWithBaseBlock)?;
+            Ok((hive.treat_hive_as_clean(), TransactionLogStatus::Failed(why.to_string())))
+        }
+    }
+}
+
+/// returns the paths of `<hive>.LOG1` and `<hive>.LOG2`, if they exist
+fn find_transaction_logs(file: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let (directory, hive_name) = match (file.parent(), file.file_name()) {
+        (Some(directory), Some(hive_name)) => (directory, hive_name.to_string_lossy()),
+        _ => return Ok(Vec::new()),
+    };
+    let directory = if directory.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        directory
+    };
+
+    let mut logs = Vec::new();
+    for extension in ["LOG1", "LOG2"] {
+        let log_name = format!("{hive_name}.{extension}");
+        for entry in std::fs::read_dir(directory)? {
+            let entry = entry?;
+            if entry.file_name().to_string_lossy().eq_ignore_ascii_case(&log_name) && entry.path().is_file() {
+                logs.push(entry.path());
+                break;
+            }
+        }
+    }
+    Ok(logs)
+}
+
+fn apply_transaction_logs(hive: Hive<File, DirtyHive>, logs: &[PathBuf]) -> anyhow::Result<Hive<File, CleanHive>> {
+    let mut logs = logs.iter().map(|log| -> anyhow::Result<TransactionLog> {
+        Ok(TransactionLog::try_from(File::open(log)?)?)
+    });
+
+    let hive = match logs.next() {
+        None => return Ok(hive.treat_hive_as_clean()),
+        Some(log) => hive.with_transaction_log(log?)?,
+    };
+    let hive = match logs.next() {
+        None => std::panic::catch_unwind(AssertUnwindSafe(|| hive.apply_logs())),
+        Some(log) => {
+            let hive = hive.with_transaction_log(log?)?;
+            std::panic::catch_unwind(AssertUnwindSafe(|| hive.apply_logs()))
+        }
+    };
+    hive.map_err(|_| anyhow!("the transaction logs contain invalid data"))
+}
@@ -0,0 +1,350 @@
+use std::convert::TryInto;
+
+use chrono::{DateTime, TimeZone, Utc};
+
+/// the hive bins data starts after the base block
+const HIVE_BINS_OFFSET: usize = 4096;
+const HIVE_BIN_HEADER_SIZE: usize = 32;
+const CELL_HEADER_SIZE: usize = 4;
+
+const KEY_HIVE_ENTRY: u16 = 0x0004;
+const KEY_COMP_NAME: u16 = 0x0020;
+const VALUE_COMP_NAME: u16 = 0x0001;
+
+/// limits the number of parent keys which are followed to build a key path
+const MAX_KEY_DEPTH: usize = 512;
+
+/// values which are larger than this are stored in a big data record, which is not recovered
+const MAX_CELL_DATA_SIZE: usize = 16344;
+
+/// the contents of a deleted value, as far as it could be recovered
+pub enum RecoveredData {
+    String(String),
+    MultiString(Vec<String>),
+    Binary(Vec<u8>),
+    Number(u64),
+    Other,
+}
+
+pub enum RecoveredEntry {
+    Key {
+        /// names of the parent keys which could be resolved, and the name of the key itself
+        path: String,
+        name: String,
+        last_written: DateTime<Utc>,
+    },
+    Value {
+        name: String,
+        value_type: Option<String>,
+        data: RecoveredData,
+    },
+}
+
+/// a key or value which has been found in an unallocated cell of a hive
+pub struct RecoveredCell {
+    /// offset of the cell, relative to the start of the hive bins data
+    pub offset: u64,
+    pub entry: RecoveredEntry,
+}
+
+/// searches the unallocated cells of a registry hive for key nodes and key
+/// values of deleted keys. Unallocated cells can contain several old cells,
+/// because adjacent free cells are merged; so every cell boundary inside of
+/// an unallocated cell is checked.
+pub fn carve_deleted_cells(hive: &[u8]) -> Vec<RecoveredCell> {
+    let mut cells = Vec::new();
+    let mut bin_offset = HIVE_BINS_OFFSET;
+
+    while hive.get(bin_offset..bin_offset + 4) == Some(b"hbin") {
+        let bin_size = match read_u32(hive, bin_offset + 8) {
+            Some(size) if size as usize >= HIVE_BIN_HEADER_SIZE && size & 0xfff == 0 => size as usize,
+            _ => {
+                log::warn!("invalid hive bin at offset 0x{bin_offset:x}, stop carving");
+                break;
+            }
+        };
+        let bin_end = (bin_offset + bin_size).min(hive.len());
+
+        let mut cell_offset = bin_offset + HIVE_BIN_HEADER_SIZE;
+        while cell_offset + CELL_HEADER_SIZE <= bin_end {
+            let raw_size = match read_u32(hive, cell_offset) {
+                Some(size) => size as i32,
+                None => break,
+            };
+            let cell_size = raw_size.unsigned_abs() as usize;
+            if cell_size < CELL_HEADER_SIZE || cell_size % 8 != 0 {
+                log::debug!("invalid cell size at offset 0x{cell_offset:x}, skipping the rest of the hive bin");
+                break;
+            }
+
+            // a positive size marks an unallocated cell
+            if raw_size > 0 {
+                let cell_end = (cell_offset + cell_size).min(bin_end);
+                let mut candidate = cell_offset;
+                while candidate + CELL_HEADER_SIZE < cell_end {
+                    if let Some(entry) = recover_entry(hive, candidate + CELL_HEADER_SIZE) {
+                        cells.push(RecoveredCell {
+                            offset: (candidate - HIVE_BINS_OFFSET) as u64,
+                            entry,
+                        });
+                    }
+                    candidate += 8;
+                }
+            }
+            cell_offset += cell_size;
+        }
+        bin_offset += bin_size;
+    }
+    cells
+}
+
+fn recover_entry(hive: &[u8], offset: usize) -> Option<RecoveredEntry> {
+    match hive.get(offset..offset + 2)? {
+        b"nk" => recover_key(hive, offset),
+        b"vk" => recover_value(hive, offset),
+        _ => None,
+    }
+}
+
+fn recover_key(hive: &[u8], offset: usize) -> Option<RecoveredEntry> {
+    let name = key_name(hive, offset)?;
+    let last_written = filetime_to_datetime(read_u64(hive, offset + 0x04)?)?;
+
+    let mut path = parent_path(hive, read_u32(hive, offset + 0x10)?);
+    path.push(name.clone());
+    Some(RecoveredEntry::Key {
+        path: path.join("\\"),
+        name,
+        last_written,
+    })
+}
+
+fn recover_value(hive: &[u8], offset: usize) -> Option<RecoveredEntry> {
+    let name_length = read_u16(hive, offset + 0x02)? as usize;
+    let data_size = read_u32(hive, offset + 0x04)?;
+    let data_offset = read_u32(hive, offset + 0x08)?;
+    let data_type = read_u32(hive, offset + 0x0C)?;
+    let flags = read_u16(hive, offset + 0x10)?;
+    let name = decode_name(
+        hive.get(offset + 0x14..offset + 0x14 + name_length)?,
+        flags & VALUE_COMP_NAME != 0,
+    )?;
+
+    // resident data is stored in the data offset field
+    let raw_data = if data_size & 0x8000_0000 != 0 {
+        let size = (data_size & 0x7fff_ffff) as usize;
+        if size > 4 {
+            return None;
+        }
+        Some(data_offset.to_le_bytes()[..size].to_vec())
+    } else {
+        cell_data(hive, data_offset, data_size as usize)
+    };
+
+    let data = match raw_data {
+        None => RecoveredData::Other,
+        Some(raw_data) => match data_type {
+            1 | 2 => RecoveredData::String(decode_utf16(&raw_data)),
+            7 => RecoveredData::MultiString(
+                decode_utf16(&raw_data)
+                    .split('\0')
+                    .filter(|s| !s.is_empty())
+                    .map(str::to_owned)
+                    .collect(),
+            ),
+            3 => RecoveredData::Binary(raw_data),
+            4 if raw_data.len() == 4 => RecoveredData::Number(read_u32(&raw_data, 0)?.into()),
+            5 if raw_data.len() == 4 => {
+                RecoveredData::Number(u32::from_be_bytes(raw_data[..].try_into().ok()?).into())
+            }
+            11 if raw_data.len() == 8 => RecoveredData::Number(read_u64(&raw_data, 0)?),
+            _ => RecoveredData::Other,
+        },
+    };
+
+    Some(RecoveredEntry::Value {
+        name,
+        value_type: value_type_name(data_type).map(str::to_owned),
+        data,
+    })
+}
+
+/// returns the names of all parent keys, starting below the root key
+fn parent_path(hive: &[u8], mut parent_offset: u32) -> Vec<String> {
+    let mut path = Vec::new();
+    for _ in 0..MAX_KEY_DEPTH {
+        let offset = HIVE_BINS_OFFSET + parent_offset as usize + CELL_HEADER_SIZE;
+        if hive.get(offset..offset + 2) != Some(b"nk") {
+            break;
+        }
+        match read_u16(hive, offset + 0x02) {
+            Some(flags) if flags & KEY_HIVE_ENTRY == 0 => (),
+            _ => break,
+        }
+        match (key_name(hive, offset), read_u32(hive, offset + 0x10)) {
+            (Some(name), Some(next_offset)) => {
+                path.push(name);
+                parent_offset = next_offset;
+            }
+            _ => break,
+        }
+    }
+    path.reverse();
+    path
+}
+
+fn key_name(hive: &[u8], offset: usize) -> Option<String> {
+    let flags = read_u16(hive, offset + 0x02)?;
+    let name_length = read_u16(hive, offset + 0x48)? as usize;
+    if name_length == 0 {
+        return None;
+    }
+    decode_name(
+        hive.get(offset + 0x4C..offset + 0x4C + name_length)?,
+        flags & KEY_COMP_NAME != 0,
+    )
+}
+
+fn cell_data(hive: &[u8], cell_offset: u32, size: usize) -> Option<Vec<u8>> {
+    if size > MAX_CELL_DATA_SIZE {
+        return None;
+    }
+    let offset = HIVE_BINS_OFFSET + cell_offset as usize + CELL_HEADER_SIZE;
+    hive.get(offset..offset + size).map(|data| data.to_vec())
+}
+
+/// compressed names are stored as Latin-1, all other names as UTF-16LE
+fn decode_name(raw: &[u8], compressed: bool) -> Option<String> {
+    let name = if compressed {
+        raw.iter().map(|b| *b as char).collect()
+    } else {
+        decode_utf16(raw)
+    };
+    if name.chars().any(char::is_control) {
+        None
+    } else {
+        Some(name)
+    }
+}
+
+fn decode_utf16(raw: &[u8]) -> String {
+    let chars: Vec<u16> = raw.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]])).collect();
+    String::from_utf16_lossy(&chars).trim_end_matches('\0').to_owned()
+}
+
+fn filetime_to_datetime(filetime: u64) -> Option<DateTime<Utc>> {
+    const SECONDS_TO_UNIX_EPOCH: i64 = 11_644_473_600;
+    let seconds = (filetime / 10_000_000) as i64 - SECONDS_TO_UNIX_EPOCH;
+    let nanos = (filetime % 10_000_000) as u32 * 100;
+    Utc.timestamp_opt(seconds, nanos).single()
+}
+
+/// uses the same names as `nt_hive2::KeyValueDataType`
+fn value_type_name(data_type: u32) -> Option<&'static str> {
+    Some(match data_type {
+        0x00 => "RegNone",
+        0x01 => "RegSZ",
+        0x02 => "RegExpandSZ",
+        0x03 => "RegBinary",
+        0x04 => "RegDWord",
+        0x05 => "RegDWordBigEndian",
+        0x06 => "RegLink",
+        0x07 => "RegMultiSZ",
+        0x08 => "RegResourceList",
+        0x09 => "RegFullResourceDescriptor",
+        0x0a => "RegResourceRequirementsList",
+        0x0b => "RegQWord",
+        0x10 => "RegFileTime",
+        _ => return None,
+    })
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(data.get(offset..offset + 2)?.try_into().ok()?))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(offset..offset + 4)?.try_into().ok()?))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(data.get(offset..offset + 8)?.try_into().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{carve_deleted_cells, RecoveredData, RecoveredEntry, HIVE_BINS_OFFSET};
+
+    /// builds a hive with a single hive bin, which contains an allocated root
+    /// key, and an unallocated cell with a deleted subkey and a deleted value
+    fn hive_with_deleted_cells() -> Vec<u8> {
+        fn key_node(flags: u16, parent: u32, name: &str) -> Vec<u8> {
+            let mut nk = vec![0u8; 0x4C];
+            nk[0..2].copy_from_slice(b"nk");
+            nk[0x02..0x04].copy_from_slice(&flags.to_le_bytes());
+            nk[0x04..0x0C].copy_from_slice(&132_999_264_000_000_000u64.to_le_bytes());
+            nk[0x10..0x14].copy_from_slice(&parent.to_le_bytes());
+            nk[0x48..0x4A].copy_from_slice(&(name.len() as u16).to_le_bytes());
+            nk.extend_from_slice(name.as_bytes());
+            nk
+        }
+
+        fn cell(size: i32, content: &[u8]) -> Vec<u8> {
+            let mut cell = size.to_le_bytes().to_vec();
+            cell.extend_from_slice(content);
+            cell.resize(size.unsigned_abs() as usize, 0);
+            cell
+        }
+
+        let mut value = vec![0u8; 0x14];
+        value[0..2].copy_from_slice(b"vk");
+        value[0x02..0x04].copy_from_slice(&3u16.to_le_bytes());
+        value[0x04..0x08].copy_from_slice(&0x8000_0002u32.to_le_bytes());
+        value[0x08..0x0C].copy_from_slice(b"a\0\0\0");
+        value[0x0C..0x10].copy_from_slice(&1u32.to_le_bytes());
+        value[0x10..0x12].copy_from_slice(&1u16.to_le_bytes());
+        value.extend_from_slice(b"Run");
+
+        let mut bin = b"hbin".to_vec();
+        bin.resize(32, 0);
+        bin[8..12].copy_from_slice(&4096u32.to_le_bytes());
+        bin.extend(cell(-0x60, &key_node(0x24, 0, "ROOT")));
+
+        // the unallocated cell contains two old cells, which have been merged
+        let mut unallocated = cell(0x60, &key_node(0x20, 0x20, "evil"));
+        unallocated.extend(cell(-0x20, &value));
+        bin.extend(cell(0xC0, &unallocated[4..]));
+        bin.resize(4096, 0);
+
+        let mut hive = vec![0u8; HIVE_BINS_OFFSET];
+        hive[0..4].copy_from_slice(b"regf");
+        hive.extend(bin);
+        hive
+    }
+
+    #[test]
+    fn test_carve_deleted_cells() {
+        let cells = carve_deleted_cells(&hive_with_deleted_cells());
+        assert_eq!(cells.len(), 2);
+
+        assert_eq!(cells[0].offset, 0x80);
+        match &cells[0].entry {
+            RecoveredEntry::Key { path, name, last_written } => {
+                assert_eq!(path, "evil");
+                assert_eq!(name, "evil");
+                assert_eq!(last_written.to_rfc3339(), "2022-06-17T08:00:00+00:00");
+            }
+            _ => panic!("expected a key"),
+        }
+
+        assert_eq!(cells[1].offset, 0xE0);
+        match &cells[1].entry {
+            RecoveredEntry::Value { name, value_type, data } => {
+                assert_eq!(name, "Run");
+                assert_eq!(value_type.as_deref(), Some("RegSZ"));
+                assert!(matches!(data, RecoveredData::String(s) if s == "a"));
+            }
+            _ => panic!("expected a value"),
+        }
+    }
+}
@@ -0,0 +1,229 @@
+use std::io::{self, ErrorKind, Read};
+
+const MAGIC: [u8; 2] = [0x1F, 0x9D];
+const INIT_BITS: u32 = 9;
+const CLEAR: u32 = 256;
+
+/// streaming decoder for data compressed by the Unix `compress` utility (`.Z` files).
+///
+/// Like `compress` itself, the decoder reads codes in groups of eight. If the
+/// code width changes or the table is cleared, the rest of the current group
+/// is skipped.
+pub struct LzwDecoder<R: Read> {
+    reader: R,
+    max_bits: u32,
+    block_mode: bool,
+
+    /// current code width
+    n_bits: u32,
+
+    /// the code width is increased if `free_entry` exceeds this value
+    max_code: u32,
+    free_entry: u32,
+
+    /// codes which have been read since the last change of the code width
+    codes_in_group: usize,
+    bit_buffer: u64,
+    bits_in_buffer: u32,
+
+    prefix: Vec<u32>,
+    suffix: Vec<u8>,
+    old_code: Option<u32>,
+    first_char: u8,
+
+    /// decoded bytes of the last code, in reverse order
+    stack: Vec<u8>,
+    finished: bool,
+}
+
+impl<R: Read> LzwDecoder<R> {
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        let mut header = [0u8; 3];
+        reader.read_exact(&mut header)?;
+        if header[..2] != MAGIC {
+            return Err(invalid_data("invalid compress header"));
+        }
+
+        let max_bits = (header[2] & 0x1F) as u32;
+        if !(INIT_BITS..=16).contains(&max_bits) {
+            return Err(invalid_data("unsupported code width in compress header"));
+        }
+        let block_mode = header[2] & 0x80 != 0;
+        let table_size = 1usize << max_bits;
+
+        Ok(Self {
+            reader,
+            max_bits,
+            block_mode,
+            n_bits: INIT_BITS,
+            max_code: Self::max_code_of(INIT_BITS, max_bits),
+            free_entry: if block_mode { 257 } else { 256 },
+            codes_in_group: 0,
+            bit_buffer: 0,
+            bits_in_buffer: 0,
+            prefix: vec![0; table_size],
+            suffix: (0..table_size).map(|c| c as u8).collect(),
+            old_code: None,
+            first_char: 0,
+            stack: Vec::new(),
+            finished: false,
+        })
+    }
+
+    fn max_code_of(n_bits: u32, max_bits: u32) -> u32 {
+        if n_bits == max_bits {
+            1 << max_bits
+        } else {
+            (1 << n_bits) - 1
+        }
+    }
+
+    /// reads the next code. Returns `None` if there is no more data
+    fn read_code(&mut self) -> io::Result<Option<u32>> {
+        while self.bits_in_buffer < self.n_bits {
+            let mut byte = [0u8];
+            match self.reader.read(&mut byte) {
+                Ok(0) => return Ok(None),
+                Ok(_) => {
+                    self.bit_buffer |= (byte[0] as u64) << self.bits_in_buffer;
+                    self.bits_in_buffer += 8;
+                }
+                Err(why) if why.kind() == ErrorKind::Interrupted => (),
+                Err(why) => return Err(why),
+            }
+        }
+
+        let code = (self.bit_buffer & ((1 << self.n_bits) - 1)) as u32;
+        self.bit_buffer >>= self.n_bits;
+        self.bits_in_buffer -= self.n_bits;
+        self.codes_in_group += 1;
+        Ok(Some(code))
+    }
+
+    /// skips the remaining codes of the current group of eight codes
+    fn skip_group(&mut self) -> io::Result<()> {
+        let remaining = (8 - self.codes_in_group % 8) % 8;
+        for _ in 0..remaining {
+            if self.read_code()?.is_none() {
+                break;
+            }
+        }
+        self.codes_in_group = 0;
+        Ok(())
+    }
+
+    /// decodes the next code onto the stack. Returns `false` if there is no more data
+    fn decode_next(&mut self) -> io::Result<bool> {
+        if self.free_entry > self.max_code {
+            self.skip_group()?;
+            self.n_bits += 1;
+            self.max_code = Self::max_code_of(self.n_bits, self.max_bits);
+        }
+
+        let code = match self.read_code()? {
+            None => return Ok(false),
+            Some(code) => code,
+        };
+
+        let old_code = match self.old_code {
+            None => {
+                if code > 255 {
+                    return Err(invalid_data("invalid first code in compressed data"));
+                }
+                self.old_code = Some(code);
+                self.first_char = code as u8;
+                self.stack.push(code as u8);
+                return Ok(true);
+            }
+            Some(old_code) => old_code,
+        };
+
+        if code == CLEAR && self.block_mode {
+            self.skip_group()?;
+            self.n_bits = INIT_BITS;
+            self.max_code = Self::max_code_of(INIT_BITS, self.max_bits);
+
+            // the next code creates an unused entry at the position of CLEAR
+            self.free_entry = 256;
+            return Ok(true);
+        }
+
+        let mut current = code;
+        if current >= self.free_entry {
+            // the code which is being defined right now: KwKwK
+            if current > self.free_entry {
+                return Err(invalid_data("invalid code in compressed data"));
+            }
+            self.stack.push(self.first_char);
+            current = old_code;
+        }
+
+        while current >= 256 {
+            self.stack.push(self.suffix[current as usize]);
+            current = self.prefix[current as usize];
+        }
+        self.first_char = current as u8;
+        self.stack.push(self.first_char);
+
+        if self.free_entry < 1 << self.max_bits {
+            self.prefix[self.free_entry as usize] = old_code;
+            self.suffix[self.free_entry as usize] = self.first_char;
+            self.free_entry += 1;
+        }
+        self.old_code = Some(code);
+        Ok(true)
+    }
+}
+
+impl<R: Read> Read for LzwDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut bytes = 0;
+        while bytes < buf.len() {
+            match self.stack.pop() {
+                Some(b) => {
+                    buf[bytes] = b;
+                    bytes += 1;
+                }
+                None if self.finished => break,
+                None => {
+                    if !self.decode_next()? {
+                        self.finished = true;
+                    }
+                }
+            }
+        }
+        Ok(bytes)
+    }
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, msg.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::LzwDecoder;
+
+    #[test]
+    fn test_decode() {
+        // "abababab", compressed with 9 bit codes: 'a', 'b', 257, 259, 'b'
+        let codes: [u32; 5] = [0x61, 0x62, 257, 259, 0x62];
+        let mut data = vec![0x1F, 0x9D, 0x90];
+        let mut bits = 0u64;
+        for (idx, code) in codes.iter().enumerate() {
+            bits |= (*code as u64) << (9 * idx);
+        }
+        data.extend_from_slice(&bits.to_le_bytes()[..6]);
+
+        let mut output = Vec::new();
+        LzwDecoder::new(&data[..]).unwrap().read_to_end(&mut output).unwrap();
+        assert_eq!(output, b"abababab");
+    }
+
+    #[test]
+    fn test_invalid_header() {
+        assert!(LzwDecoder::new(&[0x1F, 0x8B, 0x08][..]).is_err());
+    }
+}
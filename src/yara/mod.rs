@@ -1,7 +1,44 @@
 mod yara_scanner;
 mod yara_finding;
 mod yara_string;
+mod yara_metadata;
 mod yara_externals;
-mod yara_error;
+mod yara_rules_loader;
+mod yara_rule_filter;
+mod yara_rule_references;
+mod archive_member;
+#[cfg(feature = "scan_evtx")]
+mod evtx_chunk_reader;
+mod evtx_filter;
+mod evtx_match_mode;
+mod evtx_record_info;
+mod file_type_detector;
+#[cfg(feature = "scan_reg")]
+mod hive_file;
+#[cfg(feature = "scan_reg")]
+mod hive_slack_carver;
+mod lzw_decoder;
+mod registry_entry_info;
+#[cfg(feature = "scan_reg")]
+mod registry_value_decoder;
+#[cfg(feature = "scan_evtx")]
+mod script_block_assembler;
+mod tar_reader;
+mod sevenzip_reader;
+mod unscanned_entry;
 
-pub (crate) use yara_scanner::*;
\ No newline at end of file
+pub (crate) use yara_scanner::*;
+pub (crate) use yara_rules_loader::*;
+pub (crate) use yara_rule_filter::*;
+pub (crate) use yara_rule_references::RuleReferences;
+pub (crate) use yara_externals::ExternalVariable;
+pub (crate) use evtx_filter::{EvtxFilter, EvtxTimestamp};
+pub (crate) use evtx_match_mode::EvtxMatchMode;
+pub (crate) use evtx_record_info::EvtxRecordInfo;
+pub (crate) use file_type_detector::FileTypeDetection;
+#[cfg(feature = "scan_evtx")]
+pub (crate) use evtx_chunk_reader::EvtxChunkReader;
+#[cfg(feature = "scan_reg")]
+pub (crate) use hive_file::{is_primary_hive_file, open_hive};
+#[cfg(feature = "scan_reg")]
+pub (crate) use registry_entry_info::{RegistryEntryInfo, TransactionLogStatus};
\ No newline at end of file
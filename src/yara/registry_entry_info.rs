@@ -0,0 +1,205 @@
+use std::fmt::Display;
+
+use serde_json::{json, Value};
+
+/// the part of a registry entry which has been scanned
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RegistryField {
+    KeyName,
+    ValueName,
+    ValueData,
+}
+
+impl Display for RegistryField {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RegistryField::KeyName => write!(f, "key name"),
+            RegistryField::ValueName => write!(f, "value name"),
+            RegistryField::ValueData => write!(f, "value data"),
+        }
+    }
+}
+
+/// tells whether the transaction logs (`.LOG1`/`.LOG2`) of a hive have been
+/// applied before the hive was scanned
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TransactionLogStatus {
+    /// the hive is clean, so there is nothing to apply
+    NotNeeded,
+
+    /// the hive is dirty, and the named transaction logs have been applied
+    Applied(Vec<String>),
+
+    /// the hive is dirty, but no transaction logs have been found
+    Missing,
+
+    /// the hive is dirty, but the transaction logs could not be applied
+    Failed(String),
+}
+
+impl Display for TransactionLogStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TransactionLogStatus::NotNeeded => write!(f, "not needed"),
+            TransactionLogStatus::Applied(logs) => write!(f, "applied ({})", logs.join(", ")),
+            TransactionLogStatus::Missing => write!(f, "missing"),
+            TransactionLogStatus::Failed(why) => write!(f, "failed: {why}"),
+        }
+    }
+}
+
+/// information about the registry key or value in which a yara rule matched
+#[derive(Clone)]
+pub struct RegistryEntryInfo {
+    pub hive: String,
+
+    /// path of the key, relative to the root key of the hive, e.g. `Software\Microsoft`
+    pub key_path: String,
+    pub last_written: String,
+    pub value_name: Option<String>,
+    pub value_type: Option<String>,
+    pub field: RegistryField,
+    pub transaction_logs: TransactionLogStatus,
+
+    /// offset of the unallocated cell, if the entry has been recovered from a deleted cell
+    pub recovered_cell: Option<u64>,
+
+    /// how the value data has been decoded before it was scanned, e.g. `UTF-16LE -> base64`
+    pub decoding: Option<String>,
+}
+
+impl RegistryEntryInfo {
+    pub fn new(hive: &str, key_path: &str, last_written: String) -> Self {
+        Self {
+            hive: hive.to_owned(),
+            key_path: key_path.to_owned(),
+            last_written,
+            value_name: None,
+            value_type: None,
+            field: RegistryField::KeyName,
+            transaction_logs: TransactionLogStatus::NotNeeded,
+            recovered_cell: None,
+            decoding: None,
+        }
+    }
+
+    pub fn with_value(mut self, name: &str, value_type: Option<String>) -> Self {
+        self.value_name = Some(name.to_owned());
+        self.value_type = value_type;
+        self.field = RegistryField::ValueData;
+        self
+    }
+
+    pub fn with_field(mut self, field: RegistryField) -> Self {
+        self.field = field;
+        self
+    }
+
+    pub fn with_transaction_logs(mut self, status: TransactionLogStatus) -> Self {
+        self.transaction_logs = status;
+        self
+    }
+
+    pub fn with_recovered_cell(mut self, offset: u64) -> Self {
+        self.recovered_cell = Some(offset);
+        self
+    }
+
+    pub fn with_decoding(mut self, decoding: String) -> Self {
+        self.decoding = Some(decoding);
+        self
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "hive": self.hive,
+            "key_path": self.key_path,
+            "last_written": self.last_written,
+            "value_name": self.value_name,
+            "value_type": self.value_type,
+            "field": self.field.to_string(),
+            "transaction_logs": self.transaction_logs.to_string(),
+            "recovered": self.recovered_cell.is_some(),
+            "cell_offset": self.recovered_cell,
+            "decoding": self.decoding,
+        })
+    }
+}
+
+impl Display for RegistryEntryInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(offset) = self.recovered_cell {
+            write!(f, "recovered from deleted cell at offset 0x{offset:x}: ")?;
+        }
+
+        // the key of a recovered value is unknown
+        if !self.last_written.is_empty() {
+            write!(f, "key '{}' (last written {})", self.key_path, self.last_written)?;
+        }
+        if let Some(name) = &self.value_name {
+            let name = if name.is_empty() { "(default)" } else { name };
+            if !self.last_written.is_empty() {
+                write!(f, ", ")?;
+            }
+            write!(f, "value '{name}'")?;
+            if let Some(value_type) = &self.value_type {
+                write!(f, " ({value_type})")?;
+            }
+        }
+        write!(f, " in {}", self.field)?;
+        if let Some(decoding) = &self.decoding {
+            write!(f, " (decoded as {decoding})")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RegistryEntryInfo, RegistryField, TransactionLogStatus};
+
+    #[test]
+    fn test_display() {
+        let entry = RegistryEntryInfo::new("NTUSER.DAT", "Software\\Microsoft", "2022-06-17T22:51:20Z".to_owned());
+        assert_eq!(entry.to_string(), "key 'Software\\Microsoft' (last written 2022-06-17T22:51:20Z) in key name");
+
+        let entry = entry.with_value("", Some("RegSZ".to_owned()));
+        assert_eq!(entry.field, RegistryField::ValueData);
+        assert_eq!(
+            entry.with_field(RegistryField::ValueName).to_string(),
+            "key 'Software\\Microsoft' (last written 2022-06-17T22:51:20Z), value '(default)' (RegSZ) in value name"
+        );
+    }
+
+    #[test]
+    fn test_transaction_log_status() {
+        assert_eq!(TransactionLogStatus::NotNeeded.to_string(), "not needed");
+        assert_eq!(
+            TransactionLogStatus::Applied(vec!["NTUSER.DAT.LOG1".to_owned(), "NTUSER.DAT.LOG2".to_owned()]).to_string(),
+            "applied (NTUSER.DAT.LOG1, NTUSER.DAT.LOG2)"
+        );
+    }
+
+    #[test]
+    fn test_recovered_value() {
+        let entry = RegistryEntryInfo::new("NTUSER.DAT", "", String::new())
+            .with_value("OneDrive", Some("RegSZ".to_owned()))
+            .with_recovered_cell(0x1a20);
+        assert_eq!(
+            entry.to_string(),
+            "recovered from deleted cell at offset 0x1a20: value 'OneDrive' (RegSZ) in value data"
+        );
+        assert_eq!(entry.to_json()["cell_offset"], 0x1a20);
+    }
+
+    #[test]
+    fn test_decoded_value() {
+        let entry = RegistryEntryInfo::new("NTUSER.DAT", "Environment", "2022-06-17T22:21:13Z".to_owned())
+            .with_value("Payload", Some("RegBinary".to_owned()))
+            .with_decoding("UTF-16LE -> base64".to_owned());
+        assert_eq!(
+            entry.to_string(),
+            "key 'Environment' (last written 2022-06-17T22:21:13Z), value 'Payload' (RegBinary) in value data (decoded as UTF-16LE -> base64)"
+        );
+    }
+}
@@ -0,0 +1,211 @@
+use std::fmt::Display;
+
+/// encoded text must have at least this length, to avoid decoding every short word
+const MIN_ENCODED_LENGTH: usize = 8;
+
+/// a single step which has been applied to decode a registry value
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ValueDecoding {
+    Utf16Le,
+    Base64,
+    Hex,
+    Decimal,
+}
+
+impl Display for ValueDecoding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ValueDecoding::Utf16Le => write!(f, "UTF-16LE"),
+            ValueDecoding::Base64 => write!(f, "base64"),
+            ValueDecoding::Hex => write!(f, "hex"),
+            ValueDecoding::Decimal => write!(f, "decimal"),
+        }
+    }
+}
+
+/// the result of decoding a registry value, together with the steps which
+/// have been applied, e.g. `UTF-16LE -> base64`
+pub struct DecodedValue {
+    pub steps: Vec<ValueDecoding>,
+    pub data: Vec<u8>,
+}
+
+impl DecodedValue {
+    pub fn decoding(&self) -> String {
+        let steps: Vec<_> = self.steps.iter().map(|s| s.to_string()).collect();
+        steps.join(" -> ")
+    }
+
+    /// returns the decoded data as text, if this is possible
+    pub fn text(&self) -> Option<&str> {
+        std::str::from_utf8(&self.data).ok()
+    }
+}
+
+/// tries to decode binary registry data, which often contains UTF-16LE text
+/// or base64 or hex encoded blobs. Only decodings which look plausible are returned.
+pub fn decode_binary_value(data: &[u8]) -> Vec<DecodedValue> {
+    let mut results = Vec::new();
+    let mut texts = Vec::new();
+
+    if let Some(text) = utf16_text(data) {
+        texts.push((vec![ValueDecoding::Utf16Le], text.clone()));
+        results.push(DecodedValue {
+            steps: vec![ValueDecoding::Utf16Le],
+            data: text.into_bytes(),
+        });
+    }
+    if let Some(text) = ascii_text(data) {
+        texts.push((Vec::new(), text));
+    }
+
+    for (steps, text) in texts {
+        let decoded = [
+            (ValueDecoding::Hex, decode_hex(&text)),
+            (ValueDecoding::Base64, decode_base64(&text)),
+        ];
+        for (decoding, data) in decoded {
+            if let Some(data) = data {
+                let mut steps = steps.clone();
+                steps.push(decoding);
+
+                // encoded PowerShell commands are UTF-16LE text
+                let data = match utf16_text(&data) {
+                    Some(text) => {
+                        steps.push(ValueDecoding::Utf16Le);
+                        text.into_bytes()
+                    }
+                    None => data,
+                };
+                results.push(DecodedValue { steps, data });
+            }
+        }
+    }
+    results
+}
+
+/// returns the decimal representation of a numeric registry value
+pub fn decode_number(number: u64) -> DecodedValue {
+    DecodedValue {
+        steps: vec![ValueDecoding::Decimal],
+        data: number.to_string().into_bytes(),
+    }
+}
+
+/// decodes UTF-16LE text. To avoid interpreting arbitrary data as CJK text,
+/// at least half of the characters must be ASCII characters
+fn utf16_text(data: &[u8]) -> Option<String> {
+    if data.len() < 4 || data.len() % 2 != 0 {
+        return None;
+    }
+    let chars: Vec<u16> = data.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]])).collect();
+    let text = String::from_utf16(&chars).ok()?;
+    let text = text.trim_end_matches('\0');
+    if text.chars().count() < 2 || text.chars().any(|c| c.is_control() && !c.is_whitespace()) {
+        return None;
+    }
+    let ascii = text.chars().filter(char::is_ascii).count();
+    if ascii * 2 < text.chars().count() {
+        return None;
+    }
+    Some(text.to_owned())
+}
+
+fn ascii_text(data: &[u8]) -> Option<String> {
+    let end = data.iter().rposition(|b| *b != 0)? + 1;
+    let data = &data[..end];
+    if data.len() >= MIN_ENCODED_LENGTH && data.iter().all(|b| b.is_ascii_graphic() || b.is_ascii_whitespace()) {
+        Some(String::from_utf8_lossy(data).to_string())
+    } else {
+        None
+    }
+}
+
+fn without_whitespace(text: &str) -> String {
+    text.chars().filter(|c| !c.is_whitespace()).collect()
+}
+
+fn decode_hex(text: &str) -> Option<Vec<u8>> {
+    let text = without_whitespace(text);
+    if text.len() < MIN_ENCODED_LENGTH {
+        return None;
+    }
+    hex::decode(text).ok()
+}
+
+fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    let text = without_whitespace(text);
+    let text = text.trim_end_matches('=');
+    if text.len() < MIN_ENCODED_LENGTH || text.len() % 4 == 1 {
+        return None;
+    }
+
+    let mut data = Vec::with_capacity(text.len() * 3 / 4);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for c in text.bytes() {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            _ => return None,
+        };
+        buffer = (buffer << 6) | u32::from(value);
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            data.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    Some(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_base64, decode_binary_value, decode_number, ValueDecoding};
+
+    fn utf16(text: &str) -> Vec<u8> {
+        text.encode_utf16().flat_map(|c| c.to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(decode_base64("SW52b2tlLU1pbWlrYXR6").unwrap(), b"Invoke-Mimikatz");
+        assert_eq!(decode_base64("aHR0cHM6Ly9ldmlsLmV4YW1wbGU=").unwrap(), b"https://evil.example");
+        assert!(decode_base64("not base64!").is_none());
+    }
+
+    #[test]
+    fn test_utf16() {
+        let decoded = decode_binary_value(&utf16("C:\\Users\\Public\\evil.exe\0"));
+        assert_eq!(decoded.len(), 1);
+        assert_eq!(decoded[0].steps, vec![ValueDecoding::Utf16Le]);
+        assert_eq!(decoded[0].text(), Some("C:\\Users\\Public\\evil.exe"));
+
+        // ASCII text must not be decoded as UTF-16
+        assert!(decode_binary_value(b"\x01\x02no text\xff").is_empty());
+    }
+
+    #[test]
+    fn test_encoded_command() {
+        // PowerShell's -EncodedCommand expects base64 encoded UTF-16LE text
+        let encoded = "SQBuAHYAbwBrAGUALQBNAGkAbQBpAGsAYQB0AHoA";
+        let decoded = decode_binary_value(&utf16(encoded));
+        let command = decoded.iter().find(|d| d.text() == Some("Invoke-Mimikatz")).unwrap();
+        assert_eq!(command.decoding(), "UTF-16LE -> base64 -> UTF-16LE");
+
+        let decoded = decode_binary_value(b"496e766f6b652d4d696d696b61747a");
+        let command = decoded.iter().find(|d| d.text() == Some("Invoke-Mimikatz")).unwrap();
+        assert_eq!(command.decoding(), "hex");
+    }
+
+    #[test]
+    fn test_number() {
+        let decoded = decode_number(4444);
+        assert_eq!(decoded.text(), Some("4444"));
+        assert_eq!(decoded.decoding(), "decimal");
+    }
+}
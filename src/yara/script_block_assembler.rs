@@ -0,0 +1,162 @@
+use std::collections::{BTreeMap, HashMap};
+
+use serde_json::Value;
+
+use super::evtx_record_info::{text_of, EvtxRecordInfo};
+
+const SCRIPT_BLOCK_EVENT_ID: u64 = 4104;
+const SCRIPT_BLOCK_FIELD: &str = "Event.EventData.ScriptBlockText";
+
+/// a part of a PowerShell script block, which has been logged in an own record
+/// (EventID 4104 with a `MessageTotal` larger than 1)
+pub struct ScriptBlockFragment {
+    id: String,
+    number: u64,
+    total: u64,
+    text: String,
+    record: EvtxRecordInfo,
+}
+
+impl ScriptBlockFragment {
+    /// returns `None` if the record does not contain a part of a larger script block
+    pub fn from_record(record: &EvtxRecordInfo, data: &Value) -> Option<Self> {
+        if record.event_id != Some(SCRIPT_BLOCK_EVENT_ID) {
+            return None;
+        }
+        let event_data = &data["Event"]["EventData"];
+        let total: u64 = text_of(&event_data["MessageTotal"])?.parse().ok()?;
+        if total <= 1 {
+            return None;
+        }
+        Some(Self {
+            id: text_of(&event_data["ScriptBlockId"])?,
+            number: text_of(&event_data["MessageNumber"])?.parse().ok()?,
+            total,
+            text: text_of(&event_data["ScriptBlockText"]).unwrap_or_default(),
+            record: record.clone(),
+        })
+    }
+}
+
+/// a script block which has been reassembled from its fragments
+pub struct ScriptBlock {
+    pub text: String,
+
+    /// the first record of the script block, which refers to all other records
+    pub record: EvtxRecordInfo,
+
+    /// number of fragments which have not been found
+    pub missing_fragments: u64,
+}
+
+/// collects the fragments of PowerShell script blocks, until all fragments of
+/// a script block have been found
+#[derive(Default)]
+pub struct ScriptBlockAssembler {
+    pending: HashMap<String, PendingScriptBlock>,
+}
+
+struct PendingScriptBlock {
+    total: u64,
+    fragments: BTreeMap<u64, ScriptBlockFragment>,
+}
+
+impl ScriptBlockAssembler {
+    /// adds a fragment and returns the script block, if it is complete now
+    pub fn add(&mut self, fragment: ScriptBlockFragment) -> Option<ScriptBlock> {
+        let id = fragment.id.clone();
+        let pending = self.pending.entry(id.clone()).or_insert_with(|| PendingScriptBlock {
+            total: fragment.total,
+            fragments: BTreeMap::new(),
+        });
+        pending.fragments.insert(fragment.number, fragment);
+
+        if pending.fragments.len() as u64 >= pending.total {
+            self.pending.remove(&id).map(PendingScriptBlock::assemble)
+        } else {
+            None
+        }
+    }
+
+    /// returns all script blocks of which some fragments are missing
+    pub fn finish(self) -> Vec<ScriptBlock> {
+        self.pending.into_values().map(PendingScriptBlock::assemble).collect()
+    }
+}
+
+impl PendingScriptBlock {
+    fn assemble(self) -> ScriptBlock {
+        let missing_fragments = self.total.saturating_sub(self.fragments.len() as u64);
+        let record_ids = self.fragments.values().map(|f| f.record.record_id).collect();
+        let mut fragments = self.fragments.into_values();
+        let first = fragments.next().expect("script blocks have at least one fragment");
+
+        let mut text = first.text;
+        for fragment in fragments {
+            text.push_str(&fragment.text);
+        }
+
+        ScriptBlock {
+            text,
+            record: first
+                .record
+                .with_field(SCRIPT_BLOCK_FIELD.to_owned())
+                .with_script_block(first.id, record_ids),
+            missing_fragments,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{ScriptBlockAssembler, ScriptBlockFragment};
+    use crate::yara::evtx_record_info::EvtxRecordInfo;
+
+    fn fragment(record_id: u64, number: u64, text: &str) -> ScriptBlockFragment {
+        let data = json!({"Event": {
+            "System": {"EventID": 4104},
+            "EventData": {
+                "MessageNumber": number,
+                "MessageTotal": 3,
+                "ScriptBlockText": text,
+                "ScriptBlockId": "2c4e3f0a-0d5b-4d8e-9d7a-5a3b1f6e8c21",
+            }
+        }});
+        let info = EvtxRecordInfo::new(record_id, "2022-06-17T22:51:20Z".to_owned(), &data);
+        ScriptBlockFragment::from_record(&info, &data).unwrap()
+    }
+
+    #[test]
+    fn test_assemble() {
+        let mut assembler = ScriptBlockAssembler::default();
+        assert!(assembler.add(fragment(12, 2, "Invoke-")).is_none());
+        assert!(assembler.add(fragment(11, 1, "IEX; ")).is_none());
+        let script_block = assembler.add(fragment(13, 3, "Mimikatz")).unwrap();
+        assert_eq!(script_block.text, "IEX; Invoke-Mimikatz");
+        assert_eq!(script_block.record.record_id, 11);
+        assert_eq!(script_block.record.script_block_records, vec![11, 12, 13]);
+        assert_eq!(script_block.missing_fragments, 0);
+        assert!(assembler.finish().is_empty());
+    }
+
+    #[test]
+    fn test_incomplete() {
+        let mut assembler = ScriptBlockAssembler::default();
+        assert!(assembler.add(fragment(13, 3, "Mimikatz")).is_none());
+        let script_blocks = assembler.finish();
+        assert_eq!(script_blocks.len(), 1);
+        assert_eq!(script_blocks[0].missing_fragments, 2);
+    }
+
+    #[test]
+    fn test_single_record() {
+        let data = json!({"Event": {
+            "System": {"EventID": 4104},
+            "EventData": {"MessageNumber": 1, "MessageTotal": 1, "ScriptBlockId": "x"}
+        }});
+        let info = EvtxRecordInfo::new(1, "2022-06-17T22:51:20Z".to_owned(), &data);
+        assert!(ScriptBlockFragment::from_record(&info, &data).is_none());
+    }
+}
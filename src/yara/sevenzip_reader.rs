@@ -0,0 +1,1153 @@
+use std::convert::TryFrom;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+use aes::cipher::{BlockDecrypt, KeyInit};
+use anyhow::{anyhow, bail, Result};
+use bzip2::read::BzDecoder;
+use flate2::read::DeflateDecoder;
+use sha2::{Digest, Sha256};
+
+use super::archive_member::ArchiveMember;
+
+const SIGNATURE: &[u8] = &[b'7', b'z', 0xbc, 0xaf, 0x27, 0x1c];
+const SIGNATURE_HEADER_SIZE: u64 = 32;
+
+const ID_END: u8 = 0x00;
+const ID_HEADER: u8 = 0x01;
+const ID_ARCHIVE_PROPERTIES: u8 = 0x02;
+const ID_ADDITIONAL_STREAMS_INFO: u8 = 0x03;
+const ID_MAIN_STREAMS_INFO: u8 = 0x04;
+const ID_FILES_INFO: u8 = 0x05;
+const ID_PACK_INFO: u8 = 0x06;
+const ID_UNPACK_INFO: u8 = 0x07;
+const ID_SUBSTREAMS_INFO: u8 = 0x08;
+const ID_SIZE: u8 = 0x09;
+const ID_CRC: u8 = 0x0a;
+const ID_FOLDER: u8 = 0x0b;
+const ID_CODERS_UNPACK_SIZE: u8 = 0x0c;
+const ID_NUM_UNPACK_STREAM: u8 = 0x0d;
+const ID_EMPTY_STREAM: u8 = 0x0e;
+const ID_EMPTY_FILE: u8 = 0x0f;
+const ID_NAME: u8 = 0x11;
+const ID_MTIME: u8 = 0x14;
+const ID_WIN_ATTRIBUTES: u8 = 0x15;
+const ID_ENCODED_HEADER: u8 = 0x17;
+
+const METHOD_COPY: &[u8] = &[0x00];
+const METHOD_LZMA: &[u8] = &[0x03, 0x01, 0x01];
+const METHOD_LZMA2: &[u8] = &[0x21];
+const METHOD_DEFLATE: &[u8] = &[0x04, 0x01, 0x08];
+const METHOD_BZIP2: &[u8] = &[0x04, 0x02, 0x02];
+const METHOD_BCJ_X86: &[u8] = &[0x03, 0x03, 0x01, 0x03];
+const METHOD_AES: &[u8] = &[0x06, 0xf1, 0x07, 0x01];
+
+/// 7-Zip derives keys with 2^19 SHA-256 rounds. Archives which require much
+/// more rounds would block the scan for a long time with every password
+const MAX_AES_CYCLES_POWER: u8 = 24;
+
+/// 7-Zip compresses the header at most once. Archives with more levels of
+/// encoded headers are damaged or have been crafted to waste resources
+const MAX_ENCODED_HEADER_LEVELS: usize = 2;
+
+const FILE_ATTRIBUTE_DIRECTORY: u32 = 0x10;
+const FILE_ATTRIBUTE_UNIX_EXTENSION: u32 = 0x8000;
+
+/// reader for 7z archives.
+///
+/// The supported methods are Copy, LZMA, LZMA2, Deflate, BZip2, the x86 BCJ
+/// filter and AES encryption. Folders (which can contain more than one file
+/// in solid archives) are decompressed into memory, up to a configurable limit.
+pub struct SevenZipArchive<R: Read + Seek> {
+    reader: R,
+    streams: StreamsInfo,
+    files: Vec<SevenZipFile>,
+}
+
+struct SevenZipFile {
+    member: ArchiveMember,
+    has_stream: bool,
+    is_dir: bool,
+}
+
+#[derive(Default)]
+struct StreamsInfo {
+    pack_pos: u64,
+    pack_sizes: Vec<u64>,
+    folders: Vec<Folder>,
+}
+
+#[derive(Default)]
+struct Folder {
+    coders: Vec<Coder>,
+    bind_pairs: Vec<(u64, u64)>,
+    packed_streams: Vec<u64>,
+    unpack_sizes: Vec<u64>,
+    crc: Option<u32>,
+
+    /// index of the first pack stream used by this folder
+    first_pack_stream: usize,
+
+    /// sizes and checksums of the files which are stored in this folder
+    substreams: Vec<(u64, Option<u32>)>,
+}
+
+struct Coder {
+    method: Vec<u8>,
+    num_in: u64,
+    num_out: u64,
+    properties: Vec<u8>,
+}
+
+impl Folder {
+    fn unpack_size(&self) -> u64 {
+        // the result of the folder is the only output stream which is not bound
+        (0..self.unpack_sizes.len() as u64)
+            .find(|idx| !self.bind_pairs.iter().any(|(_, out)| out == idx))
+            .map(|idx| self.unpack_sizes[idx as usize])
+            .unwrap_or(0)
+    }
+
+    fn is_encrypted(&self) -> bool {
+        self.coders.iter().any(|c| c.method == METHOD_AES)
+    }
+}
+
+impl<R: Read + Seek> SevenZipArchive<R> {
+    /// reads the headers of a 7z archive. If the headers are encrypted, all
+    /// passwords are tried. Encoded headers are decompressed up to `limit` bytes
+    pub fn new(mut reader: R, passwords: &[String], limit: usize) -> Result<Self> {
+        let mut signature_header = [0u8; SIGNATURE_HEADER_SIZE as usize];
+        reader.read_exact(&mut signature_header)?;
+        if &signature_header[..6] != SIGNATURE {
+            bail!("this is not a 7z archive");
+        }
+        let mut header = ByteReader::new(&signature_header[12..]);
+        let next_header_offset = header.read_u64()?;
+        let next_header_size = header.read_u64()?;
+        let next_header_crc = header.read_u32()?;
+
+        let archive_size = reader.seek(SeekFrom::End(0))?;
+        let header_start = SIGNATURE_HEADER_SIZE
+            .checked_add(next_header_offset)
+            .filter(|start| start.checked_add(next_header_size).is_some_and(|end| end <= archive_size))
+            .ok_or_else(|| anyhow!("the 7z header lies outside of the archive"))?;
+
+        reader.seek(SeekFrom::Start(header_start))?;
+        let mut header = vec![0u8; usize::try_from(next_header_size)?];
+        reader.read_exact(&mut header)?;
+        if crc32fast::hash(&header) != next_header_crc {
+            bail!("invalid header checksum");
+        }
+
+        let mut archive = Self {
+            reader,
+            streams: StreamsInfo::default(),
+            files: Vec::new(),
+        };
+
+        for _ in 0..=MAX_ENCODED_HEADER_LEVELS {
+            let mut header_reader = ByteReader::new(&header);
+            match header_reader.read_u8()? {
+                ID_HEADER => {
+                    archive.read_header(&mut header_reader)?;
+                    return Ok(archive);
+                }
+                ID_ENCODED_HEADER => {
+                    let streams = read_streams_info(&mut header_reader)?;
+                    let folder = streams
+                        .folders
+                        .first()
+                        .ok_or_else(|| anyhow!("encoded header without data"))?;
+                    let header_size = folder.unpack_size();
+                    if header_size > limit as u64 {
+                        bail!("the encoded 7z header ({header_size} bytes) is larger than the decompression buffer");
+                    }
+                    check_pack_sizes(&streams, folder, limit)?;
+                    let limit = usize::try_from(header_size)?.min(limit);
+                    header = Self::decode_with_passwords(&mut archive.reader, &streams, 0, passwords, limit)?.data;
+                }
+                id => bail!("unexpected property id 0x{id:02x} in header"),
+            }
+        }
+        bail!("the 7z header is encoded more than {MAX_ENCODED_HEADER_LEVELS} times")
+    }
+
+    fn read_header(&mut self, header: &mut ByteReader) -> Result<()> {
+        let mut id = header.read_u8()?;
+        if id == ID_ARCHIVE_PROPERTIES {
+            loop {
+                if header.read_u8()? == ID_END {
+                    break;
+                }
+                let size = header.read_number()?;
+                header.skip(size)?;
+            }
+            id = header.read_u8()?;
+        }
+
+        if id == ID_ADDITIONAL_STREAMS_INFO {
+            read_streams_info(header)?;
+            id = header.read_u8()?;
+        }
+
+        if id == ID_MAIN_STREAMS_INFO {
+            self.streams = read_streams_info(header)?;
+            id = header.read_u8()?;
+        }
+
+        if id == ID_FILES_INFO {
+            self.files = read_files_info(header)?;
+            id = header.read_u8()?;
+        }
+
+        if id != ID_END {
+            bail!("unexpected property id 0x{id:02x} in header");
+        }
+
+        let num_streams: usize = self.streams.folders.iter().map(|f| f.substreams.len()).sum();
+        let num_files_with_stream = self.files.iter().filter(|f| f.has_stream).count();
+        if num_streams != num_files_with_stream {
+            bail!("the archive contains {num_files_with_stream} files, but {num_streams} streams");
+        }
+
+        let sizes = self.streams.folders.iter().flat_map(|f| f.substreams.iter().map(|(size, _)| *size));
+        for (file, size) in self.files.iter_mut().filter(|f| f.has_stream).zip(sizes) {
+            file.member.size = size;
+        }
+        Ok(())
+    }
+
+    /// calls `f` for every file in the archive, together with its content or with
+    /// the reason why the content could not be extracted. Directories are omitted.
+    pub fn for_each_member<F>(&mut self, passwords: &[String], limit: usize, mut f: F) -> Result<()>
+    where
+        F: FnMut(&ArchiveMember, std::result::Result<&[u8], String>) -> Result<()>,
+    {
+        let Self { reader, streams, files } = self;
+        let mut files_with_stream = files.iter().filter(|f| f.has_stream);
+
+        for (folder_idx, folder) in streams.folders.iter().enumerate() {
+            let members: Vec<&SevenZipFile> = files_with_stream
+                .by_ref()
+                .take(folder.substreams.len())
+                .collect();
+            if members.is_empty() {
+                continue;
+            }
+
+            let decoded = check_pack_sizes(streams, folder, limit)
+                .and_then(|_| Self::decode_with_passwords(reader, streams, folder_idx, passwords, limit));
+            match decoded {
+                Ok(DecodedFolder { data, unverified_password }) => {
+                    let mut offset = 0usize;
+                    for (file, (size, _)) in members.into_iter().zip(folder.substreams.iter()) {
+                        let end = offset.saturating_add(usize::try_from(*size).unwrap_or(usize::MAX));
+                        let member = ArchiveMember {
+                            unverified_password,
+                            ..file.member.clone()
+                        };
+                        match data.get(offset..end) {
+                            Some(content) => f(&member, Ok(content))?,
+                            None if data.len() >= limit => f(
+                                &member,
+                                Err("the 7z folder is larger than the decompression buffer".to_owned()),
+                            )?,
+                            None => f(
+                                &member,
+                                Err(format!(
+                                    "the 7z folder is damaged, only {} of {} bytes could be decompressed",
+                                    data.len(),
+                                    folder.unpack_size()
+                                )),
+                            )?,
+                        }
+                        offset = end;
+                    }
+                }
+                Err(why) => {
+                    for file in members.into_iter() {
+                        f(&file.member, Err(why.to_string()))?;
+                    }
+                }
+            }
+        }
+
+        // empty files have no stream, but might be relevant for some rules
+        for file in files.iter().filter(|f| !f.has_stream && !f.is_dir) {
+            f(&file.member, Ok(&[]))?;
+        }
+        Ok(())
+    }
+
+    fn decode_with_passwords(
+        reader: &mut R,
+        streams: &StreamsInfo,
+        folder_idx: usize,
+        passwords: &[String],
+        limit: usize,
+    ) -> Result<DecodedFolder> {
+        let folder = &streams.folders[folder_idx];
+        if !folder.is_encrypted() {
+            return Ok(DecodedFolder {
+                data: Self::decode_folder(reader, streams, folder, None, limit)?,
+                unverified_password: false,
+            });
+        }
+
+        // invalid key derivation parameters must not be mistaken for wrong passwords
+        for coder in folder.coders.iter().filter(|c| c.method == METHOD_AES) {
+            aes_cycles_power(&coder.properties)?;
+        }
+
+        if passwords.is_empty() {
+            bail!("the data is encrypted, but no password has been specified");
+        }
+
+        for password in passwords.iter() {
+            match Self::decode_folder(reader, streams, folder, Some(password), limit) {
+                Ok(data) => match verify_checksum(folder, &data) {
+                    Some(true) => return Ok(DecodedFolder { data, unverified_password: false }),
+                    Some(false) => log::trace!("invalid checksum after decryption, trying the next password"),
+                    None => {
+                        log::debug!("the 7z folder has no checksum, the password cannot be verified");
+                        return Ok(DecodedFolder { data, unverified_password: true });
+                    }
+                },
+                Err(why) => log::trace!("decryption failed: {why}, trying the next password"),
+            }
+        }
+        bail!("the data is encrypted, and none of the passwords is correct")
+    }
+
+    fn decode_folder(
+        reader: &mut R,
+        streams: &StreamsInfo,
+        folder: &Folder,
+        password: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<u8>> {
+        let out_index = (0..folder.unpack_sizes.len() as u64)
+            .find(|idx| !folder.bind_pairs.iter().any(|(_, out)| out == idx))
+            .ok_or_else(|| anyhow!("7z folder has no unbound output stream"))?;
+        Self::decode_stream(reader, streams, folder, out_index, password, limit, 0)
+    }
+
+    fn decode_stream(
+        reader: &mut R,
+        streams: &StreamsInfo,
+        folder: &Folder,
+        out_index: u64,
+        password: Option<&str>,
+        limit: usize,
+        depth: usize,
+    ) -> Result<Vec<u8>> {
+        // bind pairs of a damaged archive can form a cycle
+        if depth > folder.coders.len() {
+            bail!("invalid bind pairs in 7z folder");
+        }
+
+        // find the coder which creates the requested output stream
+        let mut first_in = 0;
+        let mut first_out = 0;
+        let mut coder = None;
+        for c in folder.coders.iter() {
+            if out_index < first_out + c.num_out {
+                coder = Some(c);
+                break;
+            }
+            first_in += c.num_in;
+            first_out += c.num_out;
+        }
+        let coder = coder.ok_or_else(|| anyhow!("invalid output stream index in 7z folder"))?;
+        if coder.num_in != 1 || coder.num_out != 1 {
+            bail!("unsupported 7z method {}", method_name(&coder.method));
+        }
+
+        let unpack_size = folder.unpack_sizes[out_index as usize];
+        let input = match folder.bind_pairs.iter().find(|(i, _)| *i == first_in) {
+            Some((_, out)) => {
+                // the input of an encryption filter has a larger size than its output
+                let input_limit = limit.saturating_add(16);
+                Self::decode_stream(reader, streams, folder, *out, password, input_limit, depth + 1)?
+            }
+            None => {
+                let pack_idx = folder
+                    .packed_streams
+                    .iter()
+                    .position(|i| *i == first_in)
+                    .ok_or_else(|| anyhow!("missing packed stream in 7z folder"))?
+                    + folder.first_pack_stream;
+                Self::read_pack_stream(reader, streams, pack_idx)?
+            }
+        };
+
+        let output_limit = limit.min(usize::try_from(unpack_size).unwrap_or(usize::MAX));
+        decode(coder, &input, unpack_size, password, output_limit)
+    }
+
+    fn read_pack_stream(reader: &mut R, streams: &StreamsInfo, idx: usize) -> Result<Vec<u8>> {
+        let size = *streams
+            .pack_sizes
+            .get(idx)
+            .ok_or_else(|| anyhow!("missing packed stream in 7z archive"))?;
+        let start = streams
+            .pack_sizes
+            .iter()
+            .take(idx)
+            .try_fold(SIGNATURE_HEADER_SIZE, |start, size| start.checked_add(*size))
+            .and_then(|start| start.checked_add(streams.pack_pos))
+            .ok_or_else(|| anyhow!("invalid offset of packed stream in 7z archive"))?;
+
+        reader.seek(SeekFrom::Start(start))?;
+
+        // the size has been checked against the decompression limit before
+        let mut data = vec![0u8; usize::try_from(size)?];
+        reader
+            .read_exact(&mut data)
+            .map_err(|why| anyhow!("unable to read packed 7z stream: {why}"))?;
+        Ok(data)
+    }
+}
+
+/// the packed streams are read into memory. Truncating them would make the
+/// decompression fail, and the folder would be reported as damaged
+fn check_pack_sizes(streams: &StreamsInfo, folder: &Folder, limit: usize) -> Result<()> {
+    let first = folder.first_pack_stream;
+    for size in streams.pack_sizes.iter().skip(first).take(folder.packed_streams.len()) {
+        if *size > limit as u64 {
+            bail!("the packed 7z stream ({size} bytes) is larger than the decompression buffer");
+        }
+    }
+    Ok(())
+}
+
+/// the content of a folder. If the folder is encrypted and has no checksums,
+/// the first password which could be used for decryption has been accepted
+struct DecodedFolder {
+    data: Vec<u8>,
+    unverified_password: bool,
+}
+
+/// checks the decoded data against the checksums of the folder or of its
+/// files. Returns `None` if there is no checksum which could be checked,
+/// because a wrong password can then only be detected by errors of the decompression
+fn verify_checksum(folder: &Folder, data: &[u8]) -> Option<bool> {
+    if let Some(crc) = folder.crc {
+        if data.len() as u64 == folder.unpack_size() {
+            return Some(crc32fast::hash(data) == crc);
+        }
+    }
+
+    let mut verified = None;
+    let mut offset = 0usize;
+    for (size, crc) in folder.substreams.iter() {
+        let end = offset.saturating_add(*size as usize);
+        if end > data.len() {
+            break;
+        }
+        if let Some(crc) = crc {
+            if crc32fast::hash(&data[offset..end]) != *crc {
+                return Some(false);
+            }
+            verified = Some(true);
+        }
+        offset = end;
+    }
+    verified
+}
+
+fn method_name(method: &[u8]) -> String {
+    match method {
+        METHOD_COPY => "Copy".to_owned(),
+        METHOD_LZMA => "LZMA".to_owned(),
+        METHOD_LZMA2 => "LZMA2".to_owned(),
+        METHOD_DEFLATE => "Deflate".to_owned(),
+        METHOD_BZIP2 => "BZip2".to_owned(),
+        METHOD_BCJ_X86 => "BCJ".to_owned(),
+        METHOD_AES => "7zAES".to_owned(),
+        [0x03, 0x03, 0x01, 0x1b] => "BCJ2".to_owned(),
+        _ => hex::encode(method),
+    }
+}
+
+fn decode(coder: &Coder, input: &[u8], unpack_size: u64, password: Option<&str>, limit: usize) -> Result<Vec<u8>> {
+    match &coder.method[..] {
+        METHOD_COPY => Ok(input[..input.len().min(limit)].to_vec()),
+
+        METHOD_LZMA => {
+            if coder.properties.len() < 5 {
+                bail!("invalid LZMA properties");
+            }
+            let mut writer = LimitedWriter::new(limit);
+            let options = lzma_rs::decompress::Options {
+                unpacked_size: lzma_rs::decompress::UnpackedSize::UseProvided(Some(unpack_size)),
+                ..Default::default()
+            };
+            let mut input = (&coder.properties[..5]).chain(input);
+            let result = lzma_rs::lzma_decompress_with_options(&mut input, &mut writer, &options);
+            writer.finish(result)
+        }
+
+        METHOD_LZMA2 => {
+            let mut writer = LimitedWriter::new(limit);
+            let mut input = input;
+            let result = lzma_rs::lzma2_decompress(&mut input, &mut writer);
+            writer.finish(result)
+        }
+
+        METHOD_DEFLATE => read_limited(DeflateDecoder::new(input), limit),
+        METHOD_BZIP2 => read_limited(BzDecoder::new(input), limit),
+
+        METHOD_BCJ_X86 => {
+            let mut data = input[..input.len().min(limit)].to_vec();
+            bcj_x86_decode(&mut data);
+            Ok(data)
+        }
+
+        METHOD_AES => {
+            let password = password.ok_or_else(|| anyhow!("the data is encrypted"))?;
+            let mut data = aes_decrypt(&coder.properties, password, input)?;
+            data.truncate(limit.min(usize::try_from(unpack_size).unwrap_or(usize::MAX)));
+            Ok(data)
+        }
+
+        method => bail!("unsupported 7z method {}", method_name(method)),
+    }
+}
+
+fn read_limited<D: Read>(decoder: D, limit: usize) -> Result<Vec<u8>> {
+    let mut data = Vec::new();
+    decoder.take(limit as u64).read_to_end(&mut data)?;
+    Ok(data)
+}
+
+/// writer which stores at most `limit` bytes. If more data is written,
+/// the decompression is aborted, but the data written so far is kept
+struct LimitedWriter {
+    data: Vec<u8>,
+    limit: usize,
+    truncated: bool,
+}
+
+impl LimitedWriter {
+    fn new(limit: usize) -> Self {
+        Self {
+            data: Vec::new(),
+            limit,
+            truncated: false,
+        }
+    }
+
+    fn finish(self, result: lzma_rs::error::Result<()>) -> Result<Vec<u8>> {
+        match result {
+            Err(_) if self.truncated => Ok(self.data),
+            Err(why) => Err(why.into()),
+            Ok(()) => Ok(self.data),
+        }
+    }
+}
+
+impl Write for LimitedWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let free = self.limit - self.data.len();
+        if buf.len() > free {
+            self.data.extend_from_slice(&buf[..free]);
+            self.truncated = true;
+            return Err(io::Error::new(io::ErrorKind::Other, "decompression limit reached"));
+        }
+        self.data.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// decrypts data which has been encrypted with the 7zAES method
+/// (AES-256 in CBC mode, with a key derived from the password using SHA-256)
+fn aes_decrypt(properties: &[u8], password: &str, input: &[u8]) -> Result<Vec<u8>> {
+    let num_cycles_power = aes_cycles_power(properties)?;
+    let first = properties[0];
+
+    let (salt, iv) = if first & 0xc0 == 0 {
+        (&[][..], &[][..])
+    } else {
+        let second = *properties.get(1).ok_or_else(|| anyhow!("invalid AES properties"))?;
+        let salt_size = ((first >> 7) & 1) as usize + (second >> 4) as usize;
+        let iv_size = ((first >> 6) & 1) as usize + (second & 0x0f) as usize;
+        if properties.len() < 2 + salt_size + iv_size {
+            bail!("invalid AES properties");
+        }
+        (
+            &properties[2..2 + salt_size],
+            &properties[2 + salt_size..2 + salt_size + iv_size],
+        )
+    };
+
+    let password: Vec<u8> = password.encode_utf16().flat_map(|c| c.to_le_bytes()).collect();
+    let mut key = [0u8; 32];
+    if num_cycles_power == 0x3f {
+        for (k, b) in key.iter_mut().zip(salt.iter().chain(password.iter())) {
+            *k = *b;
+        }
+    } else {
+        let mut sha = Sha256::new();
+        for round in 0..(1u64 << num_cycles_power) {
+            sha.update(salt);
+            sha.update(&password);
+            sha.update(round.to_le_bytes());
+        }
+        key.copy_from_slice(&sha.finalize());
+    }
+
+    let mut previous = [0u8; 16];
+    previous[..iv.len()].copy_from_slice(iv);
+
+    let cipher = aes::Aes256::new(&key.into());
+    let mut data = input[..input.len() - input.len() % 16].to_vec();
+    for block in data.chunks_exact_mut(16) {
+        let mut current = [0u8; 16];
+        current.copy_from_slice(block);
+        cipher.decrypt_block(block.into());
+        for (b, p) in block.iter_mut().zip(previous.iter()) {
+            *b ^= p;
+        }
+        previous = current;
+    }
+    Ok(data)
+}
+
+/// returns the number of SHA-256 rounds (as power of two) which are used to
+/// derive the key; `0x3f` means that the key is not hashed
+fn aes_cycles_power(properties: &[u8]) -> Result<u8> {
+    let first = *properties.first().ok_or_else(|| anyhow!("missing AES properties"))?;
+    let num_cycles_power = first & 0x3f;
+    if num_cycles_power > MAX_AES_CYCLES_POWER && num_cycles_power != 0x3f {
+        bail!("unsupported AES key derivation with 2^{num_cycles_power} rounds");
+    }
+    Ok(num_cycles_power)
+}
+
+/// reverts the x86 BCJ filter, which converts relative addresses of
+/// `CALL` and `JMP` instructions into absolute addresses
+fn bcj_x86_decode(buffer: &mut [u8]) {
+    const MASK_TO_ALLOWED_STATUS: [bool; 8] = [true, true, true, false, true, false, false, false];
+    const MASK_TO_BIT_NUMBER: [u32; 8] = [0, 1, 2, 2, 3, 3, 3, 3];
+    let test_ms_byte = |b: u8| b == 0 || b == 0xff;
+
+    if buffer.len() < 5 {
+        return;
+    }
+
+    let mut prev_mask = 0u32;
+    let mut prev_pos = 0u32.wrapping_sub(5);
+    let limit = buffer.len() - 5;
+    let mut pos = 0usize;
+
+    while pos <= limit {
+        let b = buffer[pos];
+        if b != 0xe8 && b != 0xe9 {
+            pos += 1;
+            continue;
+        }
+
+        let offset = (pos as u32).wrapping_sub(prev_pos);
+        prev_pos = pos as u32;
+        if offset > 5 {
+            prev_mask = 0;
+        } else {
+            for _ in 0..offset {
+                prev_mask &= 0x77;
+                prev_mask <<= 1;
+            }
+        }
+
+        let b = buffer[pos + 4];
+        if test_ms_byte(b)
+            && MASK_TO_ALLOWED_STATUS[((prev_mask >> 1) & 0x7) as usize]
+            && (prev_mask >> 1) < 0x10
+        {
+            let mut src = u32::from_le_bytes([buffer[pos + 1], buffer[pos + 2], buffer[pos + 3], b]);
+            let mut dest;
+            loop {
+                dest = src.wrapping_sub((pos as u32).wrapping_add(5));
+                if prev_mask == 0 {
+                    break;
+                }
+                let i = MASK_TO_BIT_NUMBER[(prev_mask >> 1) as usize];
+                let b = (dest >> (24 - i * 8)) as u8;
+                if !test_ms_byte(b) {
+                    break;
+                }
+                src = dest ^ ((1u32 << (32 - i * 8)) - 1);
+            }
+            buffer[pos + 4] = !(((dest >> 24) & 1).wrapping_sub(1)) as u8;
+            buffer[pos + 3] = (dest >> 16) as u8;
+            buffer[pos + 2] = (dest >> 8) as u8;
+            buffer[pos + 1] = dest as u8;
+            pos += 5;
+            prev_mask = 0;
+        } else {
+            pos += 1;
+            prev_mask |= 1;
+            if test_ms_byte(b) {
+                prev_mask |= 0x10;
+            }
+        }
+    }
+}
+
+fn read_streams_info(header: &mut ByteReader) -> Result<StreamsInfo> {
+    let mut streams = StreamsInfo::default();
+    let mut id = header.read_u8()?;
+
+    if id == ID_PACK_INFO {
+        streams.pack_pos = header.read_number()?;
+        let num_pack_streams = header.read_count()?;
+        id = header.read_u8()?;
+        if id == ID_SIZE {
+            streams.pack_sizes = (0..num_pack_streams)
+                .map(|_| header.read_number())
+                .collect::<Result<_>>()?;
+            id = header.read_u8()?;
+        }
+        if id == ID_CRC {
+            header.read_digests(num_pack_streams)?;
+            id = header.read_u8()?;
+        }
+        if id != ID_END {
+            bail!("unexpected property id 0x{id:02x} in pack info");
+        }
+        id = header.read_u8()?;
+    }
+
+    if id == ID_UNPACK_INFO {
+        if header.read_u8()? != ID_FOLDER {
+            bail!("missing folder information");
+        }
+        let num_folders = header.read_count()?;
+        if header.read_u8()? != 0 {
+            bail!("external folder information is not supported");
+        }
+
+        let mut first_pack_stream = 0;
+        for _ in 0..num_folders {
+            let mut folder = read_folder(header)?;
+            folder.first_pack_stream = first_pack_stream;
+            first_pack_stream += folder.packed_streams.len();
+            streams.folders.push(folder);
+        }
+
+        if header.read_u8()? != ID_CODERS_UNPACK_SIZE {
+            bail!("missing unpack sizes");
+        }
+        for folder in streams.folders.iter_mut() {
+            let num_out: u64 = folder.coders.iter().map(|c| c.num_out).sum();
+            folder.unpack_sizes = (0..num_out).map(|_| header.read_number()).collect::<Result<_>>()?;
+        }
+
+        id = header.read_u8()?;
+        if id == ID_CRC {
+            let digests = header.read_digests(num_folders)?;
+            for (folder, crc) in streams.folders.iter_mut().zip(digests) {
+                folder.crc = crc;
+            }
+            id = header.read_u8()?;
+        }
+        if id != ID_END {
+            bail!("unexpected property id 0x{id:02x} in unpack info");
+        }
+        id = header.read_u8()?;
+    }
+
+    // by default, every folder contains exactly one file
+    for folder in streams.folders.iter_mut() {
+        folder.substreams = vec![(folder.unpack_size(), folder.crc)];
+    }
+
+    if id == ID_SUBSTREAMS_INFO {
+        read_substreams_info(header, &mut streams.folders)?;
+        id = header.read_u8()?;
+    }
+
+    if id != ID_END {
+        bail!("unexpected property id 0x{id:02x} in streams info");
+    }
+    Ok(streams)
+}
+
+fn read_folder(header: &mut ByteReader) -> Result<Folder> {
+    let mut folder = Folder::default();
+    let num_coders = header.read_count()?;
+    for _ in 0..num_coders {
+        let flags = header.read_u8()?;
+        let method = header.read_bytes((flags & 0x0f) as usize)?.to_vec();
+        let (num_in, num_out) = if flags & 0x10 != 0 {
+            (header.read_count()? as u64, header.read_count()? as u64)
+        } else {
+            (1, 1)
+        };
+        let properties = if flags & 0x20 != 0 {
+            let size = header.read_number()? as usize;
+            header.read_bytes(size)?.to_vec()
+        } else {
+            Vec::new()
+        };
+        if flags & 0x80 != 0 {
+            bail!("alternative methods are not supported");
+        }
+        folder.coders.push(Coder {
+            method,
+            num_in,
+            num_out,
+            properties,
+        });
+    }
+
+    let num_in: u64 = folder.coders.iter().map(|c| c.num_in).sum();
+    let num_out: u64 = folder.coders.iter().map(|c| c.num_out).sum();
+    for _ in 1..num_out {
+        folder.bind_pairs.push((header.read_number()?, header.read_number()?));
+    }
+
+    let num_packed = num_in.saturating_sub(num_out.saturating_sub(1));
+    if num_packed == 1 {
+        let packed = (0..num_in)
+            .find(|idx| !folder.bind_pairs.iter().any(|(i, _)| i == idx))
+            .ok_or_else(|| anyhow!("7z folder has no packed stream"))?;
+        folder.packed_streams.push(packed);
+    } else {
+        for _ in 0..num_packed {
+            folder.packed_streams.push(header.read_number()?);
+        }
+    }
+    Ok(folder)
+}
+
+fn read_substreams_info(header: &mut ByteReader, folders: &mut [Folder]) -> Result<()> {
+    let mut num_streams = vec![1usize; folders.len()];
+    let mut id = header.read_u8()?;
+
+    if id == ID_NUM_UNPACK_STREAM {
+        for n in num_streams.iter_mut() {
+            *n = header.read_count()?;
+        }
+        id = header.read_u8()?;
+    }
+
+    let mut sizes: Vec<Vec<u64>> = Vec::new();
+    for (folder, n) in folders.iter().zip(num_streams.iter()) {
+        let mut folder_sizes = Vec::new();
+        if *n > 0 {
+            let mut sum = 0;
+            if id == ID_SIZE {
+                for _ in 1..*n {
+                    let size = header.read_number()?;
+                    sum = size.saturating_add(sum);
+                    folder_sizes.push(size);
+                }
+            }
+            folder_sizes.push(folder.unpack_size().saturating_sub(sum));
+        }
+        sizes.push(folder_sizes);
+    }
+    if id == ID_SIZE {
+        id = header.read_u8()?;
+    }
+
+    // checksums are stored for all streams, except for folders with only one
+    // stream whose checksum is already known
+    let unknown_crc = |folder: &Folder, n: usize| !(n == 1 && folder.crc.is_some());
+    let num_digests: usize = folders
+        .iter()
+        .zip(num_streams.iter())
+        .filter(|(f, n)| unknown_crc(f, **n))
+        .try_fold(0usize, |sum, (_, n)| sum.checked_add(*n))
+        .ok_or_else(|| anyhow!("invalid number of streams in 7z header"))?;
+
+    let mut digests = Vec::new();
+    if id == ID_CRC {
+        digests = header.read_digests(num_digests)?;
+        id = header.read_u8()?;
+    }
+    let mut digests = digests.into_iter();
+
+    for ((folder, n), sizes) in folders.iter_mut().zip(num_streams.iter()).zip(sizes) {
+        let crcs: Vec<Option<u32>> = if unknown_crc(folder, *n) {
+            (0..*n).map(|_| digests.next().flatten()).collect()
+        } else {
+            vec![folder.crc]
+        };
+        folder.substreams = sizes.into_iter().zip(crcs).collect();
+    }
+
+    if id != ID_END {
+        bail!("unexpected property id 0x{id:02x} in substreams info");
+    }
+    Ok(())
+}
+
+fn read_files_info(header: &mut ByteReader) -> Result<Vec<SevenZipFile>> {
+    let num_files = header.read_count()?;
+    let mut empty_stream = vec![false; num_files];
+    let mut empty_file = Vec::new();
+    let mut names = Vec::new();
+    let mut mtimes = vec![None; num_files];
+    let mut attributes = vec![None; num_files];
+
+    loop {
+        let id = header.read_u8()?;
+        if id == ID_END {
+            break;
+        }
+        let size = header.read_number()?;
+        let mut property = ByteReader::new(header.read_bytes(size as usize)?);
+
+        match id {
+            ID_EMPTY_STREAM => empty_stream = property.read_bits(num_files)?,
+            ID_EMPTY_FILE => {
+                let num_empty = empty_stream.iter().filter(|e| **e).count();
+                empty_file = property.read_bits(num_empty)?;
+            }
+            ID_NAME => {
+                if property.read_u8()? != 0 {
+                    bail!("external file names are not supported");
+                }
+                let chars: Vec<u16> = property
+                    .remaining()
+                    .chunks_exact(2)
+                    .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                    .collect();
+                names = chars
+                    .split(|c| *c == 0)
+                    .take(num_files)
+                    .map(String::from_utf16_lossy)
+                    .collect();
+            }
+            ID_MTIME => {
+                let defined = property.read_optional_bits(num_files)?;
+                if property.read_u8()? != 0 {
+                    bail!("external file times are not supported");
+                }
+                for (mtime, defined) in mtimes.iter_mut().zip(defined) {
+                    if defined {
+                        *mtime = Some(property.read_u64()?);
+                    }
+                }
+            }
+            ID_WIN_ATTRIBUTES => {
+                let defined = property.read_optional_bits(num_files)?;
+                if property.read_u8()? != 0 {
+                    bail!("external file attributes are not supported");
+                }
+                for (attribute, defined) in attributes.iter_mut().zip(defined) {
+                    if defined {
+                        *attribute = Some(property.read_u32()?);
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    let mut empty_file = empty_file.into_iter();
+    let mut files = Vec::with_capacity(num_files);
+    for idx in 0..num_files {
+        let has_stream = !empty_stream[idx];
+        let attribute = attributes[idx];
+        let is_dir = !has_stream
+            && !empty_file.next().unwrap_or(false)
+            && attribute.map(|a| a & FILE_ATTRIBUTE_DIRECTORY != 0).unwrap_or(true);
+
+        let mut member = ArchiveMember::new(names.get(idx).cloned().unwrap_or_default(), 0);
+        member.mode = attribute
+            .filter(|a| a & FILE_ATTRIBUTE_UNIX_EXTENSION != 0)
+            .map(|a| a >> 16);
+        if let Some(mtime) = mtimes[idx] {
+            member.set_mtime((mtime / 10_000_000) as i64 - 11_644_473_600);
+        }
+
+        files.push(SevenZipFile {
+            member,
+            has_stream,
+            is_dir,
+        });
+    }
+    Ok(files)
+}
+
+/// helper to parse the binary header structures of 7z archives
+struct ByteReader<'d> {
+    data: &'d [u8],
+}
+
+impl<'d> ByteReader<'d> {
+    fn new(data: &'d [u8]) -> Self {
+        Self { data }
+    }
+
+    fn remaining(&self) -> &'d [u8] {
+        self.data
+    }
+
+    fn read_bytes(&mut self, count: usize) -> Result<&'d [u8]> {
+        if count > self.data.len() {
+            bail!("unexpected end of 7z header");
+        }
+        let (bytes, rest) = self.data.split_at(count);
+        self.data = rest;
+        Ok(bytes)
+    }
+
+    /// reads the number of items of a list. Every item needs at least one byte
+    /// of the header, so larger numbers are invalid and must not be used to
+    /// allocate memory
+    fn read_count(&mut self) -> Result<usize> {
+        let count = self.read_number()?;
+        match usize::try_from(count) {
+            Ok(count) if count <= self.data.len() => Ok(count),
+            _ => bail!("invalid number of items ({count}) in 7z header"),
+        }
+    }
+
+    fn skip(&mut self, count: u64) -> Result<()> {
+        self.read_bytes(usize::try_from(count)?).map(|_| ())
+    }
+
+    fn read_u8(&mut self) -> Result<u8> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    fn read_u32(&mut self) -> Result<u32> {
+        let mut bytes = [0u8; 4];
+        bytes.copy_from_slice(self.read_bytes(4)?);
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    fn read_u64(&mut self) -> Result<u64> {
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(self.read_bytes(8)?);
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    /// reads a number with variable length. The number of leading one bits
+    /// of the first byte specifies the number of additional bytes
+    fn read_number(&mut self) -> Result<u64> {
+        let first = self.read_u8()?;
+        let mut mask = 0x80u8;
+        let mut value = 0u64;
+        for i in 0..8 {
+            if first & mask == 0 {
+                let high = (first & mask.wrapping_sub(1)) as u64;
+                return Ok(value | (high << (8 * i)));
+            }
+            value |= (self.read_u8()? as u64) << (8 * i);
+            mask >>= 1;
+        }
+        Ok(value)
+    }
+
+    fn read_bits(&mut self, count: usize) -> Result<Vec<bool>> {
+        let bytes = self.read_bytes((count + 7) / 8)?;
+        Ok((0..count)
+            .map(|idx| bytes[idx / 8] & (0x80 >> (idx % 8)) != 0)
+            .collect())
+    }
+
+    /// reads a bit vector which is preceded by a flag that all bits are set
+    fn read_optional_bits(&mut self, count: usize) -> Result<Vec<bool>> {
+        if self.read_u8()? != 0 {
+            Ok(vec![true; count])
+        } else {
+            self.read_bits(count)
+        }
+    }
+
+    fn read_digests(&mut self, count: usize) -> Result<Vec<Option<u32>>> {
+        self.read_optional_bits(count)?
+            .into_iter()
+            .map(|defined| if defined { self.read_u32().map(Some) } else { Ok(None) })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::{aes_decrypt, bcj_x86_decode, verify_checksum, ByteReader, Folder, SevenZipArchive};
+
+    #[test]
+    fn test_read_number() {
+        let mut reader = ByteReader::new(&[0x7f, 0x80, 0x80, 0xc0, 0x00, 0x01, 0xff, 1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(reader.read_number().unwrap(), 0x7f);
+        assert_eq!(reader.read_number().unwrap(), 0x80);
+        assert_eq!(reader.read_number().unwrap(), 0x0100);
+        assert_eq!(reader.read_number().unwrap(), 0x0807060504030201);
+    }
+
+    #[test]
+    fn test_read_count() {
+        // a count must not be larger than the remaining header
+        let mut reader = ByteReader::new(&[0x02, 0x00, 0x00]);
+        assert_eq!(reader.read_count().unwrap(), 2);
+        let mut reader = ByteReader::new(&[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x7f]);
+        assert!(reader.read_count().is_err());
+    }
+
+    #[test]
+    fn test_invalid_header_offset() {
+        let mut archive = vec![b'7', b'z', 0xbc, 0xaf, 0x27, 0x1c, 0, 4, 0, 0, 0, 0];
+        archive.extend_from_slice(&u64::MAX.to_le_bytes());
+        archive.extend_from_slice(&u64::MAX.to_le_bytes());
+        archive.extend_from_slice(&[0, 0, 0, 0]);
+        assert!(SevenZipArchive::new(Cursor::new(archive), &[], 1024).is_err());
+    }
+
+    #[test]
+    fn test_encoded_header_levels() {
+        // an encoded header which is stored (using the Copy method) in its own place
+        let header = [
+            0x17, 0x06, 0x00, 0x01, 0x09, 18, 0x00, 0x07, 0x0b, 0x01, 0x00, 0x01, 0x01, 0x00, 0x0c, 18, 0x00, 0x00,
+        ];
+        let mut archive = vec![b'7', b'z', 0xbc, 0xaf, 0x27, 0x1c, 0, 4, 0, 0, 0, 0];
+        archive.extend_from_slice(&0u64.to_le_bytes());
+        archive.extend_from_slice(&(header.len() as u64).to_le_bytes());
+        archive.extend_from_slice(&crc32fast::hash(&header).to_le_bytes());
+        archive.extend_from_slice(&header);
+
+        let error = SevenZipArchive::new(Cursor::new(archive), &[], 1024).err().unwrap();
+        assert!(error.to_string().contains("encoded more than"), "{}", error);
+    }
+
+    #[test]
+    fn test_verify_checksum() {
+        let folder = |crc| Folder {
+            unpack_sizes: vec![3],
+            substreams: vec![(3, crc)],
+            ..Default::default()
+        };
+        assert_eq!(verify_checksum(&folder(Some(crc32fast::hash(b"abc"))), b"abc"), Some(true));
+        assert_eq!(verify_checksum(&folder(Some(0)), b"abc"), Some(false));
+
+        // without checksums, a wrong password cannot be detected
+        assert_eq!(verify_checksum(&folder(None), b"abc"), None);
+    }
+
+    #[test]
+    fn test_aes_cycles_limit() {
+        // 2^62 rounds of SHA-256 would never finish
+        let error = aes_decrypt(&[0x3e], "infected", &[0u8; 16]).err().unwrap();
+        assert!(error.to_string().contains("2^62 rounds"));
+        assert!(aes_decrypt(&[0x3f], "infected", &[0u8; 16]).is_ok());
+    }
+
+    #[test]
+    fn test_bcj_x86() {
+        // CALL with absolute address 0x1005 at position 0 becomes relative address 0x1000
+        let mut code = vec![0xe8, 0x05, 0x10, 0x00, 0x00, 0x90, 0x90];
+        bcj_x86_decode(&mut code);
+        assert_eq!(code, vec![0xe8, 0x00, 0x10, 0x00, 0x00, 0x90, 0x90]);
+    }
+}
@@ -0,0 +1,338 @@
+use std::convert::TryFrom;
+use std::io::{self, ErrorKind, Read};
+
+use super::archive_member::ArchiveMember;
+
+const BLOCK_SIZE: u64 = 512;
+
+/// GNU long names and pax headers are read into memory, so that their size must be limited
+const MAX_EXTENDED_HEADER_SIZE: u64 = 1024 * 1024;
+
+/// sequential reader for tar archives (v7, ustar, GNU and pax).
+///
+/// Only the information needed to scan the archive members is evaluated;
+/// sparse files are read as they are stored in the archive. After
+/// [`TarReader::next_member`] returned a member, its content can be read
+/// from the `TarReader` itself.
+pub struct TarReader<R: Read> {
+    reader: R,
+
+    /// number of content bytes of the current member which have not been read yet
+    data_left: u64,
+
+    /// number of padding bytes after the content of the current member
+    padding: u64,
+}
+
+/// a member of a tar archive, together with its type
+pub struct TarMember {
+    pub member: ArchiveMember,
+    entry_type: u8,
+}
+
+impl TarMember {
+    pub fn is_file(&self) -> bool {
+        matches!(self.entry_type, b'0' | b'\0' | b'7') && !self.member.path.ends_with('/')
+    }
+}
+
+/// values of a pax extended header, which override the values of the next header
+#[derive(Default)]
+struct PaxOverrides {
+    path: Option<String>,
+    size: Option<u64>,
+    uid: Option<u64>,
+    gid: Option<u64>,
+    owner: Option<String>,
+    group: Option<String>,
+    mtime: Option<i64>,
+}
+
+impl<R: Read> TarReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            data_left: 0,
+            padding: 0,
+        }
+    }
+
+    /// skips the rest of the current member and reads the header of the next one.
+    /// Returns `None` if the end of the archive has been reached.
+    pub fn next_member(&mut self) -> io::Result<Option<TarMember>> {
+        let mut long_name = None;
+        let mut pax = PaxOverrides::default();
+
+        loop {
+            self.skip_current()?;
+
+            let mut header = [0u8; BLOCK_SIZE as usize];
+            if !self.read_block(&mut header)? || header.iter().all(|b| *b == 0) {
+                return Ok(None);
+            }
+            Self::verify_checksum(&header)?;
+
+            let entry_type = header[156];
+            let size = parse_number(&header[124..136])?;
+            self.data_left = size;
+            self.padding = (BLOCK_SIZE - size % BLOCK_SIZE) % BLOCK_SIZE;
+
+            match entry_type {
+                // GNU long name of the next member
+                b'L' => {
+                    long_name = Some(parse_string(&self.read_data()?));
+                }
+
+                // pax extended header of the next member
+                b'x' => {
+                    pax = parse_pax(&self.read_data()?);
+                }
+
+                // GNU long link name, pax global header and GNU volume header
+                b'K' | b'g' | b'V' => (),
+
+                _ => {
+                    let path = match (pax.path.take(), long_name.take()) {
+                        (Some(path), _) => path,
+                        (None, Some(path)) => path,
+                        (None, None) => Self::header_path(&header),
+                    };
+
+                    if let Some(size) = pax.size {
+                        self.data_left = size;
+                        self.padding = (BLOCK_SIZE - size % BLOCK_SIZE) % BLOCK_SIZE;
+                    }
+
+                    let mut member = ArchiveMember::new(path, self.data_left);
+                    member.mode = parse_number(&header[100..108]).ok().map(|m| m as u32);
+                    member.uid = pax.uid.or_else(|| parse_number(&header[108..116]).ok());
+                    member.gid = pax.gid.or_else(|| parse_number(&header[116..124]).ok());
+                    member.owner = pax.owner.take().or_else(|| non_empty(parse_string(&header[265..297])));
+                    member.group = pax.group.take().or_else(|| non_empty(parse_string(&header[297..329])));
+                    if let Some(mtime) = pax
+                        .mtime
+                        .or_else(|| parse_number(&header[136..148]).ok().map(|t| t as i64))
+                    {
+                        member.set_mtime(mtime);
+                    }
+
+                    return Ok(Some(TarMember { member, entry_type }));
+                }
+            }
+        }
+    }
+
+    /// returns `false` if there is no more data
+    fn read_block(&mut self, block: &mut [u8]) -> io::Result<bool> {
+        let mut bytes = 0;
+        while bytes < block.len() {
+            match self.reader.read(&mut block[bytes..]) {
+                Ok(0) if bytes == 0 => return Ok(false),
+                Ok(0) => return Err(io::Error::new(ErrorKind::UnexpectedEof, "truncated tar header")),
+                Ok(n) => bytes += n,
+                Err(why) if why.kind() == ErrorKind::Interrupted => (),
+                Err(why) => return Err(why),
+            }
+        }
+        Ok(true)
+    }
+
+    fn read_data(&mut self) -> io::Result<Vec<u8>> {
+        if self.data_left > MAX_EXTENDED_HEADER_SIZE {
+            return Err(io::Error::new(ErrorKind::InvalidData, "extended tar header is too large"));
+        }
+        let mut data = Vec::new();
+        self.read_to_end(&mut data)?;
+        Ok(data)
+    }
+
+    fn skip_current(&mut self) -> io::Result<()> {
+        // the size of a damaged header might be close to u64::MAX
+        let count = self.data_left.saturating_add(self.padding);
+        let skipped = io::copy(&mut (&mut self.reader).take(count), &mut io::sink())?;
+        self.data_left = 0;
+        self.padding = 0;
+        if skipped < count {
+            return Err(io::Error::new(ErrorKind::UnexpectedEof, "truncated tar archive"));
+        }
+        Ok(())
+    }
+
+    fn verify_checksum(header: &[u8]) -> io::Result<()> {
+        let expected = parse_number(&header[148..156])?;
+        let actual: u64 = header
+            .iter()
+            .enumerate()
+            .map(|(idx, b)| if (148..156).contains(&idx) { b' ' as u64 } else { *b as u64 })
+            .sum();
+        if expected != actual {
+            return Err(io::Error::new(ErrorKind::InvalidData, "invalid tar header checksum"));
+        }
+        Ok(())
+    }
+
+    fn header_path(header: &[u8]) -> String {
+        let name = parse_string(&header[0..100]);
+        if &header[257..262] == b"ustar" {
+            let prefix = parse_string(&header[345..500]);
+            if !prefix.is_empty() {
+                return format!("{prefix}/{name}");
+            }
+        }
+        name
+    }
+}
+
+impl<R: Read> Read for TarReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let max = buf.len().min(usize::try_from(self.data_left).unwrap_or(usize::MAX));
+        if max == 0 {
+            return Ok(0);
+        }
+        let bytes = self.reader.read(&mut buf[..max])?;
+        if bytes == 0 {
+            return Err(io::Error::new(ErrorKind::UnexpectedEof, "truncated tar member"));
+        }
+        self.data_left -= bytes as u64;
+        Ok(bytes)
+    }
+}
+
+fn non_empty(s: String) -> Option<String> {
+    if s.is_empty() {
+        None
+    } else {
+        Some(s)
+    }
+}
+
+fn parse_string(field: &[u8]) -> String {
+    let end = field.iter().position(|b| *b == 0).unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..end]).into_owned()
+}
+
+/// parses a numeric header field, which is either an octal number or
+/// (GNU extension) a big endian binary number if the highest bit is set
+fn parse_number(field: &[u8]) -> io::Result<u64> {
+    if field.first().map(|b| b & 0x80 != 0).unwrap_or(false) {
+        let mut value = (field[0] & 0x7f) as u64;
+        for b in &field[1..] {
+            if value > u64::MAX >> 8 {
+                return Err(io::Error::new(ErrorKind::InvalidData, "number in tar header is too large"));
+            }
+            value = (value << 8) | *b as u64;
+        }
+        return Ok(value);
+    }
+
+    let digits = parse_string(field);
+    let digits = digits.trim_matches(|c: char| c == ' ' || c == '\0');
+    if digits.is_empty() {
+        return Ok(0);
+    }
+    u64::from_str_radix(digits, 8).map_err(|_| {
+        io::Error::new(
+            ErrorKind::InvalidData,
+            format!("invalid number in tar header: '{digits}'"),
+        )
+    })
+}
+
+/// parses the records of a pax extended header, which have the
+/// format `<length> <key>=<value>\n`
+fn parse_pax(data: &[u8]) -> PaxOverrides {
+    let mut pax = PaxOverrides::default();
+    let mut rest = data;
+    while !rest.is_empty() {
+        let Some(space) = rest.iter().position(|b| *b == b' ') else {
+            break;
+        };
+        let Some(length) = std::str::from_utf8(&rest[..space])
+            .ok()
+            .and_then(|l| l.parse::<usize>().ok())
+            .filter(|l| *l > space && *l <= rest.len())
+        else {
+            break;
+        };
+
+        let record = String::from_utf8_lossy(&rest[space + 1..length]);
+        let record = record.strip_suffix('\n').unwrap_or(&record);
+        if let Some((key, value)) = record.split_once('=') {
+            match key {
+                "path" => pax.path = Some(value.to_owned()),
+                "size" => pax.size = value.parse().ok(),
+                "uid" => pax.uid = value.parse().ok(),
+                "gid" => pax.gid = value.parse().ok(),
+                "uname" => pax.owner = Some(value.to_owned()),
+                "gname" => pax.group = Some(value.to_owned()),
+                "mtime" => {
+                    pax.mtime = value
+                        .split('.')
+                        .next()
+                        .and_then(|secs| secs.parse().ok())
+                }
+                _ => (),
+            }
+        }
+        rest = &rest[length..];
+    }
+    pax
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_number, parse_pax, TarReader};
+
+    #[test]
+    fn test_parse_number() {
+        assert_eq!(parse_number(b"0000644\0").unwrap(), 0o644);
+        assert_eq!(parse_number(b"00000000012 ").unwrap(), 10);
+        assert_eq!(parse_number(b"\0\0\0\0\0\0\0\0").unwrap(), 0);
+        assert_eq!(parse_number(&[0x80, 0, 0, 0, 0, 0, 0x01, 0x00]).unwrap(), 256);
+        assert!(parse_number(b"0000899\0").is_err());
+        assert!(parse_number(&[0xff; 12]).is_err());
+        assert_eq!(parse_number(&[0x80, 0, 0, 0, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]).unwrap(), u64::MAX);
+    }
+
+    /// creates a header block with a valid checksum
+    fn header(name: &[u8], entry_type: u8, size: &[u8]) -> [u8; 512] {
+        let mut header = [0u8; 512];
+        header[..name.len()].copy_from_slice(name);
+        header[124..124 + size.len()].copy_from_slice(size);
+        header[156] = entry_type;
+        header[148..156].copy_from_slice(b"        ");
+        let checksum: u32 = header.iter().map(|b| *b as u32).sum();
+        header[148..156].copy_from_slice(format!("{checksum:06o}\0 ").as_bytes());
+        header
+    }
+
+    #[test]
+    fn test_huge_member_size() {
+        // a base-256 size close to u64::MAX must not overflow when the member is skipped
+        let header = header(b"big.bin", b'0', &[0x80, 0, 0, 0, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]);
+
+        let mut tar = TarReader::new(&header[..]);
+        let member = tar.next_member().unwrap().unwrap();
+        assert_eq!(member.member.size, u64::MAX);
+        assert!(tar.next_member().is_err());
+    }
+
+    #[test]
+    fn test_huge_extended_header() {
+        // a long name of 4 GiB must not be read into memory
+        for entry_type in [b'L', b'x'] {
+            let header = header(b"././@LongLink", entry_type, b"40000000000\0");
+            let mut tar = TarReader::new(&header[..]);
+            let why = tar.next_member().err().unwrap();
+            assert_eq!(why.kind(), std::io::ErrorKind::InvalidData);
+        }
+    }
+
+    #[test]
+    fn test_parse_pax() {
+        let pax = parse_pax(b"30 mtime=1700000000.123456789\n25 path=var/log/auth.log\n");
+        assert_eq!(pax.mtime, Some(1700000000));
+        assert_eq!(pax.path.as_deref(), Some("var/log/auth.log"));
+        assert_eq!(pax.size, None);
+    }
+}
@@ -0,0 +1,71 @@
+use std::collections::HashSet;
+use std::fmt::Display;
+
+use maplit::hashset;
+use serde_json::json;
+
+use crate::csv_line::CsvLine;
+use crate::scanner_result::ScannerFinding;
+
+use super::archive_member::ArchiveMember;
+
+/// an archive member which could not be scanned, e.g. because it is encrypted
+/// and none of the passwords matched. Such entries are reported explicitly,
+/// because they might hide exactly the files which are searched for.
+pub struct UnscannedEntry {
+    found_in_file: String,
+    contained_file: String,
+    archive_member: Option<ArchiveMember>,
+    reason: String,
+}
+
+impl UnscannedEntry {
+    pub fn new(found_in_file: String, contained_file: String, reason: String) -> Self {
+        Self {
+            found_in_file,
+            contained_file,
+            archive_member: None,
+            reason,
+        }
+    }
+
+    pub fn with_archive_member(mut self, member: Option<ArchiveMember>) -> Self {
+        self.archive_member = member;
+        self
+    }
+}
+
+impl Display for UnscannedEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Yara: unable to scan {}", self.found_in_file())?;
+        if let Some(member) = &self.archive_member {
+            writeln!(f, "  archive member: {}", member)?;
+        }
+        writeln!(f, "  reason: {}", self.reason)
+    }
+}
+
+impl ScannerFinding for UnscannedEntry {
+    fn format_csv(&self) -> HashSet<CsvLine> {
+        let line = CsvLine::new("Yara", "", self.found_in_file(), format!("unable to scan: {}", self.reason));
+        let line = match &self.archive_member {
+            None => line,
+            Some(m) => line.with_context(json!({"archive_member": m.to_json()})),
+        };
+        hashset![line]
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        json!({
+            "01_scanner": "yara",
+            "02_suspicious_file": self.found_in_file(),
+            "03_unscanned_reason": self.reason,
+            "05_contained_file": self.contained_file,
+            "10_archive_member": self.archive_member.as_ref().map(|m| m.to_json()),
+        })
+    }
+
+    fn found_in_file(&self) -> &str {
+        &self.found_in_file[..]
+    }
+}
@@ -1,7 +1,109 @@
 use std::collections::HashMap;
+use std::fs::{File, Metadata};
+use std::io::Read;
+use std::path::Path;
+use std::str::FromStr;
 
+use anyhow::{anyhow, Result};
 use duplicate::duplicate_item;
+use md5::Md5;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
 
+use super::archive_member::ArchiveMember;
+
+/// names of the external variables which are set by dionysos itself, and
+/// which cannot be defined by the user
+const BUILTIN_NAMES: [&str; 14] = [
+    "filename", "filepath", "extension", "filetype", "md5", "sha1", "sha256",
+    "owner", "filesize", "total_size", "mtime", "ctime", "permissions", "inode",
+];
+
+/// value of an external yara variable
+#[derive(Clone, Debug, PartialEq)]
+pub (crate) enum ExternalValue {
+    Boolean(bool),
+    Integer(i64),
+    Float(f64),
+    String(String),
+}
+
+impl ExternalValue {
+    /// interprets a value like the `-d` option of the yara command line tool:
+    /// `true` and `false` are booleans, numbers are integers or floats, and
+    /// everything else is a string
+    fn parse(value: &str) -> Self {
+        if value == "true" || value == "false" {
+            Self::Boolean(value == "true")
+        } else if let Ok(i) = value.parse::<i64>() {
+            Self::Integer(i)
+        } else if let Ok(f) = value.parse::<f64>() {
+            Self::Float(f)
+        } else {
+            Self::String(value.to_owned())
+        }
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Self::Boolean(_) => "boolean",
+            Self::Integer(_) => "integer",
+            Self::Float(_) => "float",
+            Self::String(_) => "string",
+        }
+    }
+
+    fn define_in_compiler(&self, compiler: &mut yara::Compiler, name: &str) -> Result<(), yara::YaraError> {
+        match self {
+            Self::Boolean(b) => compiler.define_variable(name, *b),
+            Self::Integer(i) => compiler.define_variable(name, *i),
+            Self::Float(f) => compiler.define_variable(name, *f),
+            Self::String(s) => compiler.define_variable(name, &s[..]),
+        }
+    }
+
+    fn define_in_scanner(&self, scanner: &mut yara::Scanner<'_>, name: &str) -> Result<(), yara::YaraError> {
+        match self {
+            Self::Boolean(b) => scanner.define_variable(name, *b),
+            Self::Integer(i) => scanner.define_variable(name, *i),
+            Self::Float(f) => scanner.define_variable(name, *f),
+            Self::String(s) => scanner.define_variable(name, &s[..]),
+        }
+    }
+}
+
+/// external variable which has been defined by the user, e.g. using `-D env=prod`
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExternalVariable {
+    name: String,
+    value: ExternalValue,
+}
+
+impl FromStr for ExternalVariable {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, value) = s
+            .split_once('=')
+            .ok_or_else(|| anyhow!("missing '=' in external variable definition '{s}'"))?;
+        let name = name.trim();
+
+        let mut chars = name.chars();
+        let is_identifier = chars.next().map(|c| c.is_ascii_alphabetic() || c == '_').unwrap_or(false)
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !is_identifier {
+            return Err(anyhow!("'{name}' is not a valid name for an external variable"));
+        }
+        if BUILTIN_NAMES.contains(&name) {
+            return Err(anyhow!("the external variable '{name}' is set by dionysos and cannot be redefined"));
+        }
+
+        Ok(Self {
+            name: name.to_owned(),
+            value: ExternalValue::parse(value.trim()),
+        })
+    }
+}
 
 #[derive(Default)]
 pub (crate) struct YaraExternals {
@@ -10,19 +112,44 @@ pub (crate) struct YaraExternals {
     extension: Option<String>,
     filetype: Option<String>,
     md5: Option<String>,
-    owner: Option<String>
+    sha1: Option<String>,
+    sha256: Option<String>,
+    owner: Option<String>,
+    filesize: Option<i64>,
+
+    /// size of the complete data. It differs from `filesize` only if data which
+    /// is larger than the decompression buffer is scanned in windows, and is -1
+    /// if the size is not known before the data has been decompressed
+    total_size: Option<i64>,
+    mtime: Option<i64>,
+    ctime: Option<i64>,
+    permissions: Option<i64>,
+    inode: Option<i64>,
+    user_defined: Vec<ExternalVariable>,
 }
 
 impl YaraExternals {
-    pub fn to_hashmap(&self) -> HashMap<&str, &str> {
+    pub fn to_hashmap(&self) -> HashMap<&str, ExternalValue> {
         let mut res = HashMap::new();
 
-        if let Some(x) = &self.filename  { res.insert("filename",  x.as_str());}
-        if let Some(x) = &self.filepath  { res.insert("filepath",  x.as_str());}
-        if let Some(x) = &self.extension { res.insert("extension", x.as_str());}
-        if let Some(x) = &self.filetype  { res.insert("filetype",  x.as_str());}
-        if let Some(x) = &self.md5       { res.insert("md5",       x.as_str());}
-        if let Some(x) = &self.owner     { res.insert("owner",     x.as_str());}
+        if let Some(x) = &self.filename  { res.insert("filename",  ExternalValue::String(x.clone()));}
+        if let Some(x) = &self.filepath  { res.insert("filepath",  ExternalValue::String(x.clone()));}
+        if let Some(x) = &self.extension { res.insert("extension", ExternalValue::String(x.clone()));}
+        if let Some(x) = &self.filetype  { res.insert("filetype",  ExternalValue::String(x.clone()));}
+        if let Some(x) = &self.md5       { res.insert("md5",       ExternalValue::String(x.clone()));}
+        if let Some(x) = &self.sha1      { res.insert("sha1",      ExternalValue::String(x.clone()));}
+        if let Some(x) = &self.sha256    { res.insert("sha256",    ExternalValue::String(x.clone()));}
+        if let Some(x) = &self.owner     { res.insert("owner",     ExternalValue::String(x.clone()));}
+        if let Some(x) = self.filesize   { res.insert("filesize",    ExternalValue::Integer(x));}
+        if let Some(x) = self.total_size { res.insert("total_size",  ExternalValue::Integer(x));}
+        if let Some(x) = self.mtime      { res.insert("mtime",       ExternalValue::Integer(x));}
+        if let Some(x) = self.ctime      { res.insert("ctime",       ExternalValue::Integer(x));}
+        if let Some(x) = self.permissions{ res.insert("permissions", ExternalValue::Integer(x));}
+        if let Some(x) = self.inode      { res.insert("inode",       ExternalValue::Integer(x));}
+
+        for variable in self.user_defined.iter() {
+            res.insert(&variable.name[..], variable.value.clone());
+        }
 
         res
     }
@@ -34,6 +161,8 @@ impl YaraExternals {
         [with_extension] [extension];
         [with_filetype]  [filetype];
         [with_md5]       [md5];
+        [with_sha1]      [sha1];
+        [with_sha256]    [sha256];
         [with_owner]     [owner]
     )]
     pub fn method_name(mut self, variable_name: String) -> Self {
@@ -41,13 +170,213 @@ impl YaraExternals {
         self
     }
 
+    #[duplicate_item (
+        method_name        variable_name;
+        [with_filesize]    [filesize];
+        [with_total_size]  [total_size];
+        [with_mtime]       [mtime];
+        [with_ctime]       [ctime];
+        [with_permissions] [permissions];
+        [with_inode]       [inode]
+    )]
+    pub fn method_name(mut self, variable_name: i64) -> Self {
+        self.variable_name = Some(variable_name);
+        self
+    }
+
+    pub fn with_user_defined(mut self, variables: &[ExternalVariable]) -> Self {
+        self.user_defined = variables.to_vec();
+        self
+    }
+
+    /// sets `md5`, `sha1`, `sha256`, `filesize` and `total_size` to the values of `data`
+    pub fn with_content(self, data: &[u8]) -> Self {
+        self.with_md5(hex::encode(Md5::digest(data)))
+            .with_sha1(hex::encode(Sha1::digest(data)))
+            .with_sha256(hex::encode(Sha256::digest(data)))
+            .with_content_size(data.len())
+    }
+
+    /// sets `filesize` and `total_size`, but not the hashes, which are expensive
+    pub fn with_content_size(self, size: usize) -> Self {
+        self.with_filesize(size as i64).with_total_size(size as i64)
+    }
+
+    /// sets `md5`, `sha1` and `sha256` to the hashes of a file. If the file
+    /// cannot be read, the variables keep their default values
+    pub fn with_file_content(self, file: &Path) -> Self {
+        match Self::hash_file(file) {
+            Ok((md5, sha1, sha256)) => self.with_md5(md5).with_sha1(sha1).with_sha256(sha256),
+            Err(why) => {
+                log::warn!("unable to calculate the hashes of '{}': {why}", file.display());
+                self
+            }
+        }
+    }
+
+    fn hash_file(file: &Path) -> std::io::Result<(String, String, String)> {
+        let mut md5 = Md5::new();
+        let mut sha1 = Sha1::new();
+        let mut sha256 = Sha256::new();
+
+        let mut reader = File::open(file)?;
+        let mut buffer = vec![0u8; 1024 * 1024];
+        loop {
+            let bytes = reader.read(&mut buffer)?;
+            if bytes == 0 {
+                break;
+            }
+            md5.update(&buffer[..bytes]);
+            sha1.update(&buffer[..bytes]);
+            sha256.update(&buffer[..bytes]);
+        }
+
+        Ok((
+            hex::encode(md5.finalize()),
+            hex::encode(sha1.finalize()),
+            hex::encode(sha256.finalize()),
+        ))
+    }
+
+    /// sets `filesize`, `total_size`, `mtime`, `ctime`, `permissions` and `inode`
+    /// from the metadata of a file. Timestamps are unix timestamps
+    #[cfg(target_family = "unix")]
+    pub fn with_metadata(self, metadata: &Metadata) -> Self {
+        use std::os::unix::fs::MetadataExt;
+        self.with_filesize(metadata.size() as i64)
+            .with_total_size(metadata.size() as i64)
+            .with_mtime(metadata.mtime())
+            .with_ctime(metadata.ctime())
+            .with_permissions((metadata.mode() & 0o7777) as i64)
+            .with_inode(metadata.ino() as i64)
+    }
+
+    /// sets `filesize`, `total_size`, `mtime`, `ctime`, `permissions` and `inode`
+    /// from the metadata of a file. Timestamps are unix timestamps; `ctime` is the creation time
+    #[cfg(not(target_family = "unix"))]
+    pub fn with_metadata(self, metadata: &Metadata) -> Self {
+        use std::time::{SystemTime, UNIX_EPOCH};
+        let timestamp = |t: std::io::Result<SystemTime>| {
+            t.ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_secs() as i64)
+                .unwrap_or(0)
+        };
+        self.with_filesize(metadata.len() as i64)
+            .with_total_size(metadata.len() as i64)
+            .with_mtime(timestamp(metadata.modified()))
+            .with_ctime(timestamp(metadata.created()))
+            .with_permissions(if metadata.permissions().readonly() { 0o444 } else { 0o644 })
+            .with_inode(0)
+    }
+
+    /// sets the variables which describe a member of an archive. Values
+    /// which are not stored in the archive are set to their defaults
+    pub fn with_archive_member(self, member: &ArchiveMember) -> Self {
+        self.with_filename(member.path.clone())
+            .with_filesize(member.size as i64)
+            .with_total_size(member.size as i64)
+            .with_mtime(member.mtime.unwrap_or(0))
+            .with_ctime(0)
+            .with_permissions(member.mode.map(|m| (m & 0o7777) as i64).unwrap_or(0))
+            .with_inode(0)
+    }
+
+    /// sets `md5`, `sha1`, `sha256`, `filesize` and `total_size` to the values
+    /// which are used if the content is unknown
+    pub fn with_unknown_content(self) -> Self {
+        self.with_md5("-".to_owned())
+            .with_sha1("-".to_owned())
+            .with_sha256("-".to_owned())
+            .with_filesize(0)
+            .with_total_size(-1)
+    }
+
+    /// values which are used to compile the rules, and which are used
+    /// for every variable which cannot be determined
     pub fn dummy() -> Self {
         Self::default()
             .with_filename("-".to_owned())
             .with_filepath("-".to_owned())
             .with_extension("-".to_owned())
             .with_filetype("-".to_owned())
-            .with_md5("-".to_owned())
+            .with_unknown_content()
             .with_owner("dummy".to_owned())
+            .with_mtime(0)
+            .with_ctime(0)
+            .with_permissions(0)
+            .with_inode(0)
     }
-}
\ No newline at end of file
+
+    pub fn define_in_compiler(&self, compiler: &mut yara::Compiler) -> Result<()> {
+        for (name, value) in self.to_hashmap() {
+            value
+                .define_in_compiler(compiler, name)
+                .map_err(|why| anyhow!("unable to define external yara variable '{}': {:?}", name, why))?;
+        }
+        Ok(())
+    }
+
+    /// defines all variables in `scanner`. Variables which are not declared by
+    /// the rules are skipped, because rules which have been compiled by `yarac`
+    /// do not know the variables which are set by dionysos
+    pub fn define_in_scanner(&self, scanner: &mut yara::Scanner<'_>) -> Result<()> {
+        for (name, value) in self.to_hashmap() {
+            match value.define_in_scanner(scanner, name) {
+                Err(why) if !is_undeclared_variable(&why) => {
+                    return Err(anyhow!("unable to define external yara variable '{}': {:?}", name, why))
+                }
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+
+    /// returns the names of the variables which are not declared by `rules`
+    pub fn undeclared_in(&self, rules: &yara::Rules) -> Result<Vec<&str>> {
+        let mut scanner = rules.scanner()?;
+        let mut undeclared = Vec::new();
+        for (name, value) in self.to_hashmap() {
+            match value.define_in_scanner(&mut scanner, name) {
+                Ok(()) => (),
+                Err(why) if is_undeclared_variable(&why) => undeclared.push(name),
+                Err(why) => {
+                    return Err(anyhow!("unable to define external yara variable '{}': {:?}", name, why))
+                }
+            }
+        }
+        undeclared.sort_unstable();
+        Ok(undeclared)
+    }
+}
+
+/// libyara returns `ERROR_INVALID_ARGUMENT` if a variable is not declared by the rules
+fn is_undeclared_variable(why: &yara::YaraError) -> bool {
+    const ERROR_INVALID_ARGUMENT: i32 = 29;
+    why.kind == yara::YaraErrorKind::Unknown(ERROR_INVALID_ARGUMENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ExternalValue, ExternalVariable};
+
+    #[test]
+    fn test_parse_external_variable() {
+        let variable: ExternalVariable = "env=prod".parse().unwrap();
+        assert_eq!(variable.name, "env");
+        assert_eq!(variable.value, ExternalValue::String("prod".to_owned()));
+
+        let variable: ExternalVariable = "max_age = 30".parse().unwrap();
+        assert_eq!(variable.name, "max_age");
+        assert_eq!(variable.value, ExternalValue::Integer(30));
+
+        assert_eq!("a=30".parse::<ExternalVariable>().unwrap().value, ExternalValue::Integer(30));
+        assert_eq!("a=0.5".parse::<ExternalVariable>().unwrap().value, ExternalValue::Float(0.5));
+        assert_eq!("a=true".parse::<ExternalVariable>().unwrap().value, ExternalValue::Boolean(true));
+        assert_eq!("a=".parse::<ExternalVariable>().unwrap().value, ExternalValue::String(String::new()));
+
+        assert!("env".parse::<ExternalVariable>().is_err());
+        assert!("1st=x".parse::<ExternalVariable>().is_err());
+        assert!("md5=x".parse::<ExternalVariable>().is_err());
+    }
+}
@@ -2,21 +2,31 @@ use std::collections::HashSet;
 use std::fmt::Display;
 
 use serde_json::{json, Value};
+use yara::Match;
 
 use crate::csv_line::CsvLine;
+use crate::process_info::ProcessInfo;
 use crate::scanner_result::ScannerFinding;
 use crate::dionysos::{display_strings, skip_display_strings};
 
+use super::archive_member::ArchiveMember;
+use super::evtx_record_info::EvtxRecordInfo;
+use super::registry_entry_info::RegistryEntryInfo;
+use super::yara_metadata::{metadata_to_json, YaraMetadata};
 use super::yara_string::YaraString;
 
 pub struct YaraFinding {
     pub identifier: String,
-    //pub namespace: String,
-    //pub metadatas: Vec<Metadata<'r>>,
-    //pub tags: Vec<String>,
+    pub namespace: String,
+    pub metadatas: Vec<YaraMetadata>,
+    pub tags: Vec<String>,
     pub strings: Vec<YaraString>,
     pub value_data: Option<String>,
     pub contained_file: Option<String>,
+    pub archive_member: Option<ArchiveMember>,
+    pub evtx_record: Option<EvtxRecordInfo>,
+    pub registry_entry: Option<RegistryEntryInfo>,
+    pub process: Option<ProcessInfo>,
     found_in_file: String,
 }
 
@@ -24,11 +34,16 @@ impl YaraFinding {
     pub fn new(rule: yara::Rule, found_in_file: String) -> Self {
         Self {
             identifier: rule.identifier.to_owned(),
-            //namespace: rule.namespace.to_owned(),
-            //tags: rule.tags.iter().map(|s|String::from(*s)).collect(),
+            namespace: rule.namespace.to_owned(),
+            metadatas: rule.metadatas.into_iter().map(|m| m.into()).collect(),
+            tags: rule.tags.iter().map(|s|String::from(*s)).collect(),
             strings: rule.strings.into_iter().map(|s| s.into()).collect(),
             value_data: None,
             contained_file: None,
+            archive_member: None,
+            evtx_record: None,
+            registry_entry: None,
+            process: None,
             found_in_file
         }
     }
@@ -42,11 +57,124 @@ impl YaraFinding {
         self.contained_file = Some(file.to_owned());
         self
     }
+
+    pub fn with_archive_member(mut self, member: ArchiveMember) -> Self {
+        self.archive_member = Some(member);
+        self
+    }
+
+    pub fn with_evtx_record(mut self, record: EvtxRecordInfo) -> Self {
+        self.evtx_record = Some(record);
+        self
+    }
+
+    pub fn with_registry_entry(mut self, entry: RegistryEntryInfo) -> Self {
+        self.registry_entry = Some(entry);
+        self
+    }
+
+    /// moves all matches by `offset` bytes, after removing all matches which
+    /// end before `skip_below`. This is used to correct the offsets of matches
+    /// in windows of larger data.
+    pub fn move_matches(&mut self, offset: usize, skip_below: usize) {
+        for s in self.strings.iter_mut() {
+            s.matches.retain(|m| m.offset + m.length > skip_below);
+            for m in s.matches.iter_mut() {
+                m.offset += offset;
+            }
+        }
+    }
+
+    /// adds the matches of another finding of the same rule
+    pub fn merge_matches(&mut self, other: YaraFinding) {
+        for other_string in other.strings.into_iter() {
+            match self.strings.iter_mut().find(|s| s.identifier == other_string.identifier) {
+                Some(s) => s.matches.extend(other_string.matches),
+                None => self.strings.push(other_string),
+            }
+        }
+    }
+
+    /// stores information about the process in which the rule matched. Only the
+    /// memory regions which contain matches are kept.
+    #[cfg(target_os = "linux")]
+    pub fn with_process(mut self, mut process: ProcessInfo) -> Self {
+        let addresses: Vec<u64> = self
+            .strings
+            .iter()
+            .flat_map(|s| s.matches.iter())
+            .map(|m| (m.base + m.offset) as u64)
+            .collect();
+        process.retain_regions(&addresses);
+        self.process = Some(process);
+        self
+    }
+
+    /// for process memory, the address and the memory region of a match are more
+    /// useful than the offset inside of the scanned memory block
+    fn process_location(&self, m: &Match) -> Option<String> {
+        self.process.as_ref().map(|process| {
+            let address = (m.base + m.offset) as u64;
+            match process.region_of(address) {
+                Some(region) => format!("0x{address:x} in {region}"),
+                None => format!("0x{address:x}"),
+            }
+        })
+    }
+
+    fn csv_line(&self, file: &str, details: String) -> CsvLine {
+        let mut context = json!({
+            "namespace": self.namespace,
+            "tags": self.tags,
+            "metadata": metadata_to_json(&self.metadatas),
+        });
+        if let Some(m) = &self.archive_member {
+            context["archive_member"] = m.to_json();
+        }
+        if let Some(r) = &self.evtx_record {
+            context["evtx_record"] = r.to_json();
+        }
+        if let Some(e) = &self.registry_entry {
+            context["registry_entry"] = e.to_json();
+        }
+        if let Some(p) = &self.process {
+            context["process"] = json!({
+                "pid": p.pid,
+                "name": p.name,
+                "cmdline": p.cmdline,
+                "executable": p.exe,
+            });
+        }
+        CsvLine::new("Yara", &self.identifier, file, details).with_context(context)
+    }
 }
 
 impl Display for YaraFinding {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         writeln!(f, "Yara: {} {}", self.identifier, self.found_in_file())?;
+        if let Some(process) = &self.process {
+            writeln!(f, "  pid: {}", process.pid)?;
+            writeln!(f, "  process name: {}", process.name)?;
+            writeln!(f, "  command line: {}", process.cmdline)?;
+            writeln!(f, "  executable: {}", process.exe)?;
+        }
+        if let Some(member) = &self.archive_member {
+            writeln!(f, "  archive member: {}", member)?;
+        }
+        if let Some(record) = &self.evtx_record {
+            writeln!(f, "  evtx record: {}", record)?;
+        }
+        if let Some(entry) = &self.registry_entry {
+            writeln!(f, "  registry entry: {}", entry)?;
+            writeln!(f, "  transaction logs: {}", entry.transaction_logs)?;
+        }
+        writeln!(f, "  namespace: {}", self.namespace)?;
+        if !self.tags.is_empty() {
+            writeln!(f, "  tags: {}", self.tags.join(", "))?;
+        }
+        for m in self.metadatas.iter() {
+            writeln!(f, "  {}: {}", m.identifier, m.value)?;
+        }
 
         if display_strings() {
             for s in self.strings.iter() {
@@ -62,7 +190,10 @@ impl Display for YaraFinding {
                     }
                 
                     for m in s.matches.iter() {
-                        writeln!(f, "    0x{:08x}: {}", m.offset, escape_vec(&m.data))?;
+                        match self.process_location(m) {
+                            None => writeln!(f, "    0x{:08x}: {}", m.offset, escape_vec(&m.data))?,
+                            Some(location) => writeln!(f, "    {}: {}", location, escape_vec(&m.data))?,
+                        }
                     }
                 }
             }
@@ -78,28 +209,33 @@ impl ScannerFinding for YaraFinding {
 
         if self.strings.is_empty() || skip_display_strings() {
             lines.insert(
-                CsvLine::new("Yara", &self.identifier, file, String::new())
+                self.csv_line(file, String::new())
             );
         } else {
             for s in self.strings.iter() {
                 if s.matches.is_empty() {
                     match &self.value_data {
                         None => {lines.insert(
-                            CsvLine::new("Yara",&self.identifier,file,s.identifier.clone())
+                            self.csv_line(file, s.identifier.clone())
                         );}
                         Some(d) => {lines.insert(
-                            CsvLine::new("Yara",&self.identifier,file,format!("{} in {}", s.identifier, d))
+                            self.csv_line(file, format!("{} in {}", s.identifier, d))
                         );}
                     }
                 } else {
                     for m in s.matches.iter() {
+                        if let Some(location) = self.process_location(m) {
+                            lines.insert(self.csv_line(file,
+                                format!("{} at {}: {}", s.identifier, location, escape_vec(&m.data))));
+                            continue;
+                        }
                         match &self.value_data {
                             None => {lines.insert(
-                                CsvLine::new("Yara",&self.identifier,file,
+                                self.csv_line(file,
                                 format!("{} at offset {:x}: {}", s.identifier, m.offset, escape_vec(&m.data)))
                             );}
                             Some(d) => {lines.insert(
-                                CsvLine::new("Yara",&self.identifier,file,
+                                self.csv_line(file,
                                 format!("{} at offset {:x}: {} in ({})", s.identifier, m.offset, escape_vec(&m.data), d))
                             );}
                         }
@@ -118,13 +254,38 @@ impl ScannerFinding for YaraFinding {
             "04_strings": self.strings.iter().map(|s: &YaraString| {
                 json!({
                     "identifier": s.identifier,
-                    "matches": s.matches.iter().map(|m| json!({
-                        "offset": m.offset,
-                        "data": escape_vec(&m.data)
-                    })).collect::<Vec<Value>>()
+                    "matches": s.matches.iter().map(|m| {
+                        let mut json_match = json!({
+                            "offset": m.offset,
+                            "data": escape_vec(&m.data)
+                        });
+                        if self.process.is_some() {
+                            json_match["address"] = json!(m.base + m.offset);
+                        }
+                        json_match
+                    }).collect::<Vec<Value>>()
                 })
             }).collect::<Vec<Value>>(),
-            "05_contained_file": self.contained_file
+            "05_contained_file": self.contained_file,
+            "06_namespace": self.namespace,
+            "07_tags": self.tags,
+            "08_metadata": metadata_to_json(&self.metadatas),
+            "09_process": self.process.as_ref().map(|p| json!({
+                "pid": p.pid,
+                "name": p.name,
+                "cmdline": p.cmdline,
+                "executable": p.exe,
+                "regions": p.regions.iter().map(|r| json!({
+                    "start": r.start,
+                    "end": r.end,
+                    "permissions": r.permissions,
+                    "offset": r.offset,
+                    "pathname": r.pathname
+                })).collect::<Vec<Value>>()
+            })),
+            "10_archive_member": self.archive_member.as_ref().map(|m| m.to_json()),
+            "11_evtx_record": self.evtx_record.as_ref().map(|r| r.to_json()),
+            "12_registry_entry": self.registry_entry.as_ref().map(|e| e.to_json())
         })
     }
 
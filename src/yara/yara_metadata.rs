@@ -0,0 +1,69 @@
+use std::fmt::Display;
+
+use serde_json::{json, Map, Value};
+use yara::{Metadata, MetadataValue};
+
+pub struct YaraMetadata {
+    pub identifier: String,
+    pub value: YaraMetadataValue,
+}
+
+pub enum YaraMetadataValue {
+    Integer(i64),
+    String(String),
+    Boolean(bool),
+}
+
+impl From<Metadata<'_>> for YaraMetadata {
+    fn from(m: Metadata<'_>) -> Self {
+        Self {
+            identifier: m.identifier.to_owned(),
+            value: match m.value {
+                MetadataValue::Integer(i) => YaraMetadataValue::Integer(i),
+                MetadataValue::String(s) => YaraMetadataValue::String(s.to_owned()),
+                MetadataValue::Boolean(b) => YaraMetadataValue::Boolean(b),
+            },
+        }
+    }
+}
+
+impl Display for YaraMetadataValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Integer(i) => write!(f, "{i}"),
+            Self::String(s) => write!(f, "{s}"),
+            Self::Boolean(b) => write!(f, "{b}"),
+        }
+    }
+}
+
+impl From<&YaraMetadataValue> for Value {
+    fn from(value: &YaraMetadataValue) -> Self {
+        match value {
+            YaraMetadataValue::Integer(i) => json!(i),
+            YaraMetadataValue::String(s) => json!(s),
+            YaraMetadataValue::Boolean(b) => json!(b),
+        }
+    }
+}
+
+/// converts the metadata of a rule into a JSON object. Yara allows the same
+/// identifier to be used multiple times (e.g. for `reference`), so the values
+/// of such identifiers are collected in an array.
+pub fn metadata_to_json(metadatas: &[YaraMetadata]) -> Value {
+    let mut map = Map::new();
+    for m in metadatas.iter() {
+        let value = Value::from(&m.value);
+        match map.get_mut(&m.identifier) {
+            None => {
+                map.insert(m.identifier.clone(), value);
+            }
+            Some(Value::Array(values)) => values.push(value),
+            Some(existing) => {
+                let first = existing.take();
+                *existing = Value::Array(vec![first, value]);
+            }
+        }
+    }
+    Value::Object(map)
+}
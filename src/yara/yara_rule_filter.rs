@@ -0,0 +1,267 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+use anyhow::anyhow;
+use regex::Regex;
+use yara::{Metadata, MetadataValue};
+
+/// the properties of a compiled rule which can be used to select it
+pub struct RuleInfo<'r> {
+    pub identifier: &'r str,
+    pub namespace: &'r str,
+    pub tags: &'r [&'r str],
+    pub metadatas: &'r [Metadata<'r>],
+}
+
+/// pattern to match rule identifiers against. Patterns which are enclosed in
+/// slashes (`/.../`) are treated as regular expressions, all other patterns
+/// are globs, which may contain `*` and `?`.
+#[derive(Clone, Debug)]
+pub struct RulePattern {
+    pattern: String,
+    regex: Regex,
+}
+
+impl FromStr for RulePattern {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let regex = if s.len() >= 2 && s.starts_with('/') && s.ends_with('/') {
+            Regex::new(&s[1..s.len() - 1])?
+        } else {
+            let mut regex = String::from("^");
+            for c in s.chars() {
+                match c {
+                    '*' => regex.push_str(".*"),
+                    '?' => regex.push('.'),
+                    c => regex.push_str(&regex::escape(&c.to_string())),
+                }
+            }
+            regex.push('$');
+            Regex::new(&regex)?
+        };
+        Ok(Self {
+            pattern: s.to_owned(),
+            regex,
+        })
+    }
+}
+
+impl Display for RulePattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.pattern)
+    }
+}
+
+impl RulePattern {
+    fn matches(&self, rule: &RuleInfo) -> bool {
+        self.regex.is_match(rule.identifier)
+            || self
+                .regex
+                .is_match(&format!("{}:{}", rule.namespace, rule.identifier))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Operator {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+/// condition on a metadata value of a rule, e.g. `score>=70` or `author=Florian Roth`.
+/// Comparisons with `<`, `<=`, `>` and `>=` require numeric values; `=` and `!=`
+/// compare numbers numerically and everything else case insensitive as text.
+#[derive(Clone, Debug)]
+pub struct MetaCondition {
+    identifier: String,
+    operator: Operator,
+    value: String,
+}
+
+impl FromStr for MetaCondition {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // the leftmost operator is used; if two operators start at the same
+        // position (e.g. `>` and `>=`), the longer one wins
+        const OPERATORS: [(&str, Operator); 6] = [
+            ("!=", Operator::Ne),
+            ("<=", Operator::Le),
+            (">=", Operator::Ge),
+            ("=", Operator::Eq),
+            ("<", Operator::Lt),
+            (">", Operator::Gt),
+        ];
+
+        let (pos, op_str, operator) = OPERATORS
+            .iter()
+            .filter_map(|(op_str, op)| s.find(op_str).map(|pos| (pos, *op_str, *op)))
+            .min_by_key(|(pos, op_str, _)| (*pos, usize::MAX - op_str.len()))
+            .ok_or_else(|| anyhow!("missing operator in metadata condition '{s}'"))?;
+
+        let identifier = s[..pos].trim();
+        let value = s[pos + op_str.len()..].trim();
+        if identifier.is_empty() {
+            return Err(anyhow!("missing metadata name in '{s}'"));
+        }
+
+        if !matches!(operator, Operator::Eq | Operator::Ne) && value.parse::<i64>().is_err() {
+            return Err(anyhow!("'{value}' is not a number, but '{op_str}' requires one"));
+        }
+
+        Ok(Self {
+            identifier: identifier.to_owned(),
+            operator,
+            value: value.to_owned(),
+        })
+    }
+}
+
+impl MetaCondition {
+    fn matches(&self, rule: &RuleInfo) -> bool {
+        rule.metadatas
+            .iter()
+            .filter(|m| m.identifier == self.identifier)
+            .any(|m| self.matches_value(&m.value))
+    }
+
+    fn matches_value(&self, value: &MetadataValue) -> bool {
+        let number = match value {
+            MetadataValue::Integer(i) => Some(*i),
+            MetadataValue::String(s) => s.trim().parse::<i64>().ok(),
+            MetadataValue::Boolean(_) => None,
+        };
+
+        match (number, self.value.parse::<i64>()) {
+            (Some(actual), Ok(expected)) => match self.operator {
+                Operator::Eq => actual == expected,
+                Operator::Ne => actual != expected,
+                Operator::Lt => actual < expected,
+                Operator::Le => actual <= expected,
+                Operator::Gt => actual > expected,
+                Operator::Ge => actual >= expected,
+            },
+            _ => {
+                let actual = match value {
+                    MetadataValue::Integer(i) => i.to_string(),
+                    MetadataValue::String(s) => s.to_string(),
+                    MetadataValue::Boolean(b) => b.to_string(),
+                };
+                match self.operator {
+                    Operator::Eq => actual.eq_ignore_ascii_case(&self.value),
+                    Operator::Ne => !actual.eq_ignore_ascii_case(&self.value),
+                    _ => false,
+                }
+            }
+        }
+    }
+}
+
+/// Selects the rules which are used for scanning.
+///
+/// Within one kind of filter (identifiers, tags or metadata), a rule must match
+/// at least one of the include filters. If there are include filters of more than
+/// one kind, a rule must satisfy all of them. A rule which matches any of the
+/// exclude filters is never used.
+#[derive(Default)]
+pub struct YaraRuleFilter {
+    include_rules: Vec<RulePattern>,
+    exclude_rules: Vec<RulePattern>,
+    include_tags: Vec<String>,
+    exclude_tags: Vec<String>,
+    include_meta: Vec<MetaCondition>,
+    exclude_meta: Vec<MetaCondition>,
+}
+
+impl YaraRuleFilter {
+    pub fn with_rules(mut self, include: Vec<RulePattern>, exclude: Vec<RulePattern>) -> Self {
+        self.include_rules = include;
+        self.exclude_rules = exclude;
+        self
+    }
+
+    pub fn with_tags(mut self, include: Vec<String>, exclude: Vec<String>) -> Self {
+        self.include_tags = include;
+        self.exclude_tags = exclude;
+        self
+    }
+
+    pub fn with_meta(mut self, include: Vec<MetaCondition>, exclude: Vec<MetaCondition>) -> Self {
+        self.include_meta = include;
+        self.exclude_meta = exclude;
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.include_rules.is_empty()
+            && self.exclude_rules.is_empty()
+            && self.include_tags.is_empty()
+            && self.exclude_tags.is_empty()
+            && self.include_meta.is_empty()
+            && self.exclude_meta.is_empty()
+    }
+
+    pub fn is_selected(&self, rule: &RuleInfo) -> bool {
+        let has_tag = |tag: &String| rule.tags.iter().any(|t| t.eq_ignore_ascii_case(tag));
+
+        let included = (self.include_rules.is_empty()
+            || self.include_rules.iter().any(|p| p.matches(rule)))
+            && (self.include_tags.is_empty() || self.include_tags.iter().any(has_tag))
+            && (self.include_meta.is_empty() || self.include_meta.iter().any(|c| c.matches(rule)));
+
+        let excluded = self.exclude_rules.iter().any(|p| p.matches(rule))
+            || self.exclude_tags.iter().any(has_tag)
+            || self.exclude_meta.iter().any(|c| c.matches(rule));
+
+        included && !excluded
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MetaCondition, Operator, RulePattern};
+    use yara::MetadataValue;
+
+    #[test]
+    fn test_glob_pattern() {
+        let pattern: RulePattern = "Webshell_*".parse().unwrap();
+        assert!(pattern.regex.is_match("Webshell_Generic"));
+        assert!(!pattern.regex.is_match("APT_Webshell_Generic"));
+
+        let pattern: RulePattern = "APT?.x".parse().unwrap();
+        assert!(pattern.regex.is_match("APT1.x"));
+        assert!(!pattern.regex.is_match("APT1_x"));
+    }
+
+    #[test]
+    fn test_regex_pattern() {
+        let pattern: RulePattern = "/^(APT|Webshell)_/".parse().unwrap();
+        assert!(pattern.regex.is_match("Webshell_Generic"));
+        assert!(!pattern.regex.is_match("HKTL_Generic"));
+    }
+
+    #[test]
+    fn test_meta_condition() {
+        let condition: MetaCondition = "score>=70".parse().unwrap();
+        assert_eq!(condition.identifier, "score");
+        assert_eq!(condition.operator, Operator::Ge);
+        assert!(condition.matches_value(&MetadataValue::Integer(70)));
+        assert!(condition.matches_value(&MetadataValue::String("80")));
+        assert!(!condition.matches_value(&MetadataValue::Integer(60)));
+
+        let condition: MetaCondition = "author = Florian Roth".parse().unwrap();
+        assert_eq!(condition.operator, Operator::Eq);
+        assert!(condition.matches_value(&MetadataValue::String("florian roth")));
+
+        let condition: MetaCondition = "author!=x".parse().unwrap();
+        assert_eq!(condition.operator, Operator::Ne);
+
+        assert!("score>=high".parse::<MetaCondition>().is_err());
+        assert!("score".parse::<MetaCondition>().is_err());
+        assert!("=70".parse::<MetaCondition>().is_err());
+    }
+}
@@ -0,0 +1,104 @@
+use regex::Regex;
+
+/// keywords and external variables which are used by the conditions of the
+/// rules. They are determined from the rule sources, so little is known
+/// about rules which have been loaded in compiled form.
+#[derive(Clone, Debug)]
+pub (crate) struct RuleReferences {
+    /// names of the rules whose conditions use `filesize`
+    rules_using_filesize: Vec<String>,
+
+    /// true if any condition might use the external variables `md5`, `sha1`
+    /// or `sha256`, which are expensive to calculate
+    uses_hashes: bool,
+}
+
+/// if nothing is known about the rules, they might use the hashes
+impl Default for RuleReferences {
+    fn default() -> Self {
+        Self {
+            rules_using_filesize: Vec::new(),
+            uses_hashes: true,
+        }
+    }
+}
+
+impl RuleReferences {
+    pub fn from_sources<'s>(sources: impl Iterator<Item = &'s str>) -> Self {
+        let comments_and_strings = Regex::new(r#"(?s)//[^\n]*|/\*.*?\*/|"(?:[^"\\\n]|\\.)*""#).unwrap();
+        let rule_header = Regex::new(r"\brule\s+([A-Za-z_][A-Za-z0-9_]*)").unwrap();
+        let condition = Regex::new(r"\bcondition\s*:").unwrap();
+        let filesize = Regex::new(r"\bfilesize\b").unwrap();
+
+        // functions of the hash module, like `hash.md5()`, are no external variables
+        let hashes = Regex::new(r"(?:^|[^.\w])(?:md5|sha1|sha256)\b").unwrap();
+
+        let mut references = Self {
+            rules_using_filesize: Vec::new(),
+            uses_hashes: false,
+        };
+        for source in sources {
+            let source = comments_and_strings.replace_all(source, " ");
+            let headers: Vec<_> = rule_header.captures_iter(&source).collect();
+            for (idx, header) in headers.iter().enumerate() {
+                let start = header.get(0).unwrap().end();
+                let end = headers.get(idx + 1).map_or(source.len(), |next| next.get(0).unwrap().start());
+                let body = &source[start..end];
+
+                let condition = match condition.find(body) {
+                    Some(m) => &body[m.end()..],
+                    None => continue,
+                };
+                if filesize.is_match(condition) {
+                    references.rules_using_filesize.push(header[1].to_owned());
+                }
+                if hashes.is_match(condition) {
+                    references.uses_hashes = true;
+                }
+            }
+        }
+        references
+    }
+
+    pub fn with_uses_hashes(mut self, uses_hashes: bool) -> Self {
+        self.uses_hashes = uses_hashes;
+        self
+    }
+
+    pub fn rules_using_filesize(&self) -> &[String] {
+        &self.rules_using_filesize[..]
+    }
+
+    pub fn uses_hashes(&self) -> bool {
+        self.uses_hashes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RuleReferences;
+
+    #[test]
+    fn test_rules_using_filesize() {
+        let source = r#"
+            rule small { condition: filesize < 100 } rule text {
+                strings: $a = "rule filesize"
+                condition: $a // filesize
+            }
+            global rule header : tag { strings: $mz = "MZ" condition: $mz at 0 and
+                filesize > 1KB }
+        "#;
+        let references = RuleReferences::from_sources(std::iter::once(source));
+        assert_eq!(references.rules_using_filesize(), &["small", "header"]);
+        assert!(!references.uses_hashes());
+    }
+
+    #[test]
+    fn test_uses_hashes() {
+        let uses_hashes = |source: &str| RuleReferences::from_sources(std::iter::once(source)).uses_hashes();
+        assert!(uses_hashes(r#"rule a { condition: md5 == "e6a65c3b01c87ea2f31134e3345a2c67" }"#));
+        assert!(uses_hashes("rule a { condition: true }\nrule b { condition:\nsha256 != \"-\" }"));
+        assert!(!uses_hashes(r#"import "hash" rule a { condition: hash.sha1(0, filesize) == "x" }"#));
+        assert!(!uses_hashes(r#"rule a { strings: $a = "md5" condition: $a }"#));
+    }
+}
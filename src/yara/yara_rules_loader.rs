@@ -0,0 +1,446 @@
+use anyhow::{anyhow, Result};
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use walkdir::WalkDir;
+
+use super::yara_externals::{ExternalVariable, YaraExternals};
+use super::yara_rule_references::RuleReferences;
+
+/// magic bytes of a file which has been created by `yara::Rules::save()`
+const COMPILED_RULES_MAGIC: &[u8] = b"YARA";
+
+/// header of a cache file. The header is followed by the hex encoded cache key
+/// in a separate line, and then by the compiled rules
+const CACHE_MAGIC: &[u8] = b"DIONYSOS_YARA_CACHE\n";
+
+/// Loads yara rules from a single file, a zip file or a directory, and compiles them.
+///
+/// Compilation of large rulesets takes some time, so the compiled rules can be
+/// stored in a cache file. The cache is keyed by a hash of all rule sources and
+/// of the names of the external variables, so that changed rules are detected
+/// and compiled again.
+pub (crate) struct YaraRulesLoader {
+    path: PathBuf,
+    cache_file: Option<PathBuf>,
+    strict: bool,
+    externals: Vec<ExternalVariable>,
+}
+
+/// a single yara file, which has been read from the filesystem or from a zip file.
+/// Every file gets its own namespace, which is derived from its path relative to
+/// the ruleset, so that rules with the same name in different files do not collide.
+struct YaraSource {
+    name: String,
+    namespace: String,
+    content: String,
+}
+
+impl YaraRulesLoader {
+    pub fn new<P>(path: P) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            cache_file: None,
+            strict: false,
+            externals: Vec::new(),
+        }
+    }
+
+    pub fn with_cache_file(mut self, cache_file: Option<PathBuf>) -> Self {
+        self.cache_file = cache_file;
+        self
+    }
+
+    /// if `strict` is set, loading the rules fails if any of the rule files
+    /// cannot be compiled. Otherwise, such files are skipped.
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// external variables which have been defined by the user. They must be
+    /// known when the rules are compiled
+    pub fn with_externals(mut self, externals: Vec<ExternalVariable>) -> Self {
+        self.externals = externals;
+        self
+    }
+
+    /// loads the rules, together with information about what their conditions
+    /// use. This information is not available for compiled rules
+    pub fn load(self) -> Result<(yara::Rules, RuleReferences)> {
+        if Self::points_to_compiled_rules(&self.path)? {
+            log::info!("loading compiled yara rules from '{}'", self.path.display());
+            let rules = Self::load_compiled_rules(&self.path)?;
+            let externals = self.externals();
+            let undeclared = externals.undeclared_in(&rules)?;
+            if !undeclared.is_empty() {
+                log::warn!(
+                    "the compiled yara rules do not declare the external variables {}, these variables will not be set",
+                    undeclared.join(", ")
+                );
+            }
+            let uses_hashes = ["md5", "sha1", "sha256"].iter().any(|name| !undeclared.contains(name));
+            return Ok((rules, RuleReferences::default().with_uses_hashes(uses_hashes)));
+        }
+
+        let sources = self.read_sources()?;
+        let references = RuleReferences::from_sources(sources.iter().map(|s| &s.content[..]));
+
+        let cache_key = self.cache_key(&sources);
+        if let Some(cache_file) = &self.cache_file {
+            match Self::load_from_cache(cache_file, &cache_key) {
+                Ok(Some(rules)) => {
+                    log::info!("using cached yara rules from '{}'", cache_file.display());
+                    return Ok((rules, references));
+                }
+                Ok(None) => log::info!(
+                    "yara rules cache '{}' is missing or stale, the rules will be compiled",
+                    cache_file.display()
+                ),
+                Err(why) => log::warn!(
+                    "unable to read yara rules cache '{}': {why}",
+                    cache_file.display()
+                ),
+            }
+        }
+
+        let begin = Instant::now();
+        let mut rules = self.compile(sources)?;
+        log::info!(
+            "compiled yara rules in {}s",
+            Instant::now().duration_since(begin).as_secs_f64()
+        );
+
+        if let Some(cache_file) = &self.cache_file {
+            if let Err(why) = Self::save_to_cache(&mut rules, cache_file, &cache_key) {
+                log::warn!(
+                    "unable to write yara rules cache '{}': {why}",
+                    cache_file.display()
+                );
+            }
+        }
+
+        Ok((rules, references))
+    }
+
+    fn read_sources(&self) -> Result<Vec<YaraSource>> {
+        let mut sources = Vec::new();
+        let metadata = std::fs::metadata(&self.path)?;
+        if metadata.is_file() {
+            if Self::points_to_zip_file(&self.path)? {
+                Self::add_rules_from_zip(&mut sources, &self.path)?;
+            } else if Self::points_to_yara_file(&self.path)? {
+                let namespace = match self.path.file_name() {
+                    Some(filename) => Self::namespace_of(Path::new(filename)),
+                    None => "default".to_owned(),
+                };
+                Self::add_rules_from_yara(&mut sources, &self.path, namespace)?;
+            } else {
+                log::warn!(
+                    "file '{}' is neither a yara nor a zip file; I'll ignore it",
+                    self.path.display()
+                );
+            }
+        } else {
+            Self::add_rules_from_directory(&mut sources, &self.path)?;
+        }
+        Ok(sources)
+    }
+
+    /// Usually, all sources are valid, so that every source is compiled only
+    /// once. A compiler cannot be used any more after an error, so if a source
+    /// fails, the remaining sources are checked separately, and the final
+    /// ruleset is created from the valid sources. Every file has its own
+    /// namespace, so that sources which compile on their own do not conflict
+    /// with each other.
+    fn compile(&self, sources: Vec<YaraSource>) -> Result<yara::Rules> {
+        let (failed_idx, why) = match Self::add_sources(self.new_compiler()?, sources.iter()) {
+            Ok(compiler) => return Ok(compiler.compile_rules()?),
+            Err(failure) => failure,
+        };
+        self.handle_compile_error(&sources[failed_idx], why)?;
+
+        let mut valid_sources: Vec<_> = sources[..failed_idx].iter().collect();
+        for source in sources[failed_idx + 1..].iter() {
+            match self.new_compiler()?.add_rules_str_with_namespace(&source.content, &source.namespace) {
+                Ok(_) => valid_sources.push(source),
+                Err(why) => self.handle_compile_error(source, why)?,
+            }
+        }
+        log::error!(
+            "skipped {} of {} yara files because of compile errors",
+            sources.len() - valid_sources.len(),
+            sources.len()
+        );
+
+        match Self::add_sources(self.new_compiler()?, valid_sources.into_iter()) {
+            Ok(compiler) => Ok(compiler.compile_rules()?),
+            Err((_, why)) => Err(why.into()),
+        }
+    }
+
+    /// adds all sources to `compiler`. If a source cannot be compiled, its
+    /// index is returned together with the error
+    fn add_sources<'s>(
+        compiler: yara::Compiler,
+        sources: impl Iterator<Item = &'s YaraSource>,
+    ) -> std::result::Result<yara::Compiler, (usize, yara::Error)> {
+        sources.enumerate().try_fold(compiler, |compiler, (idx, source)| {
+            compiler
+                .add_rules_str_with_namespace(&source.content, &source.namespace)
+                .map_err(|why| (idx, why))
+        })
+    }
+
+    fn handle_compile_error(&self, source: &YaraSource, why: yara::Error) -> Result<()> {
+        let errors = match why {
+            yara::Error::Compile(errors) => errors
+                .iter()
+                .map(|e| {
+                    let level = match e.level {
+                        yara::CompileErrorLevel::Error => "error",
+                        yara::CompileErrorLevel::Warning => "warning",
+                    };
+                    format!("{level} in line {}: {}", e.line, e.message)
+                })
+                .collect(),
+            why => vec![why.to_string()],
+        };
+
+        if self.strict {
+            Err(anyhow!(
+                "unable to compile '{}': {}",
+                source.name,
+                errors.join("; ")
+            ))
+        } else {
+            for error in errors {
+                log::error!("skipping '{}': {error}", source.name);
+            }
+            Ok(())
+        }
+    }
+
+    fn externals(&self) -> YaraExternals {
+        YaraExternals::dummy().with_user_defined(&self.externals)
+    }
+
+    fn new_compiler(&self) -> Result<yara::Compiler> {
+        let mut compiler = yara::Compiler::new()?;
+        self.externals().define_in_compiler(&mut compiler)?;
+        Ok(compiler)
+    }
+
+    /// The cache key depends on the rule sources and on the names and types of
+    /// the external variables, because both are required to compile the rules. The namespaces
+    /// are also part of the key, because they are stored in the rules. Strict mode
+    /// is also part of the key, because it decides which files are compiled.
+    fn cache_key(&self, sources: &[YaraSource]) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(env!("CARGO_PKG_VERSION").as_bytes());
+        hasher.update([u8::from(self.strict)]);
+
+        let externals = self.externals();
+        let mut externals: Vec<_> = externals.to_hashmap().into_iter().collect();
+        externals.sort_by(|a, b| a.0.cmp(b.0));
+        for (name, value) in externals {
+            hasher.update((name.len() as u64).to_le_bytes());
+            hasher.update(name.as_bytes());
+            hasher.update(value.type_name().as_bytes());
+        }
+
+        for source in sources.iter() {
+            hasher.update((source.namespace.len() as u64).to_le_bytes());
+            hasher.update(source.namespace.as_bytes());
+            hasher.update((source.content.len() as u64).to_le_bytes());
+            hasher.update(source.content.as_bytes());
+        }
+        hex::encode(hasher.finalize())
+    }
+
+    fn load_from_cache(cache_file: &Path, cache_key: &str) -> Result<Option<yara::Rules>> {
+        if !cache_file.exists() {
+            return Ok(None);
+        }
+
+        let mut reader = BufReader::new(File::open(cache_file)?);
+        let mut magic = vec![0; CACHE_MAGIC.len()];
+        reader.read_exact(&mut magic)?;
+        if magic != CACHE_MAGIC {
+            return Err(anyhow!("this is no cache file created by dionysos"));
+        }
+
+        let mut stored_key = String::new();
+        reader.read_line(&mut stored_key)?;
+        if stored_key.trim_end() != cache_key {
+            return Ok(None);
+        }
+
+        Ok(Some(yara::Rules::load_from_stream(reader)?))
+    }
+
+    fn save_to_cache(rules: &mut yara::Rules, cache_file: &Path, cache_key: &str) -> Result<()> {
+        let mut writer = BufWriter::new(File::create(cache_file)?);
+        writer.write_all(CACHE_MAGIC)?;
+        writeln!(writer, "{cache_key}")?;
+        rules.save_to_stream(&mut writer)?;
+        writer.flush()?;
+        log::info!("stored compiled yara rules in '{}'", cache_file.display());
+        Ok(())
+    }
+
+    /// loads a file which has either been created by `yarac` or which is a
+    /// dionysos cache file. The cache key is not checked in the latter case,
+    /// because there are no rule sources to compare with.
+    fn load_compiled_rules(path: &Path) -> Result<yara::Rules> {
+        let mut reader = BufReader::new(File::open(path)?);
+        if reader.fill_buf()?.starts_with(CACHE_MAGIC) {
+            let mut header = Vec::new();
+            reader.read_until(b'\n', &mut header)?;
+            reader.read_until(b'\n', &mut header)?;
+        }
+        Ok(yara::Rules::load_from_stream(reader)?)
+    }
+
+    fn points_to_compiled_rules(path: &Path) -> Result<bool> {
+        if !std::fs::metadata(path)?.is_file() {
+            return Ok(false);
+        }
+        let mut magic = Vec::with_capacity(CACHE_MAGIC.len());
+        File::open(path)?
+            .take(CACHE_MAGIC.len() as u64)
+            .read_to_end(&mut magic)?;
+        Ok(magic.starts_with(COMPILED_RULES_MAGIC) || magic.starts_with(CACHE_MAGIC))
+    }
+
+    fn add_rules_from_yara<P>(sources: &mut Vec<YaraSource>, path: P, namespace: String) -> Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        Self::add_rules_from_stream(sources, &path, namespace, &mut BufReader::new(File::open(&path)?))
+    }
+
+    fn add_rules_from_stream<P, R>(
+        sources: &mut Vec<YaraSource>,
+        path: P,
+        namespace: String,
+        stream: &mut R,
+    ) -> Result<()>
+    where
+        P: AsRef<Path>,
+        R: std::io::Read,
+    {
+        log::info!(
+            "parsing yara file: '{}' into namespace '{namespace}'",
+            path.as_ref().display()
+        );
+        let mut content = String::new();
+        stream.read_to_string(&mut content)?;
+        sources.push(YaraSource {
+            name: path.as_ref().display().to_string(),
+            namespace,
+            content,
+        });
+        Ok(())
+    }
+
+    /// creates a namespace name from a relative path, using '/' as separator on
+    /// all platforms
+    fn namespace_of(relative_path: &Path) -> String {
+        relative_path
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    fn add_rules_from_zip<P>(sources: &mut Vec<YaraSource>, path: P) -> Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let zip_file = BufReader::new(File::open(&path)?);
+        let mut zip = zip::ZipArchive::new(zip_file)?;
+        for i in 0..zip.len() {
+            let mut file = zip.by_index(i)?;
+            if file.is_file() {
+                match file.enclosed_name() {
+                    Some(file_path) => match file_path.to_str() {
+                        Some(name) => {
+                            if Self::is_yara_filename(name) {
+                                // create PathBuf to let rust release all immutable borrows of `file`
+                                let file_path = file_path.to_path_buf();
+                                let namespace = Self::namespace_of(&file_path);
+                                Self::add_rules_from_stream(sources, &file_path, namespace, &mut file)?;
+                            }
+                        }
+                        None => {
+                            log::warn!(
+                                "found no enclosed name for {}, ignoring that file",
+                                file.name()
+                            );
+                        }
+                    },
+                    None => {
+                        log::warn!(
+                            "found no enclosed name for {}, ignoring that file",
+                            file.name()
+                        );
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn add_rules_from_directory<P>(sources: &mut Vec<YaraSource>, path: P) -> Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let base_path = path.as_ref();
+        for entry in WalkDir::new(base_path).into_iter().filter_map(|e| e.ok()) {
+            let path = entry.path();
+            if Self::points_to_yara_file(path)? {
+                let namespace = Self::namespace_of(path.strip_prefix(base_path)?);
+                Self::add_rules_from_yara(sources, path, namespace)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn points_to_yara_file<P>(path: P) -> Result<bool>
+    where
+        P: AsRef<Path>,
+    {
+        let filename = match path.as_ref().file_name().and_then(|v| v.to_str()) {
+            Some(v) => v,
+            None => return Err(anyhow!("unable to read filename")),
+        };
+        Ok(Self::is_yara_filename(filename))
+    }
+
+    fn is_yara_filename(filename: &str) -> bool {
+        let lc_filename = filename.to_lowercase();
+        lc_filename.ends_with(".yar") || lc_filename.ends_with(".yara")
+    }
+
+    fn points_to_zip_file<P>(path: P) -> Result<bool>
+    where
+        P: AsRef<Path>,
+    {
+        let filename = match path.as_ref().file_name().and_then(|v| v.to_str()) {
+            Some(v) => v,
+            None => return Err(anyhow!("unable to read filename")),
+        };
+        Ok(Self::is_zip_filename(filename))
+    }
+
+    fn is_zip_filename(filename: &str) -> bool {
+        let lc_filename = filename.to_lowercase();
+        lc_filename.ends_with(".zip")
+    }
+}
@@ -1,54 +1,194 @@
 use crate::filescanner::*;
+#[cfg(target_os = "linux")]
+use crate::process_info::ProcessInfo;
+#[cfg(target_os = "linux")]
+use crate::processscanner::ProcessScanner;
 use crate::scanner_result;
 use crate::scanner_result::*;
+use crate::yara::file_type_detector::{FileType, FileTypeDetection, FileTypeDetector};
+use crate::yara::lzw_decoder::LzwDecoder;
 use crate::yara::yara_finding::YaraFinding;
 use anyhow::{anyhow, Result};
 use bzip2::read::BzDecoder;
-use filemagic::magic;
 use flate2::read::GzDecoder;
 use nt_hive2::CleanHive;
 use nt_hive2::Hive;
-use nt_hive2::HiveParseMode;
 use nt_hive2::KeyNode;
 use std::fmt::Display;
 use std::fs::File;
 use std::io::BufReader;
+use std::io::Cursor;
 use std::io::Read;
+use std::io::Seek;
 use std::path::Path;
-use std::sync::Mutex;
 use std::time::Instant;
 use walkdir::DirEntry;
-use walkdir::WalkDir;
 use xz::read::XzDecoder;
+use xz::stream::Stream as XzStream;
 
 #[cfg(feature = "scan_evtx")]
 use serde_json::Value;
 
+#[cfg(any(feature = "scan_evtx", feature = "scan_reg"))]
+use chrono::SecondsFormat;
+
+#[cfg(feature = "scan_reg")]
+use super::hive_file::{is_primary_hive_file, open_hive};
+
+#[cfg(feature = "scan_reg")]
+use super::hive_slack_carver::{carve_deleted_cells, RecoveredData, RecoveredEntry};
+
+#[cfg(feature = "scan_reg")]
+use super::registry_value_decoder::{decode_binary_value, decode_number, DecodedValue};
+
+#[cfg(feature = "scan_reg")]
+use super::registry_entry_info::{RegistryEntryInfo, RegistryField, TransactionLogStatus};
+
+#[cfg(feature = "scan_evtx")]
+use super::evtx_record_info::EvtxRecordInfo;
+
+#[cfg(feature = "scan_evtx")]
+use super::evtx_chunk_reader::{expected_records, EvtxChunkReader};
+
+#[cfg(feature = "scan_evtx")]
+use super::script_block_assembler::{ScriptBlock, ScriptBlockAssembler, ScriptBlockFragment};
+
+#[cfg(feature = "scan_evtx")]
+use evtx::{EvtxRecord, ParserSettings};
+
+#[cfg(feature = "scan_evtx")]
+use std::sync::Arc;
+
 #[cfg(target_family = "unix")]
 use file_owner::PathExt;
 
-use super::yara_externals::YaraExternals;
+use super::archive_member::ArchiveMember;
+use super::sevenzip_reader::SevenZipArchive;
+use super::tar_reader::TarReader;
+use super::unscanned_entry::UnscannedEntry;
+use super::yara_externals::{ExternalVariable, YaraExternals};
+use super::{EvtxFilter, EvtxMatchMode, RuleReferences};
+use super::yara_rule_filter::{RuleInfo, YaraRuleFilter};
 
 pub struct YaraScanner {
     rules: yara::Rules,
+    references: RuleReferences,
     scan_compressed: bool,
     timeout: u16,
     buffer_size: usize,
+    max_depth: usize,
+    window_overlap: usize,
+    passwords: Vec<String>,
+
+    /// external variables which have been defined by the user
+    externals: Vec<ExternalVariable>,
     scan_evtx: bool,
+    evtx_match_mode: EvtxMatchMode,
+    evtx_filter: EvtxFilter,
+
+    /// search for evtx chunks in files which are no evtx files
+    evtx_carve: bool,
     scan_reg: bool,
+    reg_carve: bool,
+
+    file_type_detector: FileTypeDetector,
+}
+
+/// location of scanned data inside of nested archives, e.g.
+/// `evidence.zip:logs.tar.gz:var/log/x`
+#[derive(Clone)]
+struct ContainerPath {
+    file: String,
+    members: Vec<String>,
+
+    /// metadata of the innermost archive member
+    archive_member: Option<ArchiveMember>,
+
+    /// size of the data at this location, if it is known before the data has
+    /// been read completely
+    content_size: Option<u64>,
+}
 
-    can_use_magic: Mutex<bool>,
+impl ContainerPath {
+    fn new(file: &Path) -> Self {
+        Self {
+            file: file.display().to_string(),
+            members: Vec::new(),
+            archive_member: None,
+            content_size: None,
+        }
+    }
+
+    fn member(&self, archive_member: ArchiveMember) -> Self {
+        let mut members = self.members.clone();
+        members.push(archive_member.path.clone());
+        Self {
+            file: self.file.clone(),
+            members,
+            content_size: Some(archive_member.size),
+            archive_member: Some(archive_member),
+        }
+    }
+
+    /// the location of the decompressed content of this location, whose size is
+    /// not known before it has been decompressed
+    fn decompressed(&self) -> Self {
+        Self {
+            content_size: None,
+            ..self.clone()
+        }
+    }
+
+    fn annotate(&self, finding: YaraFinding) -> YaraFinding {
+        if self.members.is_empty() {
+            finding
+        } else {
+            let finding = finding.with_contained_file(&self.members.join(":"));
+            match &self.archive_member {
+                None => finding,
+                Some(member) => finding.with_archive_member(member.clone()),
+            }
+        }
+    }
 }
 
-#[derive(Debug)]
-enum FileType {
-    GZip,
-    BZip2,
-    XZ,
-    Zip,
-    Evtx,
-    Reg,
-    Uncompressed,
+/// state of the scan of a single file, which is shared by all levels of
+/// nested archives and compressed streams
+struct UnpackContext<'s, 'r> {
+    scanner: &'s mut yara::Scanner<'r>,
+    detector: &'s FileTypeDetector,
+
+    /// archive members which could not be scanned
+    unscanned: Vec<UnscannedEntry>,
+}
+
+impl UnpackContext<'_, '_> {
+    fn add_unscanned(&mut self, location: &ContainerPath, reason: String) {
+        log::warn!("unable to scan '{location}': {reason}");
+        self.unscanned.push(
+            UnscannedEntry::new(location.to_string(), location.members.join(":"), reason)
+                .with_archive_member(location.archive_member.clone()),
+        );
+    }
+
+    /// data which cannot be unpacked is scanned as it is, but the failure must be reported
+    fn add_unpack_failure(&mut self, location: &ContainerPath, file_type: &FileType, why: anyhow::Error) {
+        let description = file_type.description().unwrap_or("the data");
+        self.add_unscanned(
+            location,
+            format!("unable to unpack {description}, it has been scanned without unpacking: {why}"),
+        );
+    }
+}
+
+impl Display for ContainerPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.file)?;
+        for member in self.members.iter() {
+            write!(f, ":{member}")?;
+        }
+        Ok(())
+    }
 }
 
 impl Display for YaraScanner {
@@ -62,37 +202,11 @@ impl FileScanner for YaraScanner {
         let mut results = Vec::new();
         let file = file.path();
 
-        let magic = if *(self.can_use_magic.lock().unwrap()) {
-            match magic!() {
-                Ok(m) => {
-                    match m.file(file) {
-                        Ok(magic) => {
-                            log::info!("treating '{}' as '{}'", file.display(), &magic);
-                            Some(magic)
-                        }
-                        Err(why) => {
-                            log::warn!(
-                                "unable to determine file type for '{}': {}",
-                                file.display(),
-                                why
-                            );
-                            None
-                        }
-                    }
-                },
-                Err(why) => {
-                    log::error!("unable to use magic: {why}");
-                    log::warn!("detecting compressed files, hive files and event log files will not work");
-                    *(self.can_use_magic.lock().unwrap()) = false;
-                    None
-                },
-            }
-        } else {
-            None
-        };
+        let (file_type, magic) = self.file_type_detector.detect_file(file);
 
         // prepare externals, which are required by some signature-base rules
         let mut externals = YaraExternals::default()
+            .with_user_defined(&self.externals)
             .with_filepath(file.display().to_string())
             .with_filename(file.file_name().unwrap().to_str().unwrap().to_string())
             .with_extension(match file.extension() {
@@ -101,6 +215,16 @@ impl FileScanner for YaraScanner {
             })
             .with_filetype(magic.clone().unwrap_or_else(|| "-".to_owned()));
 
+        // reading the whole file is only necessary if the hashes are used by any rule
+        if self.references.uses_hashes() {
+            externals = externals.with_file_content(file);
+        }
+
+        match std::fs::metadata(file) {
+            Ok(metadata) => externals = externals.with_metadata(&metadata),
+            Err(why) => log::warn!("unable to read metadata of '{}': {why}", file.display()),
+        }
+
         #[cfg(target_family = "unix")]
         {
             externals = externals.with_owner(match file.display().to_string().owner() {
@@ -126,67 +250,96 @@ impl FileScanner for YaraScanner {
         };
         scanner.set_timeout(self.timeout.into());
 
-        for entry in externals.to_hashmap() {
-            if let Err(why) = scanner.define_variable(entry.0, entry.1) {
-                return vec![Err(anyhow!(
-                    "unable to define external yara variable '{}': {:?}",
-                    entry.0,
-                    why
-                ))];
-            }
+        if let Err(why) = externals.define_in_scanner(&mut scanner) {
+            return vec![Err(why)];
         }
 
         // check if the file is a compressed file and must be decompressed before scanning
-        let file_type = self.get_filetype(magic, file);
+        let file_type = self.get_filetype(file_type, file);
         log::trace!("treating {} as {file_type:?}", file.to_string_lossy());
 
+        let mut unpack = UnpackContext {
+            scanner: &mut scanner,
+            detector: &self.file_type_detector,
+            unscanned: Vec::new(),
+        };
+
         let scan_result = match file_type {
-            FileType::GZip => self.scan_compressed(
-                &mut scanner,
-                GzDecoder::new(File::open(file).unwrap()),
-                &file.display().to_string(),
-            ),
-
-            FileType::BZip2 => self.scan_compressed(
-                &mut scanner,
-                BzDecoder::new(File::open(file).unwrap()),
-                &file.display().to_string(),
-            ),
-
-            FileType::XZ => self.scan_compressed(
-                &mut scanner,
-                XzDecoder::new(File::open(file).unwrap()),
-                &file.display().to_string(),
-            ),
-
-            FileType::Zip => {
-                self.scan_zip_archive(scanner, File::open(file).unwrap(), &file.to_string_lossy())
+            FileType::GZip
+            | FileType::BZip2
+            | FileType::XZ
+            | FileType::Zstd
+            | FileType::Lz4
+            | FileType::Lzma
+            | FileType::Compress => {
+                match File::open(file).and_then(|f| Self::decoder(&file_type, BufReader::new(f))) {
+                    Ok(decoder) => self.scan_compressed(&mut unpack, decoder, &ContainerPath::new(file), 1),
+                    Err(why) => Err(why.into()),
+                }
             }
 
+            FileType::Zip => match File::open(file) {
+                Ok(f) => self.scan_zip_archive(&mut unpack, f, &ContainerPath::new(file), 1),
+                Err(why) => Err(why.into()),
+            },
+
+            FileType::Tar => match File::open(file) {
+                Ok(f) => self.scan_tar_archive(&mut unpack, BufReader::new(f), &ContainerPath::new(file), 1),
+                Err(why) => Err(why.into()),
+            },
+
+            FileType::SevenZip => match File::open(file) {
+                Ok(f) => self.scan_7z_archive(&mut unpack, f, &ContainerPath::new(file), 1),
+                Err(why) => Err(why.into()),
+            },
+
             FileType::Evtx => {
                 #[cfg(feature = "scan_evtx")]
                 if self.scan_evtx {
-                    self.scan_evtx(&mut scanner, file)
+                    self.scan_evtx(&mut unpack, file)
                 } else {
-                    self.scan_file(&mut scanner, file)
+                    self.scan_file(unpack.scanner, file)
                 }
 
                 #[cfg(not(feature = "scan_evtx"))]
-                scanner.scan_file(&file).or_else(|e| Err(anyhow!(e)))
+                unpack.scanner.scan_file(&file).or_else(|e| Err(anyhow!(e)))
             }
 
             FileType::Reg => {
                 #[cfg(feature = "scan_reg")]
                 if self.scan_reg && matches!(file_type, FileType::Reg) {
-                    self.scan_hive_file(file, &mut scanner)
+                    self.scan_hive_file(file, unpack.scanner)
                 } else {
-                    self.scan_file(&mut scanner, file)
+                    self.scan_file(unpack.scanner, file)
                 }
 
                 #[cfg(not(feature = "scan_reg"))]
-                scanner.scan_file(&file).or_else(|e| Err(anyhow!(e)))
+                unpack.scanner.scan_file(&file).or_else(|e| Err(anyhow!(e)))
             }
-            FileType::Uncompressed => self.scan_file(&mut scanner, file),
+            FileType::Uncompressed => {
+                let result = self.scan_file(unpack.scanner, file);
+
+                #[cfg(feature = "scan_evtx")]
+                let result = match result {
+                    Ok(mut findings) if self.evtx_carve => self.carve_evtx(&mut unpack, file).map(|carved| {
+                        findings.extend(carved);
+                        findings
+                    }),
+                    result => result,
+                };
+                result
+            }
+        };
+
+        let scan_result = match scan_result {
+            Err(why) if file_type.is_compressed_stream() || file_type.is_archive() => {
+                // the externals might have been overwritten by the unpacked data
+                unpack.add_unpack_failure(&ContainerPath::new(file), &file_type, why);
+                externals
+                    .define_in_scanner(unpack.scanner)
+                    .and_then(|_| self.scan_file(unpack.scanner, file))
+            }
+            result => result,
         };
 
         match scan_result {
@@ -209,51 +362,134 @@ impl FileScanner for YaraScanner {
             }
         }
 
+        results.extend(
+            unpack
+                .unscanned
+                .into_iter()
+                .map(|u| Ok(Box::new(u) as Box<dyn ScannerFinding>)),
+        );
         results
     }
 }
 
-impl YaraScanner {
-    pub fn new<P>(path: P) -> Result<Self>
-    where
-        P: AsRef<Path>,
-    {
-        let mut rules_str = Vec::new();
-        let metadata = std::fs::metadata(&path)?;
-        if metadata.is_file() {
-            if Self::points_to_zip_file(&path)? {
-                Self::add_rules_from_zip(&mut rules_str, &path)?;
-            } else if Self::points_to_yara_file(&path)? {
-                Self::add_rules_from_yara(&mut rules_str, path)?;
-            } else {
-                log::warn!(
-                    "file '{}' is neither a yara nor a zip file; I'll ignore it",
-                    path.as_ref().display()
-                );
-            }
-        } else {
-            Self::add_rules_from_directory(&mut rules_str, path)?;
+#[cfg(target_os = "linux")]
+impl ProcessScanner for YaraScanner {
+    fn scan_process(&self, process: &ProcessInfo) -> Vec<anyhow::Result<Box<dyn ScannerFinding>>> {
+        // kernel threads have no memory which could be scanned
+        if !process.has_readable_regions() {
+            return Vec::new();
         }
 
-        let mut compiler = yara::Compiler::new()?;
-        for entry in YaraExternals::dummy().to_hashmap() {
-            compiler.define_variable(entry.0, entry.1)?;
+        let owner = match format!("/proc/{}", process.pid).owner() {
+            Ok(owner) => match owner.name() {
+                Ok(name) => name.unwrap_or_else(|| owner.id().to_string()),
+                Err(_) => owner.id().to_string(),
+            },
+            Err(why) => return vec![Err(anyhow!("unable to determine owner of {process}: {:?}", why))],
+        };
+
+        let externals = YaraExternals::default()
+            .with_user_defined(&self.externals)
+            .with_filepath(process.exe.clone())
+            .with_filename(process.name.clone())
+            .with_extension("-".to_owned())
+            .with_filetype("process memory".to_owned())
+            .with_owner(owner);
+
+        let mut scanner = match self.rules.scanner() {
+            Err(why) => return vec![Err(anyhow!("unable to create yara scanner: {:?}", why))],
+            Ok(scanner) => scanner,
+        };
+        scanner.set_timeout(self.timeout.into());
+
+        if let Err(why) = externals.define_in_scanner(&mut scanner) {
+            return vec![Err(why)];
         }
-        for rule in rules_str.into_iter() {
-            compiler = compiler.add_rules_str(&rule)?;
+
+        log::trace!("scanning the memory of {process}");
+        match scanner.scan_process(process.pid) {
+            Err(why) => vec![Err(anyhow!("yara scan error with {process}: {why}"))],
+            Ok(res) => res
+                .into_iter()
+                .map(|r| {
+                    let finding = YaraFinding::new(r, process.to_string()).with_process(process.clone());
+                    log::trace!(
+                        "new yara finding: {} in {process}",
+                        scanner_result::escape(&finding.identifier)
+                    );
+                    Ok(Box::new(finding) as Box<dyn ScannerFinding>)
+                })
+                .collect(),
         }
+    }
+}
 
-        Ok(Self {
-            rules: compiler.compile_rules()?,
+impl YaraScanner {
+    pub fn new(rules: yara::Rules) -> Self {
+        Self {
+            rules,
+            references: RuleReferences::default(),
             scan_compressed: false,
             timeout: 240,
             buffer_size: 128,
+            max_depth: 5,
+            window_overlap: 1024,
+            passwords: Vec::new(),
+            externals: Vec::new(),
 
             scan_evtx: false,
+            evtx_match_mode: EvtxMatchMode::default(),
+            evtx_filter: EvtxFilter::default(),
+            evtx_carve: false,
             scan_reg: false,
+            reg_carve: false,
 
-            can_use_magic: Mutex::new(true),
-        })
+            file_type_detector: FileTypeDetector::default(),
+        }
+    }
+
+    /// disables all rules which are not selected by the filter
+    pub fn with_rule_filter(self, filter: &YaraRuleFilter) -> Self {
+        if filter.is_empty() {
+            return self;
+        }
+
+        let mut rules = self.rules.get_rules();
+        let rules_count = rules.len();
+        let mut enabled_rules = 0;
+        for rule in rules.iter_mut() {
+            let info = RuleInfo {
+                identifier: rule.identifier,
+                namespace: rule.namespace,
+                tags: &rule.tags,
+                metadatas: &rule.metadatas,
+            };
+            if filter.is_selected(&info) {
+                enabled_rules += 1;
+            } else {
+                log::trace!("disabling yara rule {}:{}", rule.namespace, rule.identifier);
+                rule.disable();
+            }
+        }
+
+        if enabled_rules == 0 {
+            log::warn!("the yara rule filter does not match any of the {rules_count} rules");
+        } else {
+            log::info!("using {enabled_rules} of {rules_count} yara rules");
+        }
+        self
+    }
+
+    /// information about the conditions of the rules, which has been gathered
+    /// when the rules have been loaded
+    pub fn with_rule_references(mut self, references: RuleReferences) -> Self {
+        self.references = references;
+        self
+    }
+
+    pub fn with_file_type_detection(mut self, detection: FileTypeDetection) -> Self {
+        self.file_type_detector = FileTypeDetector::new(detection);
+        self
     }
 
     pub fn with_scan_compressed(mut self, scan_compressed: bool) -> Self {
@@ -266,146 +502,87 @@ impl YaraScanner {
         self
     }
 
-    pub fn with_timeout(mut self, timeout: u16) -> Self {
-        self.timeout = timeout;
+    /// sets the overlap (in KiB) of the windows which are used to scan data
+    /// that is larger than the decompression buffer
+    pub fn with_window_overlap(mut self, window_overlap: usize) -> Self {
+        self.window_overlap = window_overlap;
         self
     }
 
-    #[cfg(feature = "scan_reg")]
-    pub fn with_scan_reg(mut self, scan_reg: bool) -> Self {
-        self.scan_reg = scan_reg;
+    /// sets the maximum number of nested archives and compressed streams
+    /// which will be unpacked
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
         self
     }
 
-    #[cfg(feature = "scan_evtx")]
-    pub fn with_scan_evtx(mut self, scan_evtx: bool) -> Self {
-        self.scan_evtx = scan_evtx;
+    /// sets the passwords which are tried to open encrypted zip and 7z archives
+    pub fn with_passwords(mut self, passwords: Vec<String>) -> Self {
+        self.passwords = passwords;
         self
     }
 
-    fn add_rules_from_yara<P>(rules: &mut Vec<String>, path: P) -> Result<()>
-    where
-        P: AsRef<Path>,
-    {
-        Self::add_rules_from_stream(rules, &path, &mut BufReader::new(File::open(&path)?))
+    pub fn with_externals(mut self, externals: Vec<ExternalVariable>) -> Self {
+        self.externals = externals;
+        self
     }
 
-    fn add_rules_from_stream<P, R>(rules: &mut Vec<String>, path: P, stream: &mut R) -> Result<()>
-    where
-        P: AsRef<Path>,
-        R: std::io::Read,
-    {
-        log::info!("parsing yara file: '{}'", path.as_ref().display());
-        let mut yara_content = String::new();
-        stream.read_to_string(&mut yara_content)?;
+    pub fn with_timeout(mut self, timeout: u16) -> Self {
+        self.timeout = timeout;
+        self
+    }
 
-        // pretest the yara file. If there are any syntax errors, the compiler cannot be reused
-        let mut compiler = yara::Compiler::new()?;
-        for entry in YaraExternals::dummy().to_hashmap() {
-            compiler.define_variable(entry.0, entry.1)?;
-        }
-        match compiler.add_rules_str(&yara_content) {
-            Ok(_) => {
-                rules.push(yara_content);
-                Ok(())
-            }
-            Err(why) => {
-                log::error!("unable to compile {}: {why}", path.as_ref().display());
-                Ok(())
-            }
-        }
+    #[cfg(feature = "scan_reg")]
+    pub fn with_scan_reg(mut self, scan_reg: bool) -> Self {
+        self.scan_reg = scan_reg;
+        self
     }
 
-    fn add_rules_from_zip<P>(rules: &mut Vec<String>, path: P) -> Result<()>
-    where
-        P: AsRef<Path>,
-    {
-        let zip_file = BufReader::new(File::open(&path)?);
-        let mut zip = zip::ZipArchive::new(zip_file)?;
-        for i in 0..zip.len() {
-            let mut file = zip.by_index(i)?;
-            if file.is_file() {
-                match file.enclosed_name() {
-                    Some(file_path) => match file_path.to_str() {
-                        Some(name) => {
-                            if Self::is_yara_filename(name) {
-                                // create PathBuf to let rust release all immutable borrows of `file`
-                                let file_path = file_path.to_path_buf();
-                                Self::add_rules_from_stream(rules, &file_path, &mut file)?;
-                            }
-                        }
-                        None => {
-                            log::warn!(
-                                "found no enclosed name for {}, ignoring that file",
-                                file.name()
-                            );
-                        }
-                    },
-                    None => {
-                        log::warn!(
-                            "found no enclosed name for {}, ignoring that file",
-                            file.name()
-                        );
-                    }
-                }
-            }
-        }
-        Ok(())
+    #[cfg(feature = "scan_reg")]
+    pub fn with_reg_carve(mut self, reg_carve: bool) -> Self {
+        self.reg_carve = reg_carve;
+        self
     }
 
-    fn add_rules_from_directory<P>(rules: &mut Vec<String>, path: P) -> Result<()>
-    where
-        P: AsRef<Path>,
-    {
-        for entry in WalkDir::new(path).into_iter().filter_map(|e| e.ok()) {
-            let path = entry.path();
-            if Self::points_to_yara_file(path)? {
-                Self::add_rules_from_yara(rules, path)?;
-            }
-        }
-        Ok(())
+    #[cfg(feature = "scan_evtx")]
+    pub fn with_scan_evtx(mut self, scan_evtx: bool) -> Self {
+        self.scan_evtx = scan_evtx;
+        self
     }
 
-    fn points_to_yara_file<P>(path: P) -> Result<bool>
-    where
-        P: AsRef<Path>,
-    {
-        let filename = match path.as_ref().file_name().and_then(|v| v.to_str()) {
-            Some(v) => v,
-            None => return Err(anyhow!("unable to read filename")),
-        };
-        Ok(Self::is_yara_filename(filename))
+    #[cfg(feature = "scan_evtx")]
+    pub fn with_evtx_filter(mut self, evtx_filter: EvtxFilter) -> Self {
+        self.evtx_filter = evtx_filter;
+        self
     }
 
-    fn is_yara_filename(filename: &str) -> bool {
-        let lc_filename = filename.to_lowercase();
-        lc_filename.ends_with(".yar") || lc_filename.ends_with(".yara")
+    #[cfg(feature = "scan_evtx")]
+    pub fn with_evtx_carve(mut self, evtx_carve: bool) -> Self {
+        self.evtx_carve = evtx_carve;
+        self
     }
 
-    fn points_to_zip_file<P>(path: P) -> Result<bool>
-    where
-        P: AsRef<Path>,
-    {
-        let filename = match path.as_ref().file_name().and_then(|v| v.to_str()) {
-            Some(v) => v,
-            None => return Err(anyhow!("unable to read filename")),
-        };
-        Ok(Self::is_zip_filename(filename))
+    #[cfg(feature = "scan_evtx")]
+    pub fn with_evtx_match_mode(mut self, evtx_match_mode: EvtxMatchMode) -> Self {
+        self.evtx_match_mode = evtx_match_mode;
+        self
     }
 
-    fn is_zip_filename(filename: &str) -> bool {
-        let lc_filename = filename.to_lowercase();
-        lc_filename.ends_with(".zip")
+    fn window_size(&self) -> usize {
+        1024 * 1024 * self.buffer_size
     }
 
-    fn read_into_buffer<R: Read>(&self, reader: R) -> std::io::Result<(usize, Vec<u8>)> {
+    /// reads from `reader` until `buffer` has the size of a scan window, or
+    /// until the end of the data has been reached. Returns the number of bytes read
+    fn fill_window<R: Read>(&self, reader: &mut R, buffer: &mut Vec<u8>) -> std::io::Result<usize> {
         log::trace!("decompressing file");
         let begin = Instant::now();
-        let mut buffer = Vec::with_capacity(1024 * 1024 * self.buffer_size);
+        let missing = self.window_size().saturating_sub(buffer.len());
+        buffer.reserve_exact(missing);
 
-        let mut reader_with_limit = BufReader::new(reader.take(buffer.capacity() as u64));
+        let mut reader_with_limit = BufReader::new(reader.take(missing as u64));
 
-        let res = reader_with_limit.read_to_end(&mut buffer);
+        let res = reader_with_limit.read_to_end(buffer);
         match res {
             Ok(bytes) => {
                 log::trace!(
@@ -413,7 +590,7 @@ impl YaraScanner {
                     bytes,
                     Instant::now().duration_since(begin).as_secs_f64()
                 );
-                Ok((bytes, buffer))
+                Ok(bytes)
             }
             Err(why) => {
                 log::trace!("decompression failed: {}", why);
@@ -422,36 +599,179 @@ impl YaraScanner {
         }
     }
 
+    /// scans the records of an evtx file. Damaged chunks and records are skipped,
+    /// and the number of records which could not be parsed is reported.
+    #[cfg(feature = "scan_evtx")]
+    fn scan_evtx(&self, unpack: &mut UnpackContext, file: &Path) -> anyhow::Result<Vec<YaraFinding>> {
+        log::trace!("scanning for IOCs inside evtx file '{}'", file.display());
+        let chunks = EvtxChunkReader::from_evtx_file(BufReader::new(File::open(file)?));
+        self.scan_evtx_chunks(unpack, chunks, file)
+    }
+
+    /// scans the records of evtx chunks which have been carved out of `file`
+    #[cfg(feature = "scan_evtx")]
+    fn carve_evtx(&self, unpack: &mut UnpackContext, file: &Path) -> anyhow::Result<Vec<YaraFinding>> {
+        log::trace!("carving evtx chunks out of '{}'", file.display());
+        let chunks = EvtxChunkReader::carve(BufReader::new(File::open(file)?));
+        self.scan_evtx_chunks(unpack, chunks, file)
+    }
+
     #[cfg(feature = "scan_evtx")]
-    fn scan_evtx(
+    fn scan_evtx_chunks<R: Read + Seek>(
         &self,
-        scanner: &mut yara::Scanner,
+        unpack: &mut UnpackContext,
+        chunks: EvtxChunkReader<R>,
         file: &Path,
     ) -> anyhow::Result<Vec<YaraFinding>> {
-        log::trace!("scanning for IOCs inside evtx file '{}'", file.display());
         let filename = file.display().to_string();
+        let settings = Arc::new(ParserSettings::default());
 
         let mut results = Vec::new();
-        let mut parser = evtx::EvtxParser::from_path(file)?;
-        for result in parser.records_json_value() {
-            match result {
-                Err(why) => return Err(why.into()),
-                Ok(record) => {
-                    let res = Self::scan_json(scanner, &record.data, &filename)?;
-                    results.extend(
-                        res.into_iter()
-                            .map(|yr| yr.with_value_data(record.data.to_string())),
-                    );
+        let mut skipped_records = 0;
+        let mut damaged_chunks = 0;
+        let mut lost_records = 0;
+        let mut script_blocks = ScriptBlockAssembler::default();
+
+        for candidate in chunks {
+            let candidate = candidate?;
+            let offset = candidate.offset;
+            let mut chunk_data = match candidate.chunk {
+                Ok(chunk_data) => chunk_data,
+                Err(why) => {
+                    log::warn!("skipping damaged evtx chunk at offset 0x{offset:x} of '{filename}': {why}");
+                    damaged_chunks += 1;
+                    continue;
+                }
+            };
+            let expected_records = expected_records(&chunk_data);
+            let mut chunk = match chunk_data.parse(Arc::clone(&settings)) {
+                Ok(chunk) => chunk,
+                Err(why) => {
+                    log::warn!("skipping damaged evtx chunk at offset 0x{offset:x} of '{filename}': {why}");
+                    damaged_chunks += 1;
+                    lost_records += expected_records;
+                    continue;
+                }
+            };
+
+            let mut parsed_records = 0;
+            for record in chunk.iter() {
+                let prepared = match record {
+                    Ok(record) => self.prepare_evtx_record(record),
+                    Err(why) => Err(why),
+                };
+                match prepared {
+                    Err(why) => {
+                        log::warn!("skipping damaged evtx record in chunk at offset 0x{offset:x} of '{filename}': {why}");
+                        continue;
+                    }
+                    Ok(None) => skipped_records += 1,
+                    Ok(Some((info, data, xml))) => {
+                        // parts of larger PowerShell scripts are scanned like all other records,
+                        // and as a whole as soon as all parts have been found
+                        if let Some(fragment) = ScriptBlockFragment::from_record(&info, &data) {
+                            if let Some(script_block) = script_blocks.add(fragment) {
+                                results.extend(Self::scan_script_block(unpack.scanner, &script_block, &filename)?);
+                            }
+                        }
+
+                        let res = match (self.evtx_match_mode, xml) {
+                            (EvtxMatchMode::Field, _) => Self::scan_json(unpack.scanner, &data, "", &info, &filename)?,
+                            (EvtxMatchMode::Xml, Some(xml)) => Self::scan_string(unpack.scanner, &xml, &filename)?,
+                            _ => Self::scan_string(unpack.scanner, &data.to_string(), &filename)?,
+                        };
+                        results.extend(res.into_iter().map(|yr| match yr.evtx_record {
+                            Some(_) => yr,
+                            None => yr.with_evtx_record(info.clone()),
+                        }));
+                    }
                 }
+                parsed_records += 1;
             }
+
+            if parsed_records < expected_records {
+                damaged_chunks += 1;
+                lost_records += expected_records - parsed_records;
+            }
+        }
+
+        for script_block in script_blocks.finish() {
+            log::warn!(
+                "{} parts of script block {} in '{filename}' are missing",
+                script_block.missing_fragments,
+                script_block.record.script_block_id.as_deref().unwrap_or_default()
+            );
+            results.extend(Self::scan_script_block(unpack.scanner, &script_block, &filename)?);
+        }
+
+        if skipped_records > 0 {
+            log::info!("skipped {skipped_records} records of '{filename}' which do not match the evtx filter");
+        }
+        if damaged_chunks > 0 {
+            unpack.add_unscanned(
+                &ContainerPath::new(file),
+                format!("{lost_records} evtx records in {damaged_chunks} damaged chunks could not be parsed"),
+            );
         }
         Ok(results)
     }
 
+    /// scans a PowerShell script which has been reassembled from several records
+    #[cfg(feature = "scan_evtx")]
+    fn scan_script_block(
+        scanner: &mut yara::Scanner,
+        script_block: &ScriptBlock,
+        filename: &str,
+    ) -> anyhow::Result<Vec<YaraFinding>> {
+        Ok(Self::scan_string(scanner, &script_block.text, filename)?
+            .into_iter()
+            .map(|yr| yr.with_evtx_record(script_block.record.clone()))
+            .collect())
+    }
+
+    /// converts an evtx record, if it matches the evtx filter. The JSON representation
+    /// is always needed to extract the context of a record.
+    /// The error type is defined by the evtx crate
+    #[cfg(feature = "scan_evtx")]
+    #[allow(clippy::result_large_err)]
+    fn prepare_evtx_record(
+        &self,
+        record: EvtxRecord,
+    ) -> evtx::err::Result<Option<(EvtxRecordInfo, Value, Option<String>)>> {
+        // records which do not match the filter are skipped as early as possible
+        if !self.evtx_filter.matches_timestamp(&record.timestamp) {
+            return Ok(None);
+        }
+
+        let xml_record = match self.evtx_match_mode {
+            EvtxMatchMode::Xml => Some(record.clone()),
+            _ => None,
+        };
+        let record = record.into_json_value()?;
+        let info = EvtxRecordInfo::new(
+            record.event_record_id,
+            record.timestamp.to_rfc3339_opts(SecondsFormat::Micros, true),
+            &record.data,
+        );
+        if !self.evtx_filter.matches(&info) {
+            return Ok(None);
+        }
+
+        let xml = match xml_record {
+            Some(xml_record) => Some(xml_record.into_xml()?.data),
+            None => None,
+        };
+        Ok(Some((info, record.data, xml)))
+    }
+
+    /// scans every string value of an evtx record separately. `path` is the
+    /// path of `val` inside of the record, e.g. `Event.EventData`
     #[cfg(feature = "scan_evtx")]
     fn scan_json(
         scanner: &mut yara::Scanner,
         val: &Value,
+        path: &str,
+        record: &EvtxRecordInfo,
         filename: &str,
     ) -> anyhow::Result<Vec<YaraFinding>> {
         let mut results = Vec::new();
@@ -460,28 +780,37 @@ impl YaraScanner {
             Value::Bool(_) => Ok(vec![]),
             Value::Number(_) => Ok(vec![]),
             Value::String(s) => {
-                results.extend(Self::scan_string(scanner, s, filename)?);
+                results.extend(
+                    Self::scan_string(scanner, s, filename)?
+                        .into_iter()
+                        .map(|yr| {
+                            yr.with_value_data(s.clone())
+                                .with_evtx_record(record.clone().with_field(path.to_owned()))
+                        }),
+                );
                 Ok(results)
             }
             Value::Array(a) => {
-                for v in a.iter() {
-                    results.extend(Self::scan_json(scanner, v, filename)?);
+                for (idx, v) in a.iter().enumerate() {
+                    let path = format!("{path}[{idx}]");
+                    results.extend(Self::scan_json(scanner, v, &path, record, filename)?);
                 }
                 Ok(results)
             }
             Value::Object(o) => {
-                for (_n, v) in o.iter() {
-                    results.extend(Self::scan_json(scanner, v, filename)?);
+                for (n, v) in o.iter() {
+                    let path = if path.is_empty() { n.clone() } else { format!("{path}.{n}") };
+                    results.extend(Self::scan_json(scanner, v, &path, record, filename)?);
                 }
                 Ok(results)
             }
         }
     }
 
-    #[cfg(feature = "scan_evtx")]
+    #[cfg(any(feature = "scan_evtx", feature = "scan_reg"))]
     fn scan_string(
         scanner: &mut yara::Scanner,
-        s: &String,
+        s: &str,
         filename: &str,
     ) -> Result<Vec<YaraFinding>, yara::YaraError> {
         match scanner.scan_mem(s.as_bytes()) {
@@ -498,50 +827,62 @@ impl YaraScanner {
         &self,
         scanner: &mut yara::Scanner,
         mut hive: Hive<File, CleanHive>,
+        transaction_logs: &TransactionLogStatus,
         filename: &str,
     ) -> anyhow::Result<Vec<YaraFinding>> {
         let root_key = hive.root_key_node()?;
 
-        match Self::scan_key(scanner, &mut hive, &root_key, String::new(), filename) {
+        match Self::scan_key(scanner, &mut hive, &root_key, String::new(), transaction_logs, filename) {
             Err(why) => Err(why),
             Ok(results) => Ok(results),
         }
     }
 
+    #[cfg(feature = "scan_reg")]
     fn scan_key(
         scanner: &mut yara::Scanner,
         hive: &mut Hive<File, CleanHive>,
         key: &KeyNode,
         path: String,
+        transaction_logs: &TransactionLogStatus,
         filename: &str,
     ) -> anyhow::Result<Vec<YaraFinding>> {
         let mut results = Vec::new();
+        let entry = RegistryEntryInfo::new(
+            filename,
+            &path,
+            key.timestamp().to_rfc3339_opts(SecondsFormat::Micros, true),
+        )
+        .with_transaction_logs(transaction_logs.clone());
+        results.extend(Self::scan_registry_string(scanner, key.name(), &entry, filename)?);
+
         for v in key.values() {
+            let value_entry = entry.clone().with_value(v.name(), v.data_type().map(|t| t.to_string()));
+            if !v.name().is_empty() {
+                let name_entry = value_entry.clone().with_field(RegistryField::ValueName);
+                results.extend(Self::scan_registry_string(scanner, v.name(), &name_entry, filename)?);
+            }
+
             match v.value() {
                 nt_hive2::RegistryValue::RegSZ(s)
                 | nt_hive2::RegistryValue::RegExpandSZ(s)
                 | nt_hive2::RegistryValue::RegResourceList(s)
                 | nt_hive2::RegistryValue::RegFullResourceDescriptor(s)
                 | nt_hive2::RegistryValue::RegResourceRequirementsList(s) => {
-                    results.extend(
-                        Self::scan_string(scanner, s, filename)?
-                            .into_iter()
-                            .map(|r| r.with_value_data(Self::key_display(&path, v.name(), s))),
-                    );
+                    results.extend(Self::scan_registry_string(scanner, s, &value_entry, filename)?);
                 }
                 nt_hive2::RegistryValue::RegBinary(b) => {
-                    results.extend(scanner.scan_mem(&b[..])?.into_iter().map(|r| {
-                        YaraFinding::new(r, filename.to_string())
-                            .with_value_data(Self::key_display(&path, v.name(), "<binary data>"))
-                    }))
+                    results.extend(Self::scan_registry_binary(scanner, b, &value_entry, filename)?);
+                }
+                nt_hive2::RegistryValue::RegDWord(n) | nt_hive2::RegistryValue::RegDWordBigEndian(n) => {
+                    results.extend(Self::scan_registry_number(scanner, (*n).into(), &value_entry, filename)?);
+                }
+                nt_hive2::RegistryValue::RegQWord(n) => {
+                    results.extend(Self::scan_registry_number(scanner, *n, &value_entry, filename)?);
                 }
                 nt_hive2::RegistryValue::RegMultiSZ(sl) => {
                     for s in sl {
-                        results.extend(
-                            Self::scan_string(scanner, s, filename)?
-                                .into_iter()
-                                .map(|r| r.with_value_data(Self::key_display(&path, v.name(), s))),
-                        );
+                        results.extend(Self::scan_registry_string(scanner, s, &value_entry, filename)?);
                     }
                 }
                 _ => (),
@@ -549,66 +890,166 @@ impl YaraScanner {
         }
 
         for subkey in key.subkeys(hive)?.iter() {
-            let subkey_path = format!("{}/{}", path, subkey.borrow().name());
-            results.extend(Self::scan_key(
-                scanner,
-                hive,
-                &subkey.borrow(),
-                subkey_path,
-                filename,
-            )?);
+            let subkey = subkey.borrow();
+            let subkey_path = if path.is_empty() {
+                subkey.name().to_owned()
+            } else {
+                format!("{}\\{}", path, subkey.name())
+            };
+            results.extend(Self::scan_key(scanner, hive, &subkey, subkey_path, transaction_logs, filename)?);
         }
 
         Ok(results)
     }
 
-    fn key_display(path: &str, attr_name: &str, attr_value: &str) -> String {
-        format!("{}/@{} = '{}'", path, attr_name, attr_value)
-    }
-
-    fn get_filetype(&self, magic: Option<String>, file: &Path) -> FileType {
-        
-        if self.scan_compressed {
-            if let Some(m) = &magic {
-                if m.starts_with("XZ compressed data") {
-                    FileType::XZ
-                } else if m.starts_with("gzip compressed data") {
-                    FileType::GZip
-                } else if m.starts_with("bzip2 compressed data") {
-                    FileType::BZip2
-                } else if   m.starts_with("MS Windows Vista Event Log,") ||
-                            m.starts_with("MS Windows 10-11 Event Log") {
-                    FileType::Evtx
-                } else if m.starts_with("MS Windows registry file") {
-                    FileType::Reg
-                } else if m.starts_with("Zip archive data") {
-                    FileType::Zip
-                } else {
-                    if m.contains("compressed data") {
-                        log::warn!("unknown compression format: '{}', file will be handled without decompression", m);
+    /// scans keys and values which have been recovered from unallocated cells
+    #[cfg(feature = "scan_reg")]
+    fn scan_deleted_cells(scanner: &mut yara::Scanner, file: &Path) -> anyhow::Result<Vec<YaraFinding>> {
+        log::trace!("carving deleted cells out of '{}'", file.display());
+        let filename = file.to_string_lossy();
+        let mut results = Vec::new();
+
+        for cell in carve_deleted_cells(&std::fs::read(file)?) {
+            match cell.entry {
+                RecoveredEntry::Key { path, name, last_written } => {
+                    let entry = RegistryEntryInfo::new(
+                        &filename,
+                        &path,
+                        last_written.to_rfc3339_opts(SecondsFormat::Micros, true),
+                    )
+                    .with_recovered_cell(cell.offset);
+                    results.extend(Self::scan_registry_string(scanner, &name, &entry, &filename)?);
+                }
+                RecoveredEntry::Value { name, value_type, data } => {
+                    let entry = RegistryEntryInfo::new(&filename, "", String::new())
+                        .with_value(&name, value_type)
+                        .with_recovered_cell(cell.offset);
+                    if !name.is_empty() {
+                        let name_entry = entry.clone().with_field(RegistryField::ValueName);
+                        results.extend(Self::scan_registry_string(scanner, &name, &name_entry, &filename)?);
+                    }
+
+                    match data {
+                        RecoveredData::String(s) => {
+                            results.extend(Self::scan_registry_string(scanner, &s, &entry, &filename)?);
+                        }
+                        RecoveredData::MultiString(sl) => {
+                            for s in sl {
+                                results.extend(Self::scan_registry_string(scanner, &s, &entry, &filename)?);
+                            }
+                        }
+                        RecoveredData::Binary(b) => {
+                            results.extend(Self::scan_registry_binary(scanner, &b, &entry, &filename)?);
+                        }
+                        RecoveredData::Number(n) => {
+                            results.extend(Self::scan_registry_number(scanner, n, &entry, &filename)?);
+                        }
+                        RecoveredData::Other => (),
                     }
-                    FileType::Uncompressed
                 }
-            } else {
-                FileType::Uncompressed
             }
-        } else if let Some(m) = &magic {
-            if m.contains("compressed data") || m.contains("archive data") {
+        }
+        Ok(results)
+    }
+
+    /// scans binary data as it is, and every plausible decoding of it
+    #[cfg(feature = "scan_reg")]
+    fn scan_registry_binary(
+        scanner: &mut yara::Scanner,
+        data: &[u8],
+        entry: &RegistryEntryInfo,
+        filename: &str,
+    ) -> anyhow::Result<Vec<YaraFinding>> {
+        let mut results: Vec<_> = scanner
+            .scan_mem(data)?
+            .into_iter()
+            .map(|r| {
+                YaraFinding::new(r, filename.to_string())
+                    .with_value_data("<binary data>".to_owned())
+                    .with_registry_entry(entry.clone())
+            })
+            .collect();
+
+        for decoded in decode_binary_value(data) {
+            results.extend(Self::scan_decoded_value(scanner, &decoded, entry, filename)?);
+        }
+        Ok(results)
+    }
+
+    /// numeric values are scanned as decimal text
+    #[cfg(feature = "scan_reg")]
+    fn scan_registry_number(
+        scanner: &mut yara::Scanner,
+        number: u64,
+        entry: &RegistryEntryInfo,
+        filename: &str,
+    ) -> anyhow::Result<Vec<YaraFinding>> {
+        Self::scan_decoded_value(scanner, &decode_number(number), entry, filename)
+    }
+
+    #[cfg(feature = "scan_reg")]
+    fn scan_decoded_value(
+        scanner: &mut yara::Scanner,
+        decoded: &DecodedValue,
+        entry: &RegistryEntryInfo,
+        filename: &str,
+    ) -> anyhow::Result<Vec<YaraFinding>> {
+        let value_data = decoded.text().unwrap_or("<binary data>").to_owned();
+        let entry = entry.clone().with_decoding(decoded.decoding());
+        Ok(scanner
+            .scan_mem(&decoded.data)?
+            .into_iter()
+            .map(|r| {
+                YaraFinding::new(r, filename.to_string())
+                    .with_value_data(value_data.clone())
+                    .with_registry_entry(entry.clone())
+            })
+            .collect())
+    }
+
+    #[cfg(feature = "scan_reg")]
+    fn scan_registry_string(
+        scanner: &mut yara::Scanner,
+        data: &str,
+        entry: &RegistryEntryInfo,
+        filename: &str,
+    ) -> anyhow::Result<Vec<YaraFinding>> {
+        Ok(Self::scan_string(scanner, data, filename)?
+            .into_iter()
+            .map(|r| r.with_value_data(data.to_owned()).with_registry_entry(entry.clone()))
+            .collect())
+    }
+
+    fn get_filetype(&self, file_type: FileType, file: &Path) -> FileType {
+        if self.scan_compressed || matches!(file_type, FileType::Evtx | FileType::Reg) {
+            file_type
+        } else {
+            if file_type.is_compressed_stream() || file_type.is_archive() {
                 log::warn!("'{}' contains compressed data, but it will not be decompressed before the scan. Consider using the '-C' flag", file.display());
-                FileType::Uncompressed
-            } else if   m.starts_with("MS Windows Vista Event Log,") ||
-                        m.starts_with("MS Windows 10-11 Event Log") {
-                FileType::Evtx
-            } else if m.starts_with("MS Windows registry file") {
-                FileType::Reg
-            } else {
-                FileType::Uncompressed
             }
-        } else {
             FileType::Uncompressed
         }
     }
 
+    /// creates a reader which decompresses a compressed stream of type `file_type`
+    fn decoder<'r, R: Read + 'r>(file_type: &FileType, reader: R) -> std::io::Result<Box<dyn Read + 'r>> {
+        Ok(match file_type {
+            FileType::GZip => Box::new(GzDecoder::new(reader)),
+            FileType::BZip2 => Box::new(BzDecoder::new(reader)),
+            FileType::XZ => Box::new(XzDecoder::new(reader)),
+            FileType::Zstd => Box::new(zstd::stream::read::Decoder::new(reader)?),
+            FileType::Lz4 => Box::new(lz4_flex::frame::FrameDecoder::new(reader)),
+            FileType::Lzma => Box::new(XzDecoder::new_stream(reader, XzStream::new_lzma_decoder(u64::MAX)?)),
+            FileType::Compress => Box::new(LzwDecoder::new(reader)?),
+            _ => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!("{file_type:?} is not a compressed stream"),
+                ))
+            }
+        })
+    }
+
     fn scan_file(
         &self,
         scanner: &mut yara::Scanner<'_>,
@@ -625,84 +1066,364 @@ impl YaraScanner {
         }
     }
 
-    fn scan_compressed<R: Read>(
+    fn scan_compressed<'r, R: Read + 'r>(
         &self,
-        scanner: &mut yara::Scanner,
-        reader: R,
-        file_display_name: &str,
+        unpack: &mut UnpackContext,
+        mut reader: R,
+        location: &ContainerPath,
+        depth: usize,
     ) -> anyhow::Result<Vec<YaraFinding>> {
-        let (bytes, buffer) = self.read_into_buffer(reader)?;
+        let mut buffer = Vec::new();
+        let bytes = self.fill_window(&mut reader, &mut buffer)?;
 
-        if bytes == buffer.capacity() {
-            log::warn!("file '{file_display_name}' could not be decompressed completely")
+        if bytes == self.window_size() {
+            log::info!("'{location}' is larger than the decompression buffer, it will be scanned in windows");
+            return self.scan_stream(unpack, buffer, Box::new(reader), location, depth);
         } else if buffer.is_empty() {
-            log::warn!("uncompressed no bytes from '{}'", file_display_name);
+            log::warn!("uncompressed no bytes from '{}'", location);
         } else {
-            log::info!("uncompressed {bytes} bytes from '{file_display_name}'");
+            log::info!("uncompressed {bytes} bytes from '{location}'");
         }
 
-        match scanner.scan_mem(&buffer) {
+        self.scan_buffer(unpack, &buffer, location, depth)
+    }
+
+    /// scans decompressed data which does not fit into the decompression buffer.
+    /// Compressed streams and tar archives can be unpacked while reading, all
+    /// other data is scanned in overlapping windows.
+    fn scan_stream(
+        &self,
+        unpack: &mut UnpackContext,
+        first_window: Vec<u8>,
+        rest: Box<dyn Read + '_>,
+        location: &ContainerPath,
+        depth: usize,
+    ) -> anyhow::Result<Vec<YaraFinding>> {
+        let file_type = unpack.detector.detect_buffer(&first_window);
+
+        if !matches!(file_type, FileType::Uncompressed | FileType::Evtx | FileType::Reg) {
+            if depth < self.max_depth {
+                log::trace!("treating content of {location} as {file_type:?}");
+                let stream = Cursor::new(first_window).chain(rest);
+                if file_type.is_compressed_stream() {
+                    let decoder = Self::decoder(&file_type, stream)?;
+                    return self.scan_compressed(unpack, decoder, &location.decompressed(), depth + 1);
+                }
+                match file_type {
+                    FileType::Tar => return self.scan_tar_archive(unpack, stream, location, depth + 1),
+                    _ => {
+                        // zip and 7z archives require random access
+                        log::warn!("'{location}' is larger than the decompression buffer, it will be scanned without unpacking");
+                        let (first_window, rest) = stream.into_inner();
+                        return self.scan_windows(unpack, first_window.into_inner(), rest, location);
+                    }
+                }
+            } else {
+                log::warn!("'{location}' is nested too deeply, it will be scanned without unpacking");
+            }
+        }
+
+        self.scan_windows(unpack, first_window, rest, location)
+    }
+
+    /// scans data of any size with bounded memory. Every window overlaps with the
+    /// previous one, so that matches at the window borders are not missed.
+    /// Match offsets are relative to the beginning of the data. Conditions are
+    /// evaluated for every window, so yara's `filesize` is the size of the window.
+    /// The size of the complete data is provided in `total_size`, if it is known.
+    fn scan_windows(
+        &self,
+        unpack: &mut UnpackContext,
+        mut window: Vec<u8>,
+        mut rest: Box<dyn Read + '_>,
+        location: &ContainerPath,
+    ) -> anyhow::Result<Vec<YaraFinding>> {
+        let window_size = self.window_size();
+        let overlap = (1024 * self.window_overlap).min(window_size / 2);
+        let mut window_start = 0usize;
+        let mut findings: Vec<YaraFinding> = Vec::new();
+
+        // the hashes of the data are not known before all windows have been read
+        YaraExternals::default()
+            .with_unknown_content()
+            .with_total_size(location.content_size.map_or(-1, |size| size as i64))
+            .define_in_scanner(unpack.scanner)?;
+
+        let rules_using_filesize = self.references.rules_using_filesize();
+        if !rules_using_filesize.is_empty() {
+            log::warn!(
+                "'{location}' is scanned in windows, so `filesize` is the size of the window in the rules {}",
+                rules_using_filesize.join(", ")
+            );
+        }
+
+        loop {
+            // matches which lie completely inside the overlap have already
+            // been found in the previous window
+            let skip_below = if window_start == 0 { 0 } else { overlap };
+
+            for r in unpack.scanner.scan_mem(&window)? {
+                let mut finding = location.annotate(YaraFinding::new(r, location.to_string()));
+                finding.move_matches(window_start, skip_below);
+
+                match findings
+                    .iter_mut()
+                    .find(|f| f.identifier == finding.identifier && f.namespace == finding.namespace)
+                {
+                    Some(existing) => existing.merge_matches(finding),
+                    None => findings.push(finding),
+                }
+            }
+
+            if window.len() < window_size {
+                break;
+            }
+
+            let keep = window.split_off(window.len() - overlap);
+            window_start += window.len();
+            window = keep;
+            if self.fill_window(&mut rest, &mut window)? == 0 {
+                break;
+            }
+            log::trace!("scanning window at offset {window_start} of '{location}'");
+        }
+
+        log::info!(
+            "scanned {} bytes from '{location}'",
+            window_start + window.len()
+        );
+        Ok(findings)
+    }
+
+    /// scans decompressed data. If the data is another compressed stream or
+    /// an archive, it is unpacked and scanned recursively, until the maximum
+    /// nesting depth is reached.
+    fn scan_buffer(
+        &self,
+        unpack: &mut UnpackContext,
+        buffer: &[u8],
+        location: &ContainerPath,
+        depth: usize,
+    ) -> anyhow::Result<Vec<YaraFinding>> {
+        let file_type = unpack.detector.detect_buffer(buffer);
+
+        if !matches!(file_type, FileType::Uncompressed | FileType::Evtx | FileType::Reg) {
+            if depth < self.max_depth {
+                log::trace!("treating content of {location} as {file_type:?}");
+                let unpacked = match file_type {
+                    FileType::Zip => self.scan_zip_archive(unpack, Cursor::new(buffer), location, depth + 1),
+                    FileType::Tar => self.scan_tar_archive(unpack, buffer, location, depth + 1),
+                    FileType::SevenZip => self.scan_7z_archive(unpack, Cursor::new(buffer), location, depth + 1),
+                    _ => Self::decoder(&file_type, buffer)
+                        .map_err(anyhow::Error::from)
+                        .and_then(|decoder| self.scan_compressed(unpack, decoder, &location.decompressed(), depth + 1)),
+                };
+                match unpacked {
+                    Ok(findings) => return Ok(findings),
+                    Err(why) => unpack.add_unpack_failure(location, &file_type, why),
+                }
+            } else {
+                log::warn!("'{location}' is nested too deeply, it will be scanned without unpacking");
+            }
+        }
+
+        let externals = if self.references.uses_hashes() {
+            YaraExternals::default().with_content(buffer)
+        } else {
+            YaraExternals::default().with_content_size(buffer.len())
+        };
+        externals.define_in_scanner(unpack.scanner)?;
+
+        match unpack.scanner.scan_mem(buffer) {
             Err(why) => Err(why.into()),
             Ok(results) => Ok(results
                 .into_iter()
-                .map(|r| YaraFinding::new(r, file_display_name.to_owned()))
+                .map(|r| location.annotate(YaraFinding::new(r, location.to_string())))
                 .collect()),
         }
     }
 
-    fn scan_zip_archive(
+    fn scan_zip_archive<R: Read + Seek>(
         &self,
-        mut scanner: yara::Scanner,
-        reader: File,
-        zip_name: &str,
+        unpack: &mut UnpackContext,
+        reader: R,
+        location: &ContainerPath,
+        depth: usize,
     ) -> anyhow::Result<Vec<YaraFinding>> {
         let mut results = Vec::new();
 
-        if let Ok(mut zip) = zip::ZipArchive::new(reader) {
-            for i in 0..zip.len() {
-                let file = zip.by_index(i)?;
-                if file.is_file() {
-                    let filename = file.name().to_owned();
-                    let display_name = format!("{zip_name}:{filename}");
-                    scanner.define_variable("filename", &filename[..])?;
-
-                    match self.scan_compressed(&mut scanner, file, &display_name) {
-                        Ok(res) => {
-                            results
-                                .extend(res.into_iter().map(|r| r.with_contained_file(&filename)));
-                        }
-                        Err(why) => return Err(why),
+        let mut zip = zip::ZipArchive::new(reader).map_err(|why| anyhow!("invalid zip archive: {why}"))?;
+
+        for i in 0..zip.len() {
+            let (member, encrypted) = {
+                let file = zip.by_index_raw(i)?;
+                if !file.is_file() {
+                    continue;
+                }
+                let mut member = ArchiveMember::new(file.name().to_owned(), file.size());
+                member.mode = file.unix_mode();
+                member.modified = file.last_modified().map(|t| t.to_string());
+                (member, file.encrypted())
+            };
+            YaraExternals::default()
+                .with_archive_member(&member)
+                .define_in_scanner(unpack.scanner)?;
+            let member = location.member(member);
+
+            let password = if encrypted {
+                match self.find_zip_password(&mut zip, i) {
+                    Some(password) => Some(password),
+                    None => {
+                        unpack.add_unscanned(&member, self.no_matching_password());
+                        continue;
                     }
                 }
+            } else {
+                None
+            };
+
+            let file = match password {
+                None => zip.by_index(i),
+                Some(password) => zip.by_index_decrypt(i, password.as_bytes()),
+            };
+            let file = match file {
+                Ok(file) => file,
+                Err(why) => {
+                    unpack.add_unscanned(&member, why.to_string());
+                    continue;
+                }
+            };
+
+            match self.scan_compressed(unpack, file, &member, depth) {
+                Ok(findings) => results.extend(findings),
+                Err(why) => unpack.add_unscanned(&member, why.to_string()),
             }
         }
         Ok(results)
     }
 
+    /// the header of ZipCrypto entries verifies only one byte of the password,
+    /// so a password is accepted only if the decrypted entry has a valid checksum
+    fn find_zip_password<R: Read + Seek>(&self, zip: &mut zip::ZipArchive<R>, index: usize) -> Option<&String> {
+        self.passwords.iter().find(|password| match zip.by_index_decrypt(index, password.as_bytes()) {
+            Ok(mut file) => match std::io::copy(&mut file, &mut std::io::sink()) {
+                Ok(_) => true,
+                Err(why) => {
+                    log::trace!("decryption failed: {why}, trying the next password");
+                    false
+                }
+            },
+            Err(_) => false,
+        })
+    }
+
+    fn scan_tar_archive<R: Read>(
+        &self,
+        unpack: &mut UnpackContext,
+        reader: R,
+        location: &ContainerPath,
+        depth: usize,
+    ) -> anyhow::Result<Vec<YaraFinding>> {
+        let mut results = Vec::new();
+        let mut tar = TarReader::new(reader);
+        let mut is_first_member = true;
+
+        loop {
+            let tar_member = match tar.next_member() {
+                Ok(Some(tar_member)) => tar_member,
+                Ok(None) => break,
+                Err(why) if is_first_member => return Err(anyhow!("invalid tar archive: {why}")),
+                Err(why) => {
+                    // keep the findings of the members which could be read
+                    log::warn!("unable to read tar archive '{location}' completely: {why}");
+                    break;
+                }
+            };
+            is_first_member = false;
+
+            if tar_member.is_file() {
+                YaraExternals::default()
+                    .with_archive_member(&tar_member.member)
+                    .define_in_scanner(unpack.scanner)?;
+
+                // if the content of the member cannot be read, the reader skips
+                // its rest, so that the following members can still be scanned
+                let member = location.member(tar_member.member);
+                match self.scan_compressed(unpack, &mut tar, &member, depth) {
+                    Ok(findings) => results.extend(findings),
+                    Err(why) => unpack.add_unscanned(&member, why.to_string()),
+                }
+            }
+        }
+        Ok(results)
+    }
+
+    fn scan_7z_archive<R: Read + Seek>(
+        &self,
+        unpack: &mut UnpackContext,
+        reader: R,
+        location: &ContainerPath,
+        depth: usize,
+    ) -> anyhow::Result<Vec<YaraFinding>> {
+        let mut results = Vec::new();
+
+        let limit = self.window_size();
+        let mut archive = SevenZipArchive::new(reader, &self.passwords, limit)?;
+        archive.for_each_member(&self.passwords, limit, |member, data| {
+            let member = location.member(member.clone());
+            match data {
+                Ok(data) => {
+                    if let Some(archive_member) = &member.archive_member {
+                        YaraExternals::default()
+                            .with_archive_member(archive_member)
+                            .define_in_scanner(unpack.scanner)?;
+                    }
+                    match self.scan_buffer(unpack, data, &member, depth) {
+                        Ok(findings) => results.extend(findings),
+                        Err(why) => unpack.add_unscanned(&member, why.to_string()),
+                    }
+                }
+                Err(why) => unpack.add_unscanned(&member, why),
+            }
+            Ok(())
+        })?;
+        Ok(results)
+    }
+
+    fn no_matching_password(&self) -> String {
+        if self.passwords.is_empty() {
+            "the data is encrypted, but no password has been specified".to_owned()
+        } else {
+            "the data is encrypted, and none of the passwords is correct".to_owned()
+        }
+    }
+
     fn scan_hive_file(
         &self,
         file: &Path,
         scanner: &mut yara::Scanner,
     ) -> Result<Vec<YaraFinding>, anyhow::Error> {
-        let hive_file = File::open(file)?;
+        // transaction logs start with a base block as well, but cannot be parsed as a hive
+        if !is_primary_hive_file(file)? {
+            log::trace!(
+                "'{}' is no primary hive file, using the normal yara scanner",
+                file.display()
+            );
+            return self.scan_file(scanner, file);
+        }
 
-        let findings = match Hive::new(hive_file, HiveParseMode::NormalWithBaseBlock) {
-            Ok(hive) => {
-                let hive = hive.treat_hive_as_clean();
-                if hive.is_primary_file() {
-                    log::trace!(
-                        "scanning for IOCs inside registry hive file '{}'",
-                        file.display()
-                    );
+        let findings = match open_hive(file) {
+            Ok((hive, transaction_logs)) => {
+                log::trace!(
+                    "scanning for IOCs inside registry hive file '{}'",
+                    file.display()
+                );
 
-                    self.scan_reg(scanner, hive, &file.to_string_lossy())?
-                } else {
-                    log::trace!(
-                        "'{}' is no primary hive file, using the normal yara scanner",
-                        file.display()
-                    );
-                    self.scan_file(scanner, file)?
+                let mut findings = self.scan_reg(scanner, hive, &transaction_logs, &file.to_string_lossy())?;
+                if self.reg_carve {
+                    findings.extend(Self::scan_deleted_cells(scanner, file)?);
                 }
+                findings
             }
             Err(why) => {
                 log::error!(
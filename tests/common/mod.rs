@@ -1,5 +1,3 @@
-pub (crate) mod predicates;
-
 use std::{
     collections::HashSet,
     fs::File,
@@ -12,6 +10,7 @@ use serde_json::Value;
 use tempfile::tempdir;
 
 
+#[allow(dead_code)]
 pub fn data_path() -> PathBuf {
     PathBuf::from(env!("CARGO_MANIFEST_DIR"))
         .join("tests")
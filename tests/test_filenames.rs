@@ -2,10 +2,11 @@ use assert_cmd::Command;
 use common::{data_path};
 use predicates_core::{Predicate};
 
-use crate::common::predicates::json::*;
-use crate::common::predicates::DionysosPredicate;
+use crate::predicates::json::*;
+use crate::predicates::DionysosPredicate;
 
 mod common;
+mod predicates;
 macro_rules! json_format {
     () => (
         JsonFormatOutputPredicate::new($crate::vec::Vec::new())
@@ -30,6 +31,33 @@ fn test_suffix() {
     test_filename(r"\.txt$", json_format!("sample1.txt", "sample2.txt"));
 }
 
+#[test]
+fn test_registry_autostart() {
+    let hive = data_path().join("NTUSER.DAT");
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.arg("--path").arg(hive.display().to_string());
+    cmd.arg("--filename").arg(r"^OneDrive\.exe$");
+    cmd.arg("--reg");
+    cmd.arg("--format").arg("json");
+    let output = cmd.assert().success().get_output().stdout.clone();
+
+    // the hive itself does not match the pattern, but the file which is started by its Run key
+    let findings: Vec<serde_json::Value> = String::from_utf8(output)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(findings.len(), 1);
+    assert_eq!(
+        findings[0]["04_executed_file"],
+        "C:\\Users\\Administrator\\AppData\\Local\\Microsoft\\OneDrive\\OneDrive.exe"
+    );
+    let entry = &findings[0]["12_registry_entry"];
+    assert_eq!(entry["key_path"], "Software\\Microsoft\\Windows\\CurrentVersion\\Run");
+    assert_eq!(entry["value_name"], "OneDrive");
+    assert_eq!(entry["last_written"], "2022-06-17T21:51:38.152756Z");
+}
+
 fn test_filename<D, P>(pattern: &str, predicate: D) where D: DionysosPredicate<P>, P: Predicate<[u8]> {
     let data_path = data_path();
     let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
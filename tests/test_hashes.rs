@@ -1,5 +1,8 @@
+use std::io::Write;
+
 use common::{data_path, run_dionysos};
 use libdionysos::{Cli, OutputFormat};
+use serde_json::Value;
 
 use crate::common::filenames_from;
 
@@ -62,4 +65,30 @@ fn test_hash(hash: &str, expected_file: &str, format: OutputFormat) {
 
     assert_eq!(files.len(), 1);
     assert!(files.contains(expected_file));
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_hash_file() {
+    let mut hash_file = tempfile::Builder::new().suffix(".csv").tempfile().unwrap();
+    writeln!(hash_file, "# exported from the threat intel platform").unwrap();
+    writeln!(hash_file, "sha256,family,comment").unwrap();
+    writeln!(hash_file, "49bd6f1ad0ddd3763d1ce074b00804fd2d84433d90fbda287e62c38327cb67b7,Sample2,\"second sample, plain text\"").unwrap();
+
+    let cli = Cli::default()
+        .with_path(data_path().display().to_string())
+        .with_format(OutputFormat::Json)
+        .with_hash_file(hash_file.path().display().to_string())
+        .with_hash_csv_columns("sha256,family,comment");
+    let result = run_dionysos(cli);
+
+    let findings: Vec<Value> = result
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(findings.len(), 1);
+    assert_eq!(findings[0]["02_suspicious_file"], "sample2.txt");
+    let ioc = &findings[0]["04_ioc"];
+    assert_eq!(ioc["name"], "Sample2");
+    assert_eq!(ioc["description"], "second sample, plain text");
+    assert_eq!(ioc["source"], format!("{}:3", hash_file.path().display()));
+}
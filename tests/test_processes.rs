@@ -0,0 +1,46 @@
+#![cfg(target_os = "linux")]
+
+use std::path::PathBuf;
+use std::process::Command;
+
+use common::run_dionysos;
+use libdionysos::{Cli, OutputFormat};
+use serde_json::Value;
+
+mod common;
+
+#[test]
+fn test_yara_process() {
+    let yara_file = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("yara")
+        .join("process.yar");
+
+    // the shell keeps its command line in memory while it is waiting for `sleep`
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg("sleep 30; echo dionysos-process-marker-5c0e8a")
+        .spawn()
+        .unwrap();
+
+    let result = run_dionysos(
+        Cli::default()
+            .with_yara(yara_file.display().to_string())
+            .with_pid(child.id())
+            .with_format(OutputFormat::Json),
+    );
+    child.kill().unwrap();
+    child.wait().unwrap();
+
+    let findings: Vec<Value> = result
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(findings.len(), 1, "unexpected result: '{result}'");
+
+    let process = &findings[0]["09_process"];
+    assert_eq!(process["pid"], child.id());
+    assert_eq!(process["name"], "sh");
+    assert!(process["cmdline"].as_str().unwrap().contains("sleep 30"));
+    assert!(!process["regions"].as_array().unwrap().is_empty());
+}
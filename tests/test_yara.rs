@@ -1,8 +1,12 @@
 
+use std::collections::HashSet;
+use std::io::Write;
 use std::path::PathBuf;
 
 use common::{data_path, filenames_from, run_dionysos};
-use libdionysos::{Cli, OutputFormat};
+use libdionysos::{Cli, Dionysos, OutputFormat};
+use serde_json::Value;
+use tempfile::tempdir;
 
 mod common;
 
@@ -93,6 +97,114 @@ fn test_yara_with_evtx() {
     }
 }
 
+#[test]
+fn test_yara_evtx_record_info() {
+    let result = run_dionysos(prepare_cli("evtx.yar")
+        .with_format(OutputFormat::Json)
+        .with_yara_evtx(true));
+    let evtx_file = data_path().join("Security.evtx").display().to_string();
+
+    let findings: Vec<Value> = result
+        .lines()
+        .map(|line| serde_json::from_str::<Value>(line).unwrap())
+        .filter(|finding| finding["02_suspicious_file"] == evtx_file.as_str())
+        .collect();
+    assert!(!findings.is_empty());
+
+    for finding in findings {
+        let record = &finding["11_evtx_record"];
+        assert!(record["record_id"].as_u64().is_some());
+        assert!(record["timestamp"].as_str().unwrap().starts_with("2022-06-17T"));
+        assert_eq!(record["channel"], "Security");
+        assert_eq!(record["provider"], "Microsoft-Windows-Security-Auditing");
+        assert_eq!(record["computer"], "WinDevEval");
+        assert_eq!(record["event_id"], 4907);
+        assert_eq!(record["field"], "Event.EventData.ProcessName");
+        assert_eq!(finding["03_value"], "C:\\Windows\\System32\\poqexec.exe");
+    }
+}
+
+#[test]
+fn test_yara_evtx_match_mode() {
+    let evtx_file = data_path().join("Security.evtx").display().to_string();
+
+    for (mode, expect_match) in [("field", false), ("json", true), ("xml", true)] {
+        let result = run_dionysos(prepare_cli("evtx_record.yar")
+            .with_format(OutputFormat::Json)
+            .with_yara_evtx(true)
+            .with_evtx_match_mode(mode));
+
+        let findings: Vec<Value> = result
+            .lines()
+            .map(|line| serde_json::from_str::<Value>(line).unwrap())
+            .filter(|finding| finding["02_suspicious_file"] == evtx_file.as_str())
+            .collect();
+        assert_eq!(!findings.is_empty(), expect_match, "unexpected result in mode '{}'", mode);
+
+        for finding in findings {
+            assert_eq!(finding["11_evtx_record"]["event_id"], 4907);
+            assert_eq!(finding["11_evtx_record"]["field"], Value::Null);
+        }
+    }
+}
+
+#[test]
+fn test_yara_evtx_filter() {
+    let evtx_file = data_path().join("Security.evtx").display().to_string();
+    let cli = || prepare_cli("evtx.yar")
+        .with_format(OutputFormat::Csv)
+        .with_yara_evtx(true);
+
+    for (cli, expect_match) in [
+        (cli().with_evtx_channel("security").with_evtx_event_id(4907), true),
+        (cli().with_evtx_event_id(4624), false),
+        (cli().with_evtx_channel("Microsoft-Windows-Sysmon/Operational"), false),
+        (cli().with_evtx_time_range(Some("2022-06-17"), Some("2022-06-18T01:00:00+02:00")), true),
+        (cli().with_evtx_time_range(None, Some("2022-06-17 22:00:00")), false),
+    ] {
+        let detected_files = filenames_from(&OutputFormat::Csv)(run_dionysos(cli));
+        assert_eq!(detected_files.contains(&evtx_file), expect_match, "{:?}", detected_files);
+    }
+}
+
+#[test]
+fn test_yara_evtx_recovery() {
+    const CHUNK_SIZE: usize = 64 * 1024;
+    let evtx = std::fs::read(data_path().join("Security.evtx")).unwrap();
+    let dir = tempdir().unwrap();
+
+    // the first chunk is damaged and the last chunk is truncated
+    let mut damaged = evtx.clone();
+    damaged[4096..4104].copy_from_slice(b"XXXXXXXX");
+    damaged.truncate(4096 + CHUNK_SIZE + CHUNK_SIZE / 2);
+    let damaged_file = dir.path().join("damaged.evtx");
+    std::fs::write(&damaged_file, damaged).unwrap();
+
+    // the chunk which contains the records is hidden in unallocated space
+    let mut carved = vec![0xAAu8; 3 * 512];
+    carved.extend_from_slice(&evtx[4096 + CHUNK_SIZE..4096 + 2 * CHUNK_SIZE]);
+    carved.extend_from_slice(&[0xAAu8; 1000]);
+    let carved_file = dir.path().join("unallocated.bin");
+    std::fs::write(&carved_file, carved).unwrap();
+
+    let cli = || prepare_cli("evtx.yar")
+        .with_path(dir.path().display().to_string())
+        .with_format(OutputFormat::Csv)
+        .with_yara_evtx(true);
+
+    let damaged_file = damaged_file.display().to_string();
+    let carved_file = carved_file.display().to_string();
+    let matches = |result: &str, file: &str| result.lines().any(|l| l.contains(file) && l.contains("poqexec"));
+
+    let result = run_dionysos(cli());
+    assert!(matches(&result, &damaged_file), "{}", result);
+    assert!(!matches(&result, &carved_file), "{}", result);
+    assert!(result.contains("damaged chunks could not be parsed"), "{}", result);
+
+    let result = run_dionysos(cli().with_evtx_carve(true));
+    assert!(matches(&result, &carved_file), "{}", result);
+}
+
 #[test]
 fn test_yara_with_reg() {
     let format = OutputFormat::Json;
@@ -111,6 +223,102 @@ fn test_yara_with_reg() {
     }
 }
 
+#[test]
+fn test_yara_reg_entries() {
+    let hive = data_path().join("NTUSER.DAT").display().to_string();
+    let entries = |yara_file: &str| -> Vec<Value> {
+        run_dionysos(prepare_cli(yara_file)
+            .with_path(hive.clone())
+            .with_format(OutputFormat::Json)
+            .with_yara_reg(true))
+        .lines()
+        .map(|line| serde_json::from_str::<Value>(line).unwrap()["12_registry_entry"].clone())
+        .collect()
+    };
+
+    let entries_of_value = entries("reg.yar");
+    assert!(entries_of_value.iter().all(|e| e["hive"] == hive.as_str() && e["last_written"].is_string()));
+    assert!(entries_of_value.iter().any(|e| e["field"] == "value name"
+        && e["value_name"] == "OneDrive.exe"
+        && e["value_type"] == "RegDWord"));
+    assert!(entries_of_value.iter().any(|e| e["field"] == "value data"
+        && e["key_path"] == "Software\\Microsoft\\OneDrive"
+        && e["value_name"] == "OneDriveTrigger"));
+
+    let entries_of_key = entries("reg_names.yar");
+    assert_eq!(entries_of_key.len(), 1);
+    assert_eq!(entries_of_key[0]["field"], "key name");
+    assert_eq!(entries_of_key[0]["key_path"], "Environment");
+    assert_eq!(entries_of_key[0]["last_written"], "2022-06-17T22:21:13.427624Z");
+    assert_eq!(entries_of_key[0]["transaction_logs"], "not needed");
+}
+
+#[test]
+fn test_yara_reg_decoded_values() {
+    let hive = data_path().join("NTUSER.DAT").display().to_string();
+    let findings: Vec<Value> = run_dionysos(prepare_cli("reg_decoded.yar")
+        .with_path(hive)
+        .with_format(OutputFormat::Json)
+        .with_yara_reg(true))
+    .lines()
+    .map(|line| serde_json::from_str::<Value>(line).unwrap())
+    .collect();
+
+    // the path is stored as UTF-16LE text in a binary value
+    assert!(findings.iter().any(|f| f["12_registry_entry"]["value_type"] == "RegBinary"
+        && f["12_registry_entry"]["decoding"] == "UTF-16LE"));
+
+    let numbers: Vec<_> = findings.iter()
+        .filter(|f| f["12_registry_entry"]["decoding"] == "decimal")
+        .collect();
+    assert_eq!(numbers.len(), 1);
+    assert_eq!(numbers[0]["12_registry_entry"]["key_path"], "Console");
+    assert_eq!(numbers[0]["12_registry_entry"]["value_name"], "ScreenBufferSize");
+    assert_eq!(numbers[0]["03_value"], "589889656");
+}
+
+#[test]
+fn test_yara_reg_carve() {
+    let hive = data_path().join("NTUSER.DAT").display().to_string();
+    let entries = |reg_carve: bool| -> Vec<Value> {
+        run_dionysos(prepare_cli("deleted_key.yar")
+            .with_path(hive.clone())
+            .with_format(OutputFormat::Json)
+            .with_yara_reg(true)
+            .with_reg_carve(reg_carve))
+        .lines()
+        .map(|line| serde_json::from_str::<Value>(line).unwrap()["12_registry_entry"].clone())
+        .collect()
+    };
+
+    // the key has been deleted, so it can only be found in unallocated cells
+    assert!(entries(false).is_empty());
+
+    let recovered = entries(true);
+    assert_eq!(recovered.len(), 1);
+    assert_eq!(recovered[0]["recovered"], true);
+    assert_eq!(recovered[0]["cell_offset"], 0xce088);
+    assert_eq!(recovered[0]["key_path"], "Software\\Microsoft\\Windows\\CurrentVersion\\App Paths\\winget.exe");
+}
+
+#[test]
+fn test_yara_dirty_hive() {
+    let hive = data_path().join("NewDirtyHive").display().to_string();
+    let result = run_dionysos(prepare_cli("dirty_hive.yar")
+        .with_path(hive)
+        .with_format(OutputFormat::Json)
+        .with_yara_reg(true));
+    let entries: Vec<_> = result
+        .lines()
+        .map(|line| serde_json::from_str::<Value>(line).unwrap()["12_registry_entry"].clone())
+        .collect();
+
+    // the key has only been written to the transaction logs
+    assert_eq!(entries.len(), 1);
+    assert!(entries[0]["key_path"].as_str().unwrap().ends_with("Key3_1"));
+    assert_eq!(entries[0]["transaction_logs"], "applied (NewDirtyHive.LOG1, NewDirtyHive.LOG2)");
+}
+
 #[test]
 fn test_yara_txt() {
     let result = run_dionysos(prepare_cli("sample1.yar").with_format(OutputFormat::Txt));
@@ -126,6 +334,379 @@ fn test_yara_txt() {
     }
 }
 
+#[test]
+fn test_yara_nested_archives() {
+    let nested = |depth| {
+        let result = run_dionysos(prepare_cli("sample1.yar")
+            .with_format(OutputFormat::Csv)
+            .with_scan_compressed(true)
+            .with_nesting_depth(depth));
+        filenames_from(&OutputFormat::Csv)(result)
+    };
+    let data_path = data_path();
+    let inner_zip = data_path.join("nested.zip:inner.zip:sample1.txt").display().to_string();
+    let inner_gz = data_path.join("nested.zip:logs/messages.gz").display().to_string();
+
+    let detected_files = nested(5);
+    assert!(detected_files.contains(&inner_zip), "{} not found in {:?}", inner_zip, detected_files);
+    assert!(detected_files.contains(&inner_gz), "{} not found in {:?}", inner_gz, detected_files);
+
+    let detected_files = nested(1);
+    assert!(!detected_files.contains(&inner_zip));
+    assert!(!detected_files.contains(&inner_gz));
+}
+
+#[test]
+fn test_yara_compressed_streams() {
+    let result = run_dionysos(prepare_cli("sample1.yar")
+        .with_format(OutputFormat::Csv)
+        .with_scan_compressed(true));
+    let detected_files = filenames_from(&OutputFormat::Csv)(result);
+
+    for file in ["messages.zst", "messages.lz4", "messages.lzma", "messages.Z"] {
+        let file = data_path().join(file).display().to_string();
+        assert!(detected_files.contains(&file), "{} not found in {:?}", file, detected_files);
+    }
+}
+
+#[test]
+fn test_yara_file_type_signatures() {
+    let scan = |detection| {
+        let result = run_dionysos(prepare_cli("sample1.yar")
+            .with_format(OutputFormat::Csv)
+            .with_scan_compressed(true)
+            .with_file_type_detection(detection));
+        filenames_from(&OutputFormat::Csv)(result)
+    };
+
+    // the built-in signatures must detect all formats which libmagic detects
+    let with_magic = scan("magic");
+    let with_signatures = scan("signatures");
+    assert_eq!(with_magic, with_signatures);
+    assert!(with_signatures.contains(&data_path().join("triage.tar.gz:var/log/syslog").display().to_string()));
+}
+
+#[test]
+fn test_yara_externals() {
+    let mut externals_file = tempfile::NamedTempFile::new().unwrap();
+    writeln!(externals_file, "# maximum age in days\nmax_age=30").unwrap();
+
+    let result = run_dionysos(prepare_cli("externals.yar")
+        .with_format(OutputFormat::Csv)
+        .with_scan_compressed(true)
+        .with_yara_external("environment=lab")
+        .with_yara_externals_file(externals_file.path().display().to_string()));
+    let detected_files = filenames_from(&OutputFormat::Csv)(result);
+
+    // hashes of compressed files refer to the decompressed data
+    for file in ["sample2.txt", "sample2.txt.gz", "triage.tar.gz:var/log/syslog"] {
+        let file = data_path().join(file).display().to_string();
+        assert!(detected_files.contains(&file), "{} not found in {:?}", file, detected_files);
+    }
+
+    let archive = data_path().join("triage.tar.gz").display().to_string();
+    assert_eq!(detected_files.iter().filter(|f| f.starts_with(&archive)).count(), 1);
+    assert!(!detected_files.contains(&data_path().join("sample1.txt").display().to_string()));
+}
+
+#[test]
+fn test_yara_tar_archive() {
+    let result = run_dionysos(prepare_cli("sample1.yar")
+        .with_format(OutputFormat::Json)
+        .with_scan_compressed(true));
+    let archive = data_path().join("triage.tar.gz").display().to_string();
+
+    let members: HashSet<String> = result
+        .lines()
+        .map(|line| serde_json::from_str::<Value>(line).unwrap())
+        .filter(|finding| finding["02_suspicious_file"].as_str().unwrap().starts_with(&archive))
+        .map(|finding| {
+            let member = &finding["10_archive_member"];
+            assert_eq!(member["path"], finding["05_contained_file"]);
+            assert_eq!(member["mode"], "0640");
+            assert_eq!(member["owner"], "syslog");
+            assert_eq!(member["group"], "adm");
+            assert_eq!(member["uid"], 1000);
+            assert_eq!(member["modified"], "2023-11-14 22:13:20");
+            member["path"].as_str().unwrap().to_owned()
+        })
+        .collect();
+    assert_eq!(
+        members,
+        HashSet::from(["var/log/syslog".to_owned(), "var/log/apt/history.log".to_owned()])
+    );
+}
+
+#[test]
+fn test_yara_7z_archive() {
+    let result = run_dionysos(prepare_cli("sample1.yar")
+        .with_format(OutputFormat::Csv)
+        .with_scan_compressed(true));
+    let detected_files = filenames_from(&OutputFormat::Csv)(result);
+
+    for file in ["solid.7z:docs/readme.md", "solid.7z:bin/tool.exe"] {
+        let file = data_path().join(file).display().to_string();
+        assert!(detected_files.contains(&file), "{} not found in {:?}", file, detected_files);
+    }
+}
+
+/// scans the damaged archives, which are kept apart, so that the other
+/// tests can expect all files to be scanned
+fn scan_damaged_archives() -> (PathBuf, Vec<Value>) {
+    let damaged_path = data_path().parent().unwrap().join("damaged");
+    let result = run_dionysos(prepare_cli("sample1.yar")
+        .with_path(damaged_path.display().to_string())
+        .with_format(OutputFormat::Json)
+        .with_scan_compressed(true));
+    let findings = result
+        .lines()
+        .map(|line| serde_json::from_str::<Value>(line).unwrap())
+        .collect();
+    (damaged_path, findings)
+}
+
+fn unscanned_reason(damaged_path: &std::path::Path, findings: &[Value], file: &str) -> Option<String> {
+    let file = damaged_path.join(file).display().to_string();
+    let finding = findings
+        .iter()
+        .find(|f| f["02_suspicious_file"] == file.as_str())
+        .unwrap_or_else(|| panic!("{} not found", file));
+    finding["03_unscanned_reason"].as_str().map(str::to_owned)
+}
+
+#[test]
+fn test_yara_truncated_7z_archive() {
+    let (damaged_path, findings) = scan_damaged_archives();
+
+    // the first member is complete, the second one must not be reported as scanned
+    assert!(unscanned_reason(&damaged_path, &findings, "truncated.7z:first.txt").is_none());
+    let reason = unscanned_reason(&damaged_path, &findings, "truncated.7z:second.txt").unwrap();
+    assert!(reason.contains("only 17 of 42 bytes"), "{}", reason);
+}
+
+#[test]
+fn test_yara_corrupt_tar_member() {
+    let (damaged_path, findings) = scan_damaged_archives();
+
+    // a corrupt member must not hide the findings of the other members
+    assert!(unscanned_reason(&damaged_path, &findings, "corrupt_member.tar:logs/first.txt").is_none());
+    assert!(unscanned_reason(&damaged_path, &findings, "corrupt_member.tar:logs/broken.gz").is_some());
+    assert!(unscanned_reason(&damaged_path, &findings, "corrupt_member.tar:logs/last.txt").is_none());
+}
+
+#[test]
+fn test_yara_unpack_failure() {
+    let (damaged_path, findings) = scan_damaged_archives();
+    let file = damaged_path.join("misdetected.tar:notes.bin").display().to_string();
+    let findings: Vec<&Value> = findings
+        .iter()
+        .filter(|f| f["02_suspicious_file"] == file.as_str())
+        .collect();
+
+    // data which looks like gzip, but cannot be decompressed, must be scanned as it is
+    assert!(findings.iter().any(|f| f["01_scanner"] == "yara" && f["03_unscanned_reason"].is_null()));
+    assert!(findings.iter().any(|f| f["03_unscanned_reason"]
+        .as_str()
+        .is_some_and(|reason| reason.starts_with("unable to unpack gzip compressed data"))));
+}
+
+#[test]
+fn test_yara_encrypted_archives() {
+    let scan = |cli: Cli| -> HashSet<(String, bool)> {
+        let result = run_dionysos(cli.with_format(OutputFormat::Json).with_scan_compressed(true));
+        result
+            .lines()
+            .map(|line| serde_json::from_str::<Value>(line).unwrap())
+            .map(|finding| (
+                finding["02_suspicious_file"].as_str().unwrap().to_owned(),
+                finding["03_unscanned_reason"].is_string()))
+            .collect()
+    };
+    let encrypted = ["protected.zip:secret.bin", "protected.7z:secret.bin", "hidden.7z"];
+
+    // without passwords, the encrypted entries must be reported
+    let findings = scan(prepare_cli("sample1.yar"));
+    for file in encrypted.iter() {
+        let file = data_path().join(file).display().to_string();
+        assert!(findings.contains(&(file.clone(), true)), "{} not reported in {:?}", file, findings);
+    }
+
+    // "wrong356" passes the password check of the ZipCrypto header of protected.zip
+    let findings = scan(prepare_cli("sample1.yar")
+        .with_password("wrong")
+        .with_password("wrong356")
+        .with_password("infected"));
+    for file in ["protected.zip:secret.bin", "protected.7z:secret.bin", "hidden.7z:secret.bin"].iter() {
+        let file = data_path().join(file).display().to_string();
+        assert!(findings.contains(&(file.clone(), false)), "{} not found in {:?}", file, findings);
+    }
+    assert!(findings.iter().all(|(_, unscanned)| !unscanned));
+}
+
+#[test]
+fn test_yara_scan_windows() {
+    const MIB: u64 = 1024 * 1024;
+    let result = run_dionysos(prepare_cli("sample1.yar")
+        .with_format(OutputFormat::Json)
+        .with_scan_compressed(true)
+        .with_decompression_buffer_size(1)
+        .with_window_overlap(4));
+    let file = data_path().join("large.log.gz").display().to_string();
+
+    let findings: Vec<Value> = result
+        .lines()
+        .map(|line| serde_json::from_str::<Value>(line).unwrap())
+        .filter(|finding| finding["02_suspicious_file"] == file.as_str())
+        .collect();
+    assert_eq!(findings.len(), 1);
+
+    let offsets: HashSet<u64> = findings[0]["04_strings"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .flat_map(|s| s["matches"].as_array().unwrap().iter())
+        .map(|m| m["offset"].as_u64().unwrap())
+        .collect();
+    assert_eq!(offsets, HashSet::from([MIB - 2, 3 * MIB + 107]));
+}
+
+#[test]
+fn test_yara_total_size() {
+    let result = run_dionysos(prepare_cli("total_size.yar")
+        .with_format(OutputFormat::Csv)
+        .with_scan_compressed(true)
+        .with_password("infected")
+        .with_decompression_buffer_size(1));
+    let detected_files = filenames_from(&OutputFormat::Csv)(result);
+
+    // the size of a decompressed stream is only known if it fits into the buffer
+    let file = data_path().join("large.log.gz").display().to_string();
+    assert_eq!(detected_files, HashSet::from([file]));
+}
+
+#[test]
+fn test_yara_rule_metadata() {
+    let result = run_dionysos(prepare_cli("sample1.yar").with_format(OutputFormat::Json));
+
+    let mut lines = result.lines().peekable();
+    assert!(lines.peek().is_some());
+    for line in lines {
+        let finding: Value = serde_json::from_str(line).unwrap();
+        assert_eq!(finding["06_namespace"], "sample1.yar");
+        assert_eq!(finding["07_tags"], serde_json::json!(["sample"]));
+        assert_eq!(finding["08_metadata"]["description"], "matches the sample files");
+        assert_eq!(finding["08_metadata"]["score"], 50);
+    }
+}
+
+#[test]
+fn test_yara_namespaces() {
+    let result = run_dionysos(prepare_cli("namespaces").with_format(OutputFormat::Json));
+    let sample = data_path().join("sample1.txt").display().to_string();
+
+    let namespaces: HashSet<String> = result
+        .lines()
+        .map(|line| serde_json::from_str::<Value>(line).unwrap())
+        .filter(|finding| finding["02_suspicious_file"] == sample.as_str())
+        .map(|finding| finding["06_namespace"].as_str().unwrap().to_owned())
+        .collect();
+    assert_eq!(
+        namespaces,
+        HashSet::from(["vendor_a/world.yar".to_owned(), "vendor_b/world.yar".to_owned()])
+    );
+}
+
+#[test]
+fn test_yara_rule_filter() {
+    let cases = [
+        (prepare_cli("sample1.yar").with_yara_include_tag("sample"), true),
+        (prepare_cli("sample1.yar").with_yara_include_tag("webshell"), false),
+        (prepare_cli("sample1.yar").with_yara_include_meta("score>=50"), true),
+        (prepare_cli("sample1.yar").with_yara_include_meta("score>=70"), false),
+        (prepare_cli("sample1.yar").with_yara_exclude_rule("wor*"), false),
+        (prepare_cli("sample1.yar").with_yara_exclude_rule("/^hello$/"), true),
+    ];
+    for (cli, has_findings) in cases {
+        let result = run_dionysos(cli.with_format(OutputFormat::Json));
+        assert_eq!(!result.is_empty(), has_findings, "unexpected result: '{result}'");
+    }
+}
+
+#[test]
+fn test_yara_cache() {
+    let cache_dir = tempdir().unwrap();
+    let cache_file = cache_dir.path().join("rules.cache");
+    let other_cache_file = cache_dir.path().join("other.cache");
+    let format = OutputFormat::Csv;
+    let extract_filenames = filenames_from(&format);
+    let data_path = data_path();
+    let cached_cli = || prepare_cli("sample1.yar").with_yara_cache(cache_file.display().to_string());
+
+    // the first run compiles the rules and stores them in the cache
+    run_dionysos(cached_cli());
+    run_dionysos(prepare_cli("process.yar").with_yara_cache(other_cache_file.display().to_string()));
+
+    // replace the cached rules by rules which match nothing, but keep the cache
+    // key. If the next run used the cache, it must not find anything
+    // the header consists of the magic line and the cache key
+    let header_len = |content: &[u8]| content.iter().enumerate().filter(|(_, b)| **b == b'\n').nth(1).unwrap().0 + 1;
+    let cached = std::fs::read(&cache_file).unwrap();
+    let other = std::fs::read(&other_cache_file).unwrap();
+    let mut replaced = cached[..header_len(&cached)].to_vec();
+    replaced.extend_from_slice(&other[header_len(&other)..]);
+    std::fs::write(&cache_file, &replaced).unwrap();
+
+    let result = run_dionysos(cached_cli().with_format(format.clone()));
+    assert!(extract_filenames(result).is_empty());
+    assert_eq!(std::fs::read(&cache_file).unwrap(), replaced);
+
+    // the original cache file can also be used as ruleset
+    std::fs::write(&cache_file, &cached).unwrap();
+    let result = run_dionysos(prepare_cli("sample1.yar").with_yara(cache_file.display().to_string()).with_format(format));
+    let detected_files = extract_filenames(result);
+    for file in UNCOMPRESSED_EXPECTED_FILES.iter() {
+        let file = data_path.join(file);
+        assert!(detected_files.contains(& file.display().to_string()), "file {} not found in {:?}", file.display(), detected_files);
+    }
+    cache_dir.close().unwrap();
+}
+
+#[test]
+fn test_yara_compiled_by_yarac() {
+    // sample1.yarc has been compiled with `yarac sample1.yar sample1.yarc`, so it
+    // does not declare the external variables which are set by dionysos
+    let format = OutputFormat::Csv;
+    let extract_filenames = filenames_from(&format);
+    let result = run_dionysos(prepare_cli("sample1.yarc").with_format(format));
+    let data_path = data_path();
+
+    let detected_files = extract_filenames(result);
+    for file in UNCOMPRESSED_EXPECTED_FILES.iter() {
+        let file = data_path.join(file);
+        assert!(detected_files.contains(& file.display().to_string()), "file {} not found in {:?}", file.display(), detected_files);
+    }
+}
+
+#[test]
+fn test_yara_skip_broken_files() {
+    let format = OutputFormat::Csv;
+    let extract_filenames = filenames_from(&format);
+    let result = run_dionysos(prepare_cli("mixed").with_format(format));
+    let data_path = data_path();
+
+    let detected_files = extract_filenames(result);
+    for file in UNCOMPRESSED_EXPECTED_FILES.iter() {
+        let file = data_path.join(file);
+        assert!(detected_files.contains(& file.display().to_string()), "file {} not found in {:?}", file.display(), detected_files);
+    }
+}
+
+#[test]
+fn test_yara_strict() {
+    let dionysos = Dionysos::new(prepare_cli("mixed").with_yara_strict(true)).unwrap();
+    assert!(dionysos.run().is_err());
+}
+
 fn prepare_cli(yara_file: &str) -> Cli {
     let yara_file = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
         .join("tests")